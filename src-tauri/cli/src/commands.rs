@@ -0,0 +1,326 @@
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use nowork_core::{
+    error::NoWorkError,
+    query::run_query,
+    tags,
+    tasks::{self, load_task, TaskView},
+    types::{NewTag, NewTask, Tag, Task, TaskQuery},
+};
+use rusqlite::Connection;
+
+use crate::{Priority, View};
+
+pub struct ListFilter<'a> {
+    pub view: View,
+    pub tags: &'a [String],
+    pub priorities: &'a [Priority],
+    pub overdue: bool,
+    pub done: bool,
+    pub limit: Option<u32>,
+}
+
+// today, tomorrow, a local date (midnight) or date-time, or RFC 3339
+fn parse_due(input: &str) -> Result<DateTime<Utc>, NoWorkError> {
+    let today = Local::now().date_naive();
+    let local = |naive: NaiveDateTime| Local.from_local_datetime(&naive).earliest().map(|dt| dt.with_timezone(&Utc));
+
+    let input = input.trim();
+    let due = if input.eq_ignore_ascii_case("today") {
+        local(today.and_hms_opt(0, 0, 0).unwrap())
+    } else if input.eq_ignore_ascii_case("tomorrow") {
+        local((today + Duration::days(1)).and_hms_opt(0, 0, 0).unwrap())
+    } else {
+        DateTime::parse_from_rfc3339(input)
+            .map(|dt| dt.with_timezone(&Utc))
+            .ok()
+            .or_else(|| NaiveDateTime::parse_from_str(input, "%Y-%m-%dT%H:%M").ok().and_then(local))
+            .or_else(|| NaiveDateTime::parse_from_str(input, "%Y-%m-%d %H:%M").ok().and_then(local))
+            .or_else(|| {
+                NaiveDate::parse_from_str(input, "%Y-%m-%d")
+                    .ok()
+                    .and_then(|date| local(date.and_hms_opt(0, 0, 0).unwrap()))
+            })
+    };
+    due.ok_or_else(|| NoWorkError::validation(format!("Not a due date: {}", input)))
+}
+
+// exact name first, then ignoring case
+fn find_tag<'a>(all: &'a [Tag], name: &str) -> Option<&'a Tag> {
+    all.iter()
+        .find(|tag| tag.name == name)
+        .or_else(|| all.iter().find(|tag| tag.name.eq_ignore_ascii_case(name)))
+}
+
+pub fn add(
+    conn: &mut Connection,
+    name: String,
+    due: Option<&str>,
+    priority: Option<Priority>,
+    tag_names: &[String],
+) -> Result<Task, NoWorkError> {
+    if name.trim().is_empty() {
+        return Err(NoWorkError::validation("The task needs a name"));
+    }
+
+    // existing tags keep their color, add_task would overwrite it
+    let existing = tags::list_tags(conn)?;
+    let new_tags = tag_names
+        .iter()
+        .map(|name| match find_tag(&existing, name) {
+            Some(tag) => NewTag {
+                name: tag.name.clone(),
+                color: tag.color.clone(),
+            },
+            None => NewTag {
+                name: name.clone(),
+                color: "default".to_string(),
+            },
+        })
+        .collect();
+
+    let task = NewTask {
+        name: name.trim().to_string(),
+        due_date: due.map(parse_due).transpose()?,
+        priority: priority.map(Into::into),
+        tags: Some(new_tags),
+        recurrence: None,
+    };
+    let task_id = tasks::add_task(conn, task)?;
+    load_task(conn, task_id)
+}
+
+pub fn list(conn: &Connection, filter: ListFilter) -> Result<Vec<Task>, NoWorkError> {
+    let view = match filter.view {
+        View::All => TaskView::All,
+        View::Incomplete => TaskView::Incomplete,
+        View::Today => TaskView::DueToday,
+        View::Week => TaskView::DueThisWeek,
+    };
+
+    let existing = tags::list_tags(conn)?;
+    let tag_ids = filter
+        .tags
+        .iter()
+        .map(|name| find_tag(&existing, name).map(|tag| tag.id).ok_or(NoWorkError::TagNotFound))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut query: TaskQuery = view.query();
+    query.tags.all = tag_ids;
+    query.priorities = filter.priorities.iter().map(|p| Some((*p).into())).collect();
+    query.limit = filter.limit;
+    if filter.overdue {
+        query.overdue = Some(true);
+    }
+    if filter.done {
+        query.completed = Some(true);
+    }
+
+    Ok(run_query(conn, &query)?.tasks)
+}
+
+pub fn done(conn: &mut Connection, ids: &[i32], subtasks: bool) -> Result<Vec<Task>, NoWorkError> {
+    ids.iter()
+        .map(|&id| {
+            // completing a missing task is not an error in the core
+            load_task(conn, id)?;
+            tasks::complete_task(conn, id, subtasks)?;
+            load_task(conn, id)
+        })
+        .collect()
+}
+
+// Checks every id before deleting any
+pub fn remove(conn: &mut Connection, ids: &[i32]) -> Result<(), NoWorkError> {
+    for &id in ids {
+        load_task(conn, id)?;
+    }
+
+    let tx = conn.transaction()?;
+    for &id in ids {
+        tasks::delete_task(&tx, id)?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+pub fn tag(conn: &mut Connection, task_id: i32, tag_names: &[String], remove: bool) -> Result<Task, NoWorkError> {
+    load_task(conn, task_id)?;
+
+    let tx = conn.transaction()?;
+    let existing = tags::list_tags(&tx)?;
+    for name in tag_names {
+        let tag_id = match (find_tag(&existing, name), remove) {
+            (Some(tag), _) => tag.id,
+            (None, true) => return Err(NoWorkError::TagNotFound),
+            (None, false) => {
+                let new_tag = NewTag {
+                    name: name.clone(),
+                    color: "default".to_string(),
+                };
+                tags::add_tag(&tx, new_tag)?.id
+            }
+        };
+
+        if remove {
+            tags::remove_tag_from_task(&tx, task_id, tag_id as i32)?;
+        } else {
+            tags::add_tag_to_task(&tx, task_id, tag_id as i32)?;
+        }
+    }
+    tx.commit()?;
+
+    load_task(conn, task_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nowork_core::migrations;
+    use chrono::Datelike;
+    use std::path::Path;
+
+    fn database() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "foreign_keys", true).unwrap();
+        migrations::migrate(&mut conn, Path::new("unused")).unwrap();
+        conn
+    }
+
+    fn local(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Local
+            .with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .earliest()
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn names(tasks: &[Task]) -> Vec<&str> {
+        let mut names: Vec<&str> = tasks.iter().map(|task| task.name.as_str()).collect();
+        names.sort_unstable();
+        names
+    }
+
+    fn tag_names(task: &Task) -> Vec<&str> {
+        task.tags.iter().flatten().map(|tag| tag.name.as_str()).collect()
+    }
+
+    #[test]
+    fn parses_due_dates() {
+        let today = Local::now().date_naive();
+        let midnight = |date: NaiveDate| local(date.year(), date.month(), date.day(), 0, 0);
+        assert_eq!(parse_due("today").unwrap(), midnight(today));
+        assert_eq!(parse_due(" Tomorrow ").unwrap(), midnight(today + Duration::days(1)));
+        assert_eq!(parse_due("2026-03-04").unwrap(), local(2026, 3, 4, 0, 0));
+        assert_eq!(parse_due("2026-03-04 14:30").unwrap(), local(2026, 3, 4, 14, 30));
+        assert_eq!(parse_due("2026-03-04T14:30").unwrap(), local(2026, 3, 4, 14, 30));
+        assert_eq!(
+            parse_due("2026-03-04T14:30:00+02:00").unwrap(),
+            Utc.with_ymd_and_hms(2026, 3, 4, 12, 30, 0).unwrap()
+        );
+        for input in ["next week", "2026-13-01", "04/03/2026", ""] {
+            assert!(matches!(parse_due(input), Err(NoWorkError::Validation(_))), "{}", input);
+        }
+    }
+
+    #[test]
+    fn finds_tags_by_exact_name_before_ignoring_case() {
+        let tag = |id, name: &str| Tag {
+            id,
+            name: name.to_string(),
+            color: "default".to_string(),
+        };
+        let all = [tag(1, "Work"), tag(2, "work")];
+        assert_eq!(find_tag(&all, "work").unwrap().id, 2);
+        assert_eq!(find_tag(&all, "Work").unwrap().id, 1);
+        assert_eq!(find_tag(&all, "WORK").unwrap().id, 1);
+        assert!(find_tag(&all, "home").is_none());
+    }
+
+    #[test]
+    fn add_reuses_tags_and_keeps_their_color() {
+        let mut conn = database();
+        tags::add_tag(
+            &conn,
+            NewTag {
+                name: "Work".to_string(),
+                color: "red".to_string(),
+            },
+        )
+        .unwrap();
+
+        let wanted = ["work".to_string(), "urgent".to_string()];
+        let task = add(&mut conn, " Write report ".to_string(), Some("2026-03-04"), None, &wanted).unwrap();
+        assert_eq!(task.name, "Write report");
+        assert_eq!(task.due_date, Some(local(2026, 3, 4, 0, 0)));
+        let mut tags = tags::list_tags(&conn).unwrap();
+        tags.sort_by(|a, b| a.name.cmp(&b.name));
+        let colors: Vec<(&str, &str)> = tags.iter().map(|t| (t.name.as_str(), t.color.as_str())).collect();
+        assert_eq!(colors, [("Work", "red"), ("urgent", "default")]);
+
+        assert!(add(&mut conn, "  ".to_string(), None, None, &[]).is_err());
+        assert!(add(&mut conn, "Later".to_string(), Some("someday"), None, &[]).is_err());
+    }
+
+    #[test]
+    fn list_filters_by_tag_priority_state_and_limit() {
+        let mut conn = database();
+        add(&mut conn, "Report".to_string(), None, Some(Priority::High), &["work".to_string()]).unwrap();
+        add(&mut conn, "Slides".to_string(), None, Some(Priority::Low), &["work".to_string()]).unwrap();
+        add(&mut conn, "Groceries".to_string(), Some("2000-01-01"), None, &["home".to_string()]).unwrap();
+        let done_task = add(&mut conn, "Laundry".to_string(), None, None, &["home".to_string()]).unwrap();
+        tasks::complete_task(&mut conn, done_task.id, false).unwrap();
+
+        let filter = || ListFilter {
+            view: View::All,
+            tags: &[],
+            priorities: &[],
+            overdue: false,
+            done: false,
+            limit: None,
+        };
+        assert_eq!(names(&list(&conn, filter()).unwrap()), ["Groceries", "Laundry", "Report", "Slides"]);
+        let incomplete = ListFilter { view: View::Incomplete, ..filter() };
+        assert_eq!(names(&list(&conn, incomplete).unwrap()), ["Groceries", "Report", "Slides"]);
+
+        let work = ["WORK".to_string()];
+        assert_eq!(names(&list(&conn, ListFilter { tags: &work, ..filter() }).unwrap()), ["Report", "Slides"]);
+        let high = [Priority::High];
+        assert_eq!(names(&list(&conn, ListFilter { priorities: &high, ..filter() }).unwrap()), ["Report"]);
+        assert_eq!(names(&list(&conn, ListFilter { overdue: true, ..filter() }).unwrap()), ["Groceries"]);
+        assert_eq!(names(&list(&conn, ListFilter { done: true, ..filter() }).unwrap()), ["Laundry"]);
+        assert_eq!(list(&conn, ListFilter { limit: Some(2), ..filter() }).unwrap().len(), 2);
+
+        let unknown = ["travel".to_string()];
+        assert!(matches!(list(&conn, ListFilter { tags: &unknown, ..filter() }), Err(NoWorkError::TagNotFound)));
+    }
+
+    #[test]
+    fn done_and_remove_check_ids() {
+        let mut conn = database();
+        let first = add(&mut conn, "First".to_string(), None, None, &[]).unwrap();
+        let second = add(&mut conn, "Second".to_string(), None, None, &[]).unwrap();
+
+        let completed = done(&mut conn, &[first.id], false).unwrap();
+        assert!(completed[0].completed);
+        assert!(matches!(done(&mut conn, &[999], false), Err(NoWorkError::TaskNotFound)));
+
+        // nothing is deleted when one of the ids does not exist
+        assert!(matches!(remove(&mut conn, &[second.id, 999]), Err(NoWorkError::TaskNotFound)));
+        assert!(load_task(&conn, second.id).is_ok());
+        remove(&mut conn, &[first.id, second.id]).unwrap();
+        assert!(matches!(load_task(&conn, second.id), Err(NoWorkError::TaskNotFound)));
+    }
+
+    #[test]
+    fn tag_adds_new_tags_but_only_removes_existing_ones() {
+        let mut conn = database();
+        let task = add(&mut conn, "Report".to_string(), None, None, &[]).unwrap();
+
+        let tagged = tag(&mut conn, task.id, &["work".to_string()], false).unwrap();
+        assert_eq!(tag_names(&tagged), ["work"]);
+        assert!(matches!(tag(&mut conn, task.id, &["home".to_string()], true), Err(NoWorkError::TagNotFound)));
+        let untagged = tag(&mut conn, task.id, &["Work".to_string()], true).unwrap();
+        assert!(tag_names(&untagged).is_empty());
+        assert!(matches!(tag(&mut conn, 999, &["work".to_string()], false), Err(NoWorkError::TaskNotFound)));
+    }
+}
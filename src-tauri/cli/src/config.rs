@@ -0,0 +1,47 @@
+use nowork_core::error::NoWorkError;
+use rusqlite::{Connection, OptionalExtension};
+use std::path::{Path, PathBuf};
+
+// the app's bundle identifier, Tauri keeps its data in a folder of that name
+const APP_IDENTIFIER: &str = "com.nowork.app";
+
+// --db or NOWORK_DB if given, otherwise the database of the desktop app
+pub fn db_path(explicit: Option<PathBuf>) -> Result<PathBuf, NoWorkError> {
+    if let Some(path) = explicit {
+        return Ok(path);
+    }
+
+    let path = dirs::data_dir()
+        .map(|dir| dir.join(APP_IDENTIFIER).join("tasks.db"))
+        .ok_or_else(|| NoWorkError::validation("Could not find the app's data folder, pass --db"))?;
+    // a database created here would not be the app's
+    if !path.exists() {
+        return Err(NoWorkError::validation(format!(
+            "No database at {}, open the app once or pass --db",
+            path.display()
+        )));
+    }
+    Ok(path)
+}
+
+// next to the database, like the app's
+pub fn backup_dir(db_path: &Path) -> PathBuf {
+    db_path
+        .parent()
+        .map(|dir| dir.join("backups"))
+        .unwrap_or_else(|| PathBuf::from("backups"))
+}
+
+// With a server chosen in settings the app does not show this database's tasks
+pub fn warn_if_remote(conn: &Connection) -> Result<(), NoWorkError> {
+    let server: Option<String> = conn
+        .query_row("SELECT url FROM remote_server WHERE id = 1", [], |row| row.get(0))
+        .optional()?;
+    if let Some(url) = server {
+        eprintln!(
+            "nowork-cli: the app keeps its tasks on {}, these are the ones on this device",
+            url
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,165 @@
+// NoWork from the terminal: works on the same tasks.db as the desktop app.
+// The database is shared through SQLite's WAL mode and busy timeout, so the
+// app may stay open, it shows the changes the next time it loads its lists.
+mod commands;
+mod config;
+mod output;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use nowork_core::{database, error::NoWorkError, types::TaskPriority};
+use std::path::PathBuf;
+
+#[derive(Parser)]
+#[command(name = "nowork-cli", version, about = "Manage NoWork tasks from the command line")]
+struct Cli {
+    /// Database to use instead of the app's
+    #[arg(long, env = "NOWORK_DB", global = true)]
+    db: Option<PathBuf>,
+
+    /// Print tasks and tags as JSON, as the app's commands return them
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Add a task
+    Add {
+        /// Name of the task, the words are joined with spaces
+        #[arg(required = true)]
+        name: Vec<String>,
+        /// Due date: today, tomorrow, 2026-03-14, 2026-03-14T09:30 or RFC 3339
+        #[arg(short, long)]
+        due: Option<String>,
+        #[arg(short, long)]
+        priority: Option<Priority>,
+        /// Tag to add, created if missing. Repeat for more tags.
+        #[arg(short, long = "tag")]
+        tags: Vec<String>,
+    },
+    /// List top-level tasks, incomplete ones unless told otherwise
+    List {
+        #[arg(long, value_enum, default_value_t = View::Incomplete)]
+        view: View,
+        /// Only tasks with every one of these tags
+        #[arg(short, long = "tag")]
+        tags: Vec<String>,
+        /// Only tasks with one of these priorities
+        #[arg(short, long = "priority")]
+        priorities: Vec<Priority>,
+        /// Only tasks past their due date
+        #[arg(long)]
+        overdue: bool,
+        /// Only completed tasks
+        #[arg(long, conflicts_with = "overdue")]
+        done: bool,
+        #[arg(long)]
+        limit: Option<u32>,
+    },
+    /// Complete tasks
+    Done {
+        #[arg(required = true)]
+        ids: Vec<i32>,
+        /// Complete their unfinished subtasks too
+        #[arg(long)]
+        subtasks: bool,
+    },
+    /// Delete tasks with their subtasks
+    Rm {
+        #[arg(required = true)]
+        ids: Vec<i32>,
+    },
+    /// Add tags to a task, or take them off with --remove
+    Tag {
+        id: i32,
+        #[arg(required = true)]
+        tags: Vec<String>,
+        #[arg(long)]
+        remove: bool,
+    },
+    /// List every tag
+    Tags,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum Priority {
+    Low,
+    Medium,
+    High,
+}
+
+impl From<Priority> for TaskPriority {
+    fn from(priority: Priority) -> Self {
+        match priority {
+            Priority::Low => TaskPriority::Low,
+            Priority::Medium => TaskPriority::Medium,
+            Priority::High => TaskPriority::High,
+        }
+    }
+}
+
+// the sidebar's task lists
+#[derive(ValueEnum, Clone, Copy)]
+enum View {
+    All,
+    Incomplete,
+    Today,
+    Week,
+}
+
+fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(&cli) {
+        if cli.json {
+            eprintln!("{}", serde_json::to_string(&e).unwrap_or_else(|_| e.to_string()));
+        } else {
+            eprintln!("nowork-cli: {}", e);
+        }
+        std::process::exit(1);
+    }
+}
+
+fn run(cli: &Cli) -> Result<(), NoWorkError> {
+    let db_path = config::db_path(cli.db.clone())?;
+    let db = database::open(&db_path, &config::backup_dir(&db_path))?;
+    let mut conn = db.get()?;
+    config::warn_if_remote(&conn)?;
+
+    match &cli.command {
+        Command::Add { name, due, priority, tags } => {
+            let task = commands::add(&mut conn, name.join(" "), due.as_deref(), *priority, tags)?;
+            output::task(&task, cli.json)
+        }
+        Command::List {
+            view,
+            tags,
+            priorities,
+            overdue,
+            done,
+            limit,
+        } => {
+            let filter = commands::ListFilter {
+                view: *view,
+                tags,
+                priorities,
+                overdue: *overdue,
+                done: *done,
+                limit: *limit,
+            };
+            output::tasks(&commands::list(&conn, filter)?, cli.json)
+        }
+        Command::Done { ids, subtasks } => output::tasks(&commands::done(&mut conn, ids, *subtasks)?, cli.json),
+        Command::Rm { ids } => {
+            commands::remove(&mut conn, ids)?;
+            output::removed(ids, cli.json)
+        }
+        Command::Tag { id, tags, remove } => {
+            let task = commands::tag(&mut conn, *id, tags, *remove)?;
+            output::task(&task, cli.json)
+        }
+        Command::Tags => output::tags(&nowork_core::tags::list_tags(&conn)?, cli.json),
+    }
+}
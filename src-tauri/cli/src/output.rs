@@ -0,0 +1,85 @@
+use chrono::{Local, Timelike};
+use nowork_core::{
+    error::NoWorkError,
+    types::{Tag, Task, TaskPriority},
+};
+use serde::Serialize;
+use std::io::{self, ErrorKind, Write};
+
+// println that stops quietly once the reader is gone, e.g. piped into head
+fn print(text: &str) -> Result<(), NoWorkError> {
+    match writeln!(io::stdout().lock(), "{}", text) {
+        Err(e) if e.kind() == ErrorKind::BrokenPipe => std::process::exit(0),
+        result => Ok(result?),
+    }
+}
+
+fn json<T: Serialize + ?Sized>(value: &T) -> Result<(), NoWorkError> {
+    print(&serde_json::to_string_pretty(value)?)
+}
+
+// One line per task: id, checkbox, name, then due date, priority, subtask
+// progress and tags when set
+fn line(task: &Task) -> String {
+    let mut line = format!(
+        "{:>4}  [{}] {}",
+        task.id,
+        if task.completed { "x" } else { " " },
+        task.name
+    );
+
+    if let Some(due) = task.due_date {
+        let due = due.with_timezone(&Local);
+        // tasks due on a day rather than at a time are due at midnight
+        let format = if due.hour() == 0 && due.minute() == 0 { "%Y-%m-%d" } else { "%Y-%m-%d %H:%M" };
+        line += &format!("  due {}", due.format(format));
+    }
+    match task.priority {
+        Some(TaskPriority::High) => line += "  !high",
+        Some(TaskPriority::Medium) => line += "  !medium",
+        Some(TaskPriority::Low) => line += "  !low",
+        None => {}
+    }
+    if let Some(progress) = &task.subtask_progress {
+        line += &format!("  ({}/{})", progress.completed, progress.total);
+    }
+    for tag in task.tags.iter().flatten() {
+        line += &format!("  #{}", tag.name);
+    }
+    line
+}
+
+pub fn task(task: &Task, as_json: bool) -> Result<(), NoWorkError> {
+    if as_json {
+        return json(task);
+    }
+    print(&line(task))
+}
+
+pub fn tasks(tasks: &[Task], as_json: bool) -> Result<(), NoWorkError> {
+    if as_json {
+        return json(tasks);
+    }
+    for task in tasks {
+        print(&line(task))?;
+    }
+    Ok(())
+}
+
+pub fn removed(ids: &[i32], as_json: bool) -> Result<(), NoWorkError> {
+    if as_json {
+        return json(ids);
+    }
+    let ids: Vec<String> = ids.iter().map(|id| id.to_string()).collect();
+    print(&format!("Deleted {}", ids.join(", ")))
+}
+
+pub fn tags(tags: &[Tag], as_json: bool) -> Result<(), NoWorkError> {
+    if as_json {
+        return json(tags);
+    }
+    for tag in tags {
+        print(&format!("{:>4}  #{}  ({})", tag.id, tag.name, tag.color))?;
+    }
+    Ok(())
+}
@@ -0,0 +1,189 @@
+use chrono::{DateTime, Local, Utc};
+use rusqlite::{backup::Backup, Connection, OpenFlags};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use crate::database::Database;
+use crate::error::NoWorkError;
+use crate::migrations;
+use crate::types::{BackupInfo, BackupKind};
+
+// pages copied per step, the live database stays usable in between
+const PAGES_PER_STEP: std::ffi::c_int = 256;
+const STEP_PAUSE: Duration = Duration::from_millis(10);
+
+const DAILY_BACKUPS_TO_KEEP: usize = 7;
+const DAILY_BACKUP_INTERVAL: chrono::Duration = chrono::Duration::hours(24);
+// how often a running app or server checks whether a daily backup is due
+const DAILY_BACKUP_CHECK: Duration = Duration::from_secs(60 * 60);
+
+// Copies the live database into a new file in backup_dir using SQLite's online
+// backup API. The copy is written under a temporary name and renamed once
+// complete, so a half written backup is never listed.
+pub fn create_backup(conn: &Connection, backup_dir: &Path, kind: BackupKind) -> Result<BackupInfo, NoWorkError> {
+    std::fs::create_dir_all(backup_dir)?;
+
+    let stamp = Local::now().format("%Y%m%dT%H%M%S");
+    let mut file_name = format!("{}-{}.db", kind.prefix(), stamp);
+    let mut n = 1;
+    while backup_dir.join(&file_name).exists() {
+        file_name = format!("{}-{}-{}.db", kind.prefix(), stamp, n);
+        n += 1;
+    }
+
+    let partial_path = backup_dir.join(format!("{}.partial", file_name));
+    {
+        let mut dst = Connection::open(&partial_path)?;
+        Backup::new(conn, &mut dst)?.run_to_completion(PAGES_PER_STEP, STEP_PAUSE, None)?;
+        // the copy inherits WAL mode, switch back so the backup is a single self-contained file
+        dst.pragma_update_and_check(None, "journal_mode", "DELETE", |row| row.get::<_, String>(0))?;
+    }
+
+    let path = backup_dir.join(&file_name);
+    std::fs::rename(&partial_path, &path)?;
+
+    read_backup_info(&path)?.ok_or(NoWorkError::BackupNotFound)
+}
+
+fn read_backup_info(path: &Path) -> Result<Option<BackupInfo>, NoWorkError> {
+    let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+        return Ok(None);
+    };
+    if !file_name.ends_with(".db") {
+        return Ok(None);
+    }
+    let Some(kind) = BackupKind::from_file_name(file_name) else {
+        return Ok(None);
+    };
+
+    let metadata = std::fs::metadata(path)?;
+    let created_at: DateTime<Utc> = metadata.modified()?.into();
+
+    Ok(Some(BackupInfo {
+        file_name: file_name.to_string(),
+        kind,
+        created_at,
+        size_bytes: metadata.len(),
+    }))
+}
+
+// Every backup in backup_dir, newest first
+pub fn list_backups(backup_dir: &Path) -> Result<Vec<BackupInfo>, NoWorkError> {
+    if !backup_dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups = Vec::new();
+    for entry in std::fs::read_dir(backup_dir)? {
+        if let Some(info) = read_backup_info(&entry?.path())? {
+            backups.push(info);
+        }
+    }
+
+    backups.sort_by(|a, b| b.created_at.cmp(&a.created_at).then_with(|| b.file_name.cmp(&a.file_name)));
+    Ok(backups)
+}
+
+// Resolves a file name from list_backups to a path, refusing anything that
+// could point outside backup_dir
+fn backup_path(backup_dir: &Path, file_name: &str) -> Result<PathBuf, NoWorkError> {
+    if file_name.contains(['/', '\\']) || file_name.starts_with('.') || BackupKind::from_file_name(file_name).is_none() {
+        return Err(NoWorkError::validation("Not a valid backup file name"));
+    }
+
+    let path = backup_dir.join(file_name);
+    if !path.is_file() {
+        return Err(NoWorkError::BackupNotFound);
+    }
+    Ok(path)
+}
+
+pub fn delete_backup_file(backup_dir: &Path, file_name: &str) -> Result<(), NoWorkError> {
+    std::fs::remove_file(backup_path(backup_dir, file_name)?)?;
+    Ok(())
+}
+
+// Keeps the newest `keep` backups of each kind and deletes the rest.
+// Returns the backups that were removed.
+pub fn prune(backup_dir: &Path, kind: Option<BackupKind>, keep: usize) -> Result<Vec<BackupInfo>, NoWorkError> {
+    let mut removed = Vec::new();
+    let mut seen: HashMap<BackupKind, usize> = HashMap::new();
+
+    for backup in list_backups(backup_dir)? {
+        if kind.is_some_and(|k| k != backup.kind) {
+            continue;
+        }
+
+        let count = seen.entry(backup.kind).or_insert(0);
+        *count += 1;
+
+        if *count > keep {
+            std::fs::remove_file(backup_dir.join(&backup.file_name))?;
+            removed.push(backup);
+        }
+    }
+
+    Ok(removed)
+}
+
+// Replaces the live database with the contents of a backup. The current data
+// is backed up first so a restore can itself be undone.
+pub fn restore(db: &Database, backup_dir: &Path, file_name: &str) -> Result<(), NoWorkError> {
+    let path = backup_path(backup_dir, file_name)?;
+    let src = Connection::open_with_flags(&path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)?;
+
+    let check: String = src.query_row("PRAGMA quick_check", [], |row| row.get(0))?;
+    if check != "ok" {
+        return Err(NoWorkError::validation("This backup is damaged and cannot be restored"));
+    }
+
+    let version = migrations::current_version(&src)?;
+    if version > migrations::latest_version() {
+        return Err(NoWorkError::SchemaTooNew {
+            found: version,
+            supported: migrations::latest_version(),
+        });
+    }
+
+    let mut dst = db.get()?;
+    create_backup(&dst, backup_dir, BackupKind::PreRestore)?;
+
+    // other pooled connections would keep serving cached pages and statements
+    db.close_idle();
+    Backup::new(&src, &mut dst)?.run_to_completion(PAGES_PER_STEP, STEP_PAUSE, None)?;
+
+    // an older backup is brought up to the current schema
+    migrations::migrate(&mut dst, backup_dir)
+}
+
+// Takes a daily backup if the newest one is more than a day old
+pub fn run_daily_backup(db: &Database, backup_dir: &Path) -> Result<Option<BackupInfo>, NoWorkError> {
+    let newest = list_backups(backup_dir)?
+        .into_iter()
+        .find(|b| b.kind == BackupKind::Daily);
+
+    if newest.is_some_and(|b| Utc::now() - b.created_at < DAILY_BACKUP_INTERVAL) {
+        return Ok(None);
+    }
+
+    let backup = create_backup(&*db.get()?, backup_dir, BackupKind::Daily)?;
+    prune(backup_dir, Some(BackupKind::Daily), DAILY_BACKUPS_TO_KEEP)?;
+    Ok(Some(backup))
+}
+
+// Checks hourly for as long as the app or server runs, so it also covers
+// sessions that stay open for days. Never returns, spawn it on the runtime.
+pub async fn run_daily_backups(db: Database, backup_dir: PathBuf) {
+    loop {
+        let db = db.clone();
+        let dir = backup_dir.clone();
+        let result = tokio::task::spawn_blocking(move || run_daily_backup(&db, &dir))
+            .await
+            .map_err(NoWorkError::from)
+            .and_then(|r| r);
+        if let Err(e) = result {
+            log::error!("Daily backup failed: {}", e);
+        }
+        tokio::time::sleep(DAILY_BACKUP_CHECK).await;
+    }
+}
@@ -0,0 +1,137 @@
+use rusqlite::Connection;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::error::NoWorkError;
+use crate::migrations;
+
+// connections kept open between commands, extra ones are closed when returned
+const MAX_IDLE_CONNECTIONS: usize = 4;
+const STATEMENT_CACHE_CAPACITY: usize = 64;
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Shared handle to tasks.db, registered as Tauri state by the app and as
+// router state by the server. Cloning is cheap and every clone draws from
+// the same pool of configured connections.
+#[derive(Clone)]
+pub struct Database {
+    inner: Arc<DatabaseInner>,
+}
+
+struct DatabaseInner {
+    path: PathBuf,
+    idle: Mutex<Vec<Connection>>,
+}
+
+// A connection checked out of the pool, handed back when dropped
+pub struct PooledConnection {
+    db: Database,
+    conn: Option<Connection>,
+}
+
+impl Deref for PooledConnection {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().expect("connection already returned")
+    }
+}
+
+impl DerefMut for PooledConnection {
+    fn deref_mut(&mut self) -> &mut Connection {
+        self.conn.as_mut().expect("connection already returned")
+    }
+}
+
+impl Drop for PooledConnection {
+    fn drop(&mut self) {
+        let Some(conn) = self.conn.take() else { return };
+        // a connection left mid-transaction must not be reused
+        if !conn.is_autocommit() {
+            return;
+        }
+        if let Ok(mut idle) = self.db.inner.idle.lock() {
+            if idle.len() < MAX_IDLE_CONNECTIONS {
+                idle.push(conn);
+            }
+        }
+    }
+}
+
+fn configure(conn: &Connection) -> rusqlite::Result<()> {
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    conn.pragma_update(None, "foreign_keys", true)?;
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+    conn.pragma_update(None, "synchronous", "NORMAL")?;
+    conn.set_prepared_statement_cache_capacity(STATEMENT_CACHE_CAPACITY);
+    Ok(())
+}
+
+impl Database {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Database {
+            inner: Arc::new(DatabaseInner {
+                path: path.into(),
+                idle: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.inner.path
+    }
+
+    // Checks out an idle connection or opens a new one
+    pub fn get(&self) -> Result<PooledConnection, NoWorkError> {
+        let idle = self
+            .inner
+            .idle
+            .lock()
+            .map_err(|e| NoWorkError::Storage(e.to_string()))?
+            .pop();
+
+        let conn = match idle {
+            Some(conn) => conn,
+            None => {
+                let conn = Connection::open(&self.inner.path)?;
+                configure(&conn)?;
+                conn
+            }
+        };
+
+        Ok(PooledConnection {
+            db: self.clone(),
+            conn: Some(conn),
+        })
+    }
+
+    // Runs blocking database work off the main thread
+    pub async fn run<T, F>(&self, work: F) -> Result<T, NoWorkError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> Result<T, NoWorkError> + Send + 'static,
+    {
+        let db = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut conn = db.get()?;
+            work(&mut conn)
+        })
+        .await?
+    }
+
+    // Closes every idle connection, e.g. before the file is replaced
+    pub fn close_idle(&self) {
+        if let Ok(mut idle) = self.inner.idle.lock() {
+            idle.clear();
+        }
+    }
+}
+
+// Opens the database at path and applies any pending schema migrations
+pub fn open(path: impl Into<PathBuf>, backup_dir: &Path) -> Result<Database, NoWorkError> {
+    let db = Database::new(path);
+    migrations::migrate(&mut *db.get()?, backup_dir)?;
+    Ok(db)
+}
@@ -0,0 +1,192 @@
+use rusqlite::ErrorCode;
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+// Broad groups the frontend can react to without knowing every code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ErrorCategory {
+    NotFound,
+    Validation,
+    Storage,
+    Network,
+    Auth,
+}
+
+// Error returned from every Tauri command and server endpoint. It is sent as
+// { code, category, message } where code is stable and message is meant
+// to be shown to the user.
+#[derive(Debug)]
+pub enum NoWorkError {
+    TaskNotFound,
+    SubtaskNotFound,
+    TagNotFound,
+    BackupNotFound,
+    CredentialsNotFound,
+    AccountNotFound,
+    EventNotFound,
+    Validation(String),
+    InvalidRecurrence(String),
+    DatabaseLocked,
+    SchemaTooNew { found: i32, supported: i32 },
+    Storage(String),
+    Network(String),
+    InvalidResponse(String),
+    AuthFailed,
+    // a server request without the right bearer token
+    InvalidToken,
+    Keyring(String),
+    // a conditional write lost against a change made elsewhere
+    EditConflict,
+}
+
+impl NoWorkError {
+    pub fn validation(message: impl Into<String>) -> Self {
+        NoWorkError::Validation(message.into())
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            NoWorkError::TaskNotFound => "task_not_found",
+            NoWorkError::SubtaskNotFound => "subtask_not_found",
+            NoWorkError::TagNotFound => "tag_not_found",
+            NoWorkError::BackupNotFound => "backup_not_found",
+            NoWorkError::CredentialsNotFound => "credentials_not_found",
+            NoWorkError::AccountNotFound => "account_not_found",
+            NoWorkError::EventNotFound => "event_not_found",
+            NoWorkError::Validation(_) => "invalid_input",
+            NoWorkError::InvalidRecurrence(_) => "invalid_recurrence",
+            NoWorkError::DatabaseLocked => "database_locked",
+            NoWorkError::SchemaTooNew { .. } => "schema_too_new",
+            NoWorkError::Storage(_) => "storage_failed",
+            NoWorkError::Network(_) => "network_failed",
+            NoWorkError::InvalidResponse(_) => "invalid_response",
+            NoWorkError::AuthFailed => "auth_failed",
+            NoWorkError::InvalidToken => "invalid_token",
+            NoWorkError::Keyring(_) => "keyring_failed",
+            NoWorkError::EditConflict => "edit_conflict",
+        }
+    }
+
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            NoWorkError::TaskNotFound
+            | NoWorkError::SubtaskNotFound
+            | NoWorkError::TagNotFound
+            | NoWorkError::BackupNotFound
+            | NoWorkError::CredentialsNotFound
+            | NoWorkError::AccountNotFound
+            | NoWorkError::EventNotFound => ErrorCategory::NotFound,
+            NoWorkError::Validation(_) | NoWorkError::InvalidRecurrence(_) => ErrorCategory::Validation,
+            NoWorkError::DatabaseLocked | NoWorkError::SchemaTooNew { .. } | NoWorkError::Storage(_) => {
+                ErrorCategory::Storage
+            }
+            NoWorkError::Network(_) | NoWorkError::InvalidResponse(_) | NoWorkError::EditConflict => {
+                ErrorCategory::Network
+            }
+            NoWorkError::AuthFailed | NoWorkError::InvalidToken | NoWorkError::Keyring(_) => ErrorCategory::Auth,
+        }
+    }
+}
+
+impl fmt::Display for NoWorkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NoWorkError::TaskNotFound => write!(f, "No task found with given id"),
+            NoWorkError::SubtaskNotFound => write!(f, "No subtask found with given id"),
+            NoWorkError::TagNotFound => write!(f, "No tag found with given name"),
+            NoWorkError::BackupNotFound => write!(f, "No backup found with given name"),
+            NoWorkError::CredentialsNotFound => write!(f, "No saved credentials for this account"),
+            NoWorkError::AccountNotFound => write!(f, "No calendar account found with this id"),
+            NoWorkError::EventNotFound => write!(f, "No event found with given href"),
+            NoWorkError::Validation(message) => write!(f, "{}", message),
+            NoWorkError::InvalidRecurrence(reason) => write!(f, "Invalid recurrence rule: {}", reason),
+            NoWorkError::DatabaseLocked => write!(f, "The database is busy, please try again"),
+            NoWorkError::SchemaTooNew { found, supported } => write!(
+                f,
+                "Database schema version {} is newer than this version of NoWork supports ({}). Please update NoWork.",
+                found, supported
+            ),
+            NoWorkError::Storage(reason) => write!(f, "Database error: {}", reason),
+            NoWorkError::Network(reason) => write!(f, "Network error: {}", reason),
+            NoWorkError::InvalidResponse(reason) => write!(f, "Unexpected response from server: {}", reason),
+            NoWorkError::AuthFailed => write!(f, "Authentication failed, check your email and password"),
+            NoWorkError::InvalidToken => write!(f, "Missing or invalid API token"),
+            NoWorkError::Keyring(reason) => write!(f, "Could not access the system keyring: {}", reason),
+            NoWorkError::EditConflict => write!(f, "This was changed on another device, sync and try again"),
+        }
+    }
+}
+
+impl std::error::Error for NoWorkError {}
+
+impl Serialize for NoWorkError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("NoWorkError", 3)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("category", &self.category())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
+impl From<rusqlite::Error> for NoWorkError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e.sqlite_error_code() {
+            Some(ErrorCode::DatabaseBusy) | Some(ErrorCode::DatabaseLocked) => NoWorkError::DatabaseLocked,
+            _ => NoWorkError::Storage(e.to_string()),
+        }
+    }
+}
+
+impl From<std::io::Error> for NoWorkError {
+    fn from(e: std::io::Error) -> Self {
+        NoWorkError::Storage(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for NoWorkError {
+    fn from(e: serde_json::Error) -> Self {
+        NoWorkError::Storage(e.to_string())
+    }
+}
+
+impl From<tokio::task::JoinError> for NoWorkError {
+    fn from(e: tokio::task::JoinError) -> Self {
+        NoWorkError::Storage(e.to_string())
+    }
+}
+
+impl From<rrule::RRuleError> for NoWorkError {
+    fn from(e: rrule::RRuleError) -> Self {
+        NoWorkError::InvalidRecurrence(e.to_string())
+    }
+}
+
+#[cfg(feature = "calendar")]
+impl From<reqwest::Error> for NoWorkError {
+    fn from(e: reqwest::Error) -> Self {
+        match e.status() {
+            Some(status) if status.as_u16() == 401 || status.as_u16() == 403 => NoWorkError::AuthFailed,
+            _ => NoWorkError::Network(e.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "calendar")]
+impl From<roxmltree::Error> for NoWorkError {
+    fn from(e: roxmltree::Error) -> Self {
+        NoWorkError::InvalidResponse(e.to_string())
+    }
+}
+
+#[cfg(feature = "calendar")]
+impl From<keyring_core::Error> for NoWorkError {
+    fn from(e: keyring_core::Error) -> Self {
+        match e {
+            keyring_core::Error::NoEntry => NoWorkError::CredentialsNotFound,
+            e => NoWorkError::Keyring(e.to_string()),
+        }
+    }
+}
@@ -0,0 +1,16 @@
+// Task storage and logic without any Tauri dependency, shared by the desktop
+// app's commands and the nowork-server binary. Every function works on an
+// open connection, callers decide how to get one (see Database::run).
+pub mod backup;
+pub mod database;
+pub mod error;
+pub mod migrations;
+pub mod query;
+pub mod quick_add;
+pub mod recurrence;
+pub mod search;
+pub mod subtasks;
+pub mod sync;
+pub mod tags;
+pub mod tasks;
+pub mod types;
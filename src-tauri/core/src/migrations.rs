@@ -0,0 +1,749 @@
+use chrono::Local;
+use rusqlite::{params, Connection, Transaction};
+use std::path::Path;
+
+use crate::error::NoWorkError;
+
+// A single schema change. Migrations run in order, each inside its own
+// transaction, and bump PRAGMA user_version to their version on success.
+struct Migration {
+    version: i32,
+    description: &'static str,
+    up: fn(&Transaction) -> rusqlite::Result<()>,
+}
+
+// Append new migrations to the end, never edit or reorder released ones.
+// Databases created before this framework existed report user_version 0,
+// so the early steps must tolerate tables and columns that already exist.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "initial schema",
+        up: initial_schema,
+    },
+    Migration {
+        version: 2,
+        description: "task priority",
+        up: |tx| add_column_if_missing(tx, "tasks", "priority", "INTEGER"),
+    },
+    Migration {
+        version: 3,
+        description: "task recurrence",
+        up: |tx| {
+            add_column_if_missing(tx, "tasks", "recurrence_rule", "TEXT")?;
+            add_column_if_missing(tx, "tasks", "recurrence_mode", "INTEGER")
+        },
+    },
+    Migration {
+        version: 4,
+        description: "subtasks",
+        up: |tx| {
+            add_column_if_missing(tx, "tasks", "parent_id", "INTEGER REFERENCES tasks(id) ON DELETE CASCADE")?;
+            add_column_if_missing(tx, "tasks", "position", "INTEGER NOT NULL DEFAULT 0")?;
+            tx.execute("CREATE INDEX IF NOT EXISTS idx_tasks_parent_id ON tasks(parent_id)", [])?;
+            Ok(())
+        },
+    },
+    Migration {
+        version: 5,
+        description: "full-text task search",
+        up: task_search_index,
+    },
+    Migration {
+        version: 6,
+        description: "calendar cache",
+        up: calendar_cache,
+    },
+    Migration {
+        version: 7,
+        description: "task sync",
+        up: task_sync,
+    },
+    Migration {
+        version: 8,
+        description: "calendar accounts",
+        up: calendar_accounts,
+    },
+    Migration {
+        version: 9,
+        description: "ics subscriptions",
+        up: ics_subscriptions,
+    },
+    Migration {
+        version: 10,
+        description: "remote storage",
+        up: remote_storage,
+    },
+    Migration {
+        version: 11,
+        description: "sync change log",
+        up: sync_change_log,
+    },
+    Migration {
+        version: 12,
+        description: "calendar account ids",
+        up: calendar_account_ids,
+    },
+    Migration {
+        version: 13,
+        description: "failed remote changes",
+        up: |tx| {
+            // set once the server rejected a queued change, which then waits
+            // for the user to retry or discard it
+            add_column_if_missing(tx, "remote_queue", "failed_at", "TEXT")?;
+            add_column_if_missing(tx, "remote_queue", "error", "TEXT")
+        },
+    },
+    Migration {
+        version: 14,
+        description: "remote idempotency keys",
+        up: |tx| {
+            // a queued request is sent again with the same Idempotency-Key
+            add_column_if_missing(tx, "remote_queue", "idempotency_key", "TEXT")
+        },
+    },
+    Migration {
+        version: 15,
+        description: "sync devices",
+        up: sync_devices,
+    },
+];
+
+pub fn latest_version() -> i32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+pub fn current_version(conn: &Connection) -> rusqlite::Result<i32> {
+    conn.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+// Brings the database up to the latest schema, backing it up into
+// backup_dir first if it already holds data from an older version.
+pub fn migrate(conn: &mut Connection, backup_dir: &Path) -> Result<(), NoWorkError> {
+    let current = current_version(conn)?;
+    let latest = latest_version();
+
+    if current > latest {
+        return Err(NoWorkError::SchemaTooNew {
+            found: current,
+            supported: latest,
+        });
+    }
+
+    if current == latest {
+        return Ok(());
+    }
+
+    if has_user_tables(conn)? {
+        backup_before_migration(conn, backup_dir, current)?;
+    }
+
+    // Rebuilding a table drops the old copy, which would cascade into the
+    // tables referencing it. The pragma does nothing inside a transaction,
+    // so foreign keys are off around all of the migrations.
+    let foreign_keys: bool = conn.query_row("PRAGMA foreign_keys", [], |row| row.get(0))?;
+    conn.pragma_update(None, "foreign_keys", false)?;
+    let result = run_migrations(conn, current);
+    conn.pragma_update(None, "foreign_keys", foreign_keys)?;
+    result
+}
+
+fn run_migrations(conn: &mut Connection, current: i32) -> Result<(), NoWorkError> {
+    for migration in MIGRATIONS.iter().filter(|m| m.version > current) {
+        let tx = conn.transaction()?;
+
+        (migration.up)(&tx).map_err(|e| {
+            NoWorkError::Storage(format!(
+                "Migration {} ({}) failed: {}",
+                migration.version, migration.description, e
+            ))
+        })?;
+
+        // user_version lives in the database header and is covered by the transaction
+        tx.pragma_update(None, "user_version", migration.version)?;
+        tx.commit()?;
+    }
+
+    Ok(())
+}
+
+fn has_user_tables(conn: &Connection) -> rusqlite::Result<bool> {
+    conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%')",
+        [],
+        |row| row.get(0),
+    )
+}
+
+// VACUUM INTO writes a consistent copy even if the database is in WAL mode
+fn backup_before_migration(conn: &Connection, backup_dir: &Path, from_version: i32) -> Result<(), NoWorkError> {
+    std::fs::create_dir_all(backup_dir)?;
+
+    let file_name = format!(
+        "pre-migration-v{}-{}.db",
+        from_version,
+        Local::now().format("%Y%m%dT%H%M%S")
+    );
+    let backup_path = backup_dir.join(file_name);
+
+    conn.execute("VACUUM INTO ?1", params![backup_path.to_string_lossy()])
+        .map_err(|e| NoWorkError::Storage(format!("Failed to back up database before migrating: {}", e)))?;
+
+    Ok(())
+}
+
+fn add_column_if_missing(tx: &Transaction, table: &str, column: &str, decl: &str) -> rusqlite::Result<()> {
+    let exists: bool = tx.query_row(
+        "SELECT EXISTS(SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2)",
+        params![table, column],
+        |row| row.get(0),
+    )?;
+
+    if !exists {
+        tx.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, decl), [])?;
+    }
+    Ok(())
+}
+
+fn initial_schema(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS tasks (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            due_date TEXT,
+            created_at TEXT DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
+            completed BOOLEAN DEFAULT 0,
+            completed_at TEXT
+        )",
+        [],
+    )?;
+
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS tags (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            color TEXT DEFAULT 'default'
+        )",
+        [],
+    )?;
+
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS task_tags (
+            task_id INTEGER NOT NULL,
+            tag_id INTEGER NOT NULL,
+            PRIMARY KEY (task_id, tag_id),
+            FOREIGN KEY (task_id) REFERENCES tasks(id) ON DELETE CASCADE,
+            FOREIGN KEY (tag_id) REFERENCES tags(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    Ok(())
+}
+
+// External content FTS5 index over tasks, kept in sync by triggers
+fn task_search_index(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS tasks_fts USING fts5(
+            name,
+            content = 'tasks',
+            content_rowid = 'id',
+            tokenize = 'unicode61 remove_diacritics 2'
+        );
+
+        CREATE TRIGGER IF NOT EXISTS tasks_fts_insert AFTER INSERT ON tasks BEGIN
+            INSERT INTO tasks_fts (rowid, name) VALUES (new.id, new.name);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS tasks_fts_delete AFTER DELETE ON tasks BEGIN
+            INSERT INTO tasks_fts (tasks_fts, rowid, name) VALUES ('delete', old.id, old.name);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS tasks_fts_update AFTER UPDATE OF name ON tasks BEGIN
+            INSERT INTO tasks_fts (tasks_fts, rowid, name) VALUES ('delete', old.id, old.name);
+            INSERT INTO tasks_fts (rowid, name) VALUES (new.id, new.name);
+        END;
+
+        INSERT INTO tasks_fts (tasks_fts) VALUES ('rebuild');",
+    )
+}
+
+// Calendars and their iCalendar resources as last synced from the server,
+// read by the calendar views so they work offline
+fn calendar_cache(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS calendar_accounts (
+            email TEXT PRIMARY KEY,
+            server_url TEXT,
+            home_url TEXT
+        );
+
+        CREATE TABLE IF NOT EXISTS calendars (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            account TEXT NOT NULL REFERENCES calendar_accounts(email) ON DELETE CASCADE,
+            url TEXT NOT NULL,
+            display_name TEXT,
+            color TEXT,
+            ctag TEXT,
+            sync_token TEXT,
+            components TEXT NOT NULL DEFAULT '',
+            synced_at TEXT,
+            UNIQUE (account, url)
+        );
+
+        CREATE TABLE IF NOT EXISTS calendar_objects (
+            calendar_id INTEGER NOT NULL REFERENCES calendars(id) ON DELETE CASCADE,
+            href TEXT NOT NULL,
+            etag TEXT,
+            data TEXT NOT NULL,
+            PRIMARY KEY (calendar_id, href)
+        );",
+    )
+}
+
+// Which server resource each synced task lives in. fingerprint is the local
+// task's synced fields as JSON at the last sync, a task that no longer
+// matches it was edited locally since. data is the server's iCalendar text at
+// the last sync, local edits are written into it so properties NoWork does
+// not know about survive. A link whose task_id went NULL belongs to a task
+// deleted locally, ignored marks resources that are not tasks NoWork shows,
+// e.g. events or subtasks.
+fn task_sync(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "calendar_accounts", "task_calendar_url", "TEXT")?;
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS task_sync_links (
+            account TEXT NOT NULL REFERENCES calendar_accounts(email) ON DELETE CASCADE,
+            href TEXT NOT NULL,
+            uid TEXT NOT NULL,
+            task_id INTEGER UNIQUE REFERENCES tasks(id) ON DELETE SET NULL,
+            etag TEXT,
+            fingerprint TEXT,
+            data TEXT NOT NULL,
+            ignored BOOLEAN NOT NULL DEFAULT 0,
+            PRIMARY KEY (account, href)
+        );",
+    )
+}
+
+// Accounts used to be created by their first sync and were all assumed to be
+// iCloud unless a server was given. provider picks the defaults the settings
+// page shows, enabled hides a calendar without forgetting it.
+fn calendar_accounts(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "calendar_accounts", "provider", "TEXT NOT NULL DEFAULT 'caldav'")?;
+    add_column_if_missing(tx, "calendars", "enabled", "BOOLEAN NOT NULL DEFAULT 1")?;
+    tx.execute(
+        "UPDATE calendar_accounts SET provider = 'icloud'
+         WHERE server_url IS NULL OR server_url = 'https://caldav.icloud.com'",
+        [],
+    )?;
+    Ok(())
+}
+
+// Subscribed feeds and imported files are calendars of a built-in "local"
+// account with no server behind it. refresh_minutes is set for feeds only,
+// their HTTP ETag goes in ctag and synced_at is when they were last fetched.
+fn ics_subscriptions(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "calendars", "refresh_minutes", "INTEGER")?;
+    tx.execute(
+        "INSERT OR IGNORE INTO calendar_accounts (email, provider) VALUES ('local', 'local')",
+        [],
+    )?;
+    Ok(())
+}
+
+// The server the app keeps its tasks on instead of this database, if any.
+// While it cannot be reached changes wait in remote_queue in the order they
+// were made, and reads are answered from the last responses in remote_cache.
+fn remote_storage(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS remote_server (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            url TEXT NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS remote_queue (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            method TEXT NOT NULL,
+            path TEXT NOT NULL,
+            body TEXT,
+            queued_at TEXT NOT NULL DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+        );
+        CREATE TABLE IF NOT EXISTS remote_cache (
+            key TEXT PRIMARY KEY,
+            body TEXT NOT NULL
+        );",
+    )
+}
+
+// timestamps of the change log, same format as created_at
+const SYNC_NOW: &str = "strftime('%Y-%m-%dT%H:%M:%fZ', 'now')";
+
+// tasks columns that are synced field by field, parent_id goes as "parent"
+// holding the parent's uid since row ids differ between devices
+const SYNCED_TASK_COLUMNS: &[&str] = &[
+    "name",
+    "due_date",
+    "created_at",
+    "completed",
+    "completed_at",
+    "priority",
+    "recurrence_rule",
+    "recurrence_mode",
+    "position",
+];
+
+// Statement run by a trigger to log one field of a local change. Its time is
+// at least a millisecond past every change this device has seen for the
+// field, so an edit always wins over the value the user was looking at even
+// if another device's clock runs ahead.
+fn log_change(entity: &str, uid: &str, field: &str, value: &str) -> String {
+    log_changes(entity, uid, field, value, "sync_device")
+}
+
+// log_change for every row of source, which must join sync_device
+fn log_changes(entity: &str, uid: &str, field: &str, value: &str, source: &str) -> String {
+    format!(
+        "INSERT INTO change_log (entity, uid, field, value, updated_at, device_id)
+         SELECT '{entity}', {uid}, {field}, {value},
+                MAX({now}, COALESCE((SELECT strftime('%Y-%m-%dT%H:%M:%fZ', MAX(updated_at), '+0.001 seconds')
+                                     FROM change_log WHERE entity = '{entity}' AND uid = {uid} AND field = {field}), '')),
+                sync_device.device_id
+         FROM {source};",
+        entity = entity,
+        uid = uid,
+        field = field,
+        value = value,
+        now = SYNC_NOW,
+        source = source,
+    )
+}
+
+// One row per changed field of a task or tag, made on this device or received
+// from another. Tasks are known across devices by uid, tags by name. Deleting
+// still removes the row, the change log keeps a "deleted" tombstone in its
+// place. origin_seq is the seq a change has on the device that made it, NULL
+// for this device's own changes. Triggers fill the log for every write except
+// while sync_device.applying is set, when received changes are written back.
+fn sync_change_log(tx: &Transaction) -> rusqlite::Result<()> {
+    add_column_if_missing(tx, "tasks", "uid", "TEXT")?;
+    add_column_if_missing(tx, "tasks", "updated_at", "TEXT")?;
+    add_column_if_missing(tx, "tags", "updated_at", "TEXT")?;
+
+    tx.execute_batch(&format!(
+        "UPDATE tasks SET uid = lower(hex(randomblob(16))) WHERE uid IS NULL;
+        UPDATE tasks SET updated_at = COALESCE(completed_at, created_at, {now});
+        UPDATE tags SET updated_at = {now};
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_tasks_uid ON tasks(uid);
+
+        CREATE TABLE IF NOT EXISTS sync_device (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            device_id TEXT NOT NULL,
+            applying BOOLEAN NOT NULL DEFAULT 0
+        );
+        INSERT OR IGNORE INTO sync_device (id, device_id) VALUES (1, lower(hex(randomblob(16))));
+
+        CREATE TABLE IF NOT EXISTS change_log (
+            seq INTEGER PRIMARY KEY AUTOINCREMENT,
+            entity TEXT NOT NULL,
+            uid TEXT NOT NULL,
+            field TEXT NOT NULL,
+            value TEXT NOT NULL,
+            updated_at TEXT NOT NULL,
+            device_id TEXT NOT NULL,
+            origin_seq INTEGER
+        );
+        CREATE INDEX IF NOT EXISTS idx_change_log_field ON change_log(entity, uid, field);
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_change_log_origin ON change_log(device_id, origin_seq);",
+        now = SYNC_NOW,
+    ))?;
+
+    let local = "(SELECT applying FROM sync_device) = 0";
+    let parent_uid = |row: &str| format!("json_quote((SELECT uid FROM tasks WHERE id = {}.parent_id))", row);
+    let tag_field = |row: &str| format!("'tag:' || (SELECT name FROM tags WHERE id = {}.tag_id)", row);
+    let task_uid = |row: &str| format!("(SELECT uid FROM tasks WHERE id = {}.task_id)", row);
+
+    // what the log holds for tasks and tags that existed before it
+    let mut seed = String::new();
+    for column in SYNCED_TASK_COLUMNS {
+        seed += &format!(
+            "INSERT INTO change_log (entity, uid, field, value, updated_at, device_id)
+             SELECT 'task', uid, '{column}', json_quote({column}), updated_at, (SELECT device_id FROM sync_device) FROM tasks;",
+            column = column
+        );
+    }
+    seed += &format!(
+        "INSERT INTO change_log (entity, uid, field, value, updated_at, device_id)
+         SELECT 'task', uid, 'parent', {parent}, updated_at, (SELECT device_id FROM sync_device) FROM tasks AS task;
+        INSERT INTO change_log (entity, uid, field, value, updated_at, device_id)
+         SELECT 'tag', name, 'deleted', 'false', updated_at, (SELECT device_id FROM sync_device) FROM tags;
+        INSERT INTO change_log (entity, uid, field, value, updated_at, device_id)
+         SELECT 'tag', name, 'color', json_quote(color), updated_at, (SELECT device_id FROM sync_device) FROM tags;
+        INSERT INTO change_log (entity, uid, field, value, updated_at, device_id)
+         SELECT 'task', tasks.uid, 'tag:' || tags.name, 'true', tasks.updated_at, (SELECT device_id FROM sync_device)
+         FROM task_tags JOIN tasks ON tasks.id = task_tags.task_id JOIN tags ON tags.id = task_tags.tag_id;",
+        parent = parent_uid("task"),
+    );
+    tx.execute_batch(&seed)?;
+
+    // new tasks get their uid here unless they arrive with one
+    let new_uid = "(SELECT uid FROM tasks WHERE id = new.id)";
+    let mut insert_task = format!(
+        "UPDATE tasks SET uid = COALESCE(new.uid, lower(hex(randomblob(16)))), updated_at = {now} WHERE id = new.id;",
+        now = SYNC_NOW
+    );
+    for column in SYNCED_TASK_COLUMNS {
+        insert_task += &log_change("task", new_uid, &format!("'{}'", column), &format!("json_quote(new.{})", column));
+    }
+    insert_task += &log_change("task", new_uid, "'parent'", &parent_uid("new"));
+
+    let mut triggers = format!(
+        "CREATE TRIGGER IF NOT EXISTS tasks_sync_insert AFTER INSERT ON tasks WHEN {local} BEGIN {insert_task} END;
+        CREATE TRIGGER IF NOT EXISTS tasks_sync_delete AFTER DELETE ON tasks WHEN {local} BEGIN {delete_task} END;",
+        local = local,
+        insert_task = insert_task,
+        delete_task = log_change("task", "old.uid", "'deleted'", "'true'"),
+    );
+    for (column, field, value) in SYNCED_TASK_COLUMNS
+        .iter()
+        .map(|c| (c.to_string(), format!("'{}'", c), format!("json_quote(new.{})", c)))
+        .chain(std::iter::once(("parent_id".to_string(), "'parent'".to_string(), parent_uid("new"))))
+    {
+        triggers += &format!(
+            "CREATE TRIGGER IF NOT EXISTS tasks_sync_update_{column} AFTER UPDATE OF {column} ON tasks
+             WHEN {local} AND old.{column} IS NOT new.{column} BEGIN
+                UPDATE tasks SET updated_at = {now} WHERE id = new.id;
+                {log}
+             END;",
+            column = column,
+            local = local,
+            now = SYNC_NOW,
+            log = log_change("task", "new.uid", &field, &value),
+        );
+    }
+
+    // tags are known by name, which never changes
+    triggers += &format!(
+        "CREATE TRIGGER IF NOT EXISTS tags_sync_insert AFTER INSERT ON tags WHEN {local} BEGIN
+            UPDATE tags SET updated_at = {now} WHERE id = new.id;
+            {created}
+            {color}
+         END;
+        CREATE TRIGGER IF NOT EXISTS tags_sync_update_color AFTER UPDATE OF color ON tags
+         WHEN {local} AND old.color IS NOT new.color BEGIN
+            UPDATE tags SET updated_at = {now} WHERE id = new.id;
+            {color}
+         END;
+        CREATE TRIGGER IF NOT EXISTS tags_sync_delete BEFORE DELETE ON tags WHEN {local} BEGIN
+            {untagged}
+            {deleted}
+         END;",
+        local = local,
+        untagged = log_changes(
+            "task",
+            "tasks.uid",
+            "'tag:' || old.name",
+            "'false'",
+            "task_tags JOIN tasks ON tasks.id = task_tags.task_id JOIN sync_device WHERE task_tags.tag_id = old.id",
+        ),
+        now = SYNC_NOW,
+        created = log_change("tag", "new.name", "'deleted'", "'false'"),
+        color = log_change("tag", "new.name", "'color'", "json_quote(new.color)"),
+        deleted = log_change("tag", "old.name", "'deleted'", "'true'"),
+    );
+
+    // A task's tags are fields of the task, "tag:<name>" set to true or false.
+    // Deleting a tag takes it off its tasks above, so one created again with
+    // the same name starts out on none. Links removed along with their task
+    // need no entry of their own.
+    triggers += &format!(
+        "CREATE TRIGGER IF NOT EXISTS task_tags_sync_insert AFTER INSERT ON task_tags WHEN {local} BEGIN
+            UPDATE tasks SET updated_at = {now} WHERE id = new.task_id;
+            {added}
+         END;
+        CREATE TRIGGER IF NOT EXISTS task_tags_sync_delete AFTER DELETE ON task_tags
+         WHEN {local} AND {old_task} IS NOT NULL AND {old_tag} IS NOT NULL BEGIN
+            UPDATE tasks SET updated_at = {now} WHERE id = old.task_id;
+            {removed}
+         END;",
+        local = local,
+        now = SYNC_NOW,
+        old_task = task_uid("old"),
+        old_tag = tag_field("old"),
+        added = log_change("task", &task_uid("new"), &tag_field("new"), "'true'"),
+        removed = log_change("task", &task_uid("old"), &tag_field("old"), "'false'"),
+    );
+
+    tx.execute_batch(&triggers)
+}
+
+// The devices that sync with this database as a server, and the change log
+// seq each one has received everything up to. Entries every device has seen
+// can be pruned, see sync::prune.
+fn sync_devices(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS sync_devices (
+            device_id TEXT PRIMARY KEY,
+            acked_seq INTEGER NOT NULL,
+            last_sync_at TEXT NOT NULL
+        );",
+    )
+}
+
+// Accounts were keyed by username alone, so the same username could not be
+// added on a second server. They get a numeric id instead, 0 for the local
+// account, and are unique per server and username. Tables referencing them
+// are rebuilt to point at the id, accounts without a server_url predate the
+// choice of server and were iCloud.
+fn calendar_account_ids(tx: &Transaction) -> rusqlite::Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE calendar_accounts_new (
+            id INTEGER PRIMARY KEY,
+            username TEXT NOT NULL,
+            provider TEXT NOT NULL DEFAULT 'caldav',
+            server_url TEXT,
+            home_url TEXT,
+            task_calendar_url TEXT,
+            UNIQUE (server_url, username)
+        );
+        INSERT INTO calendar_accounts_new (id, username, provider, server_url, home_url, task_calendar_url)
+         SELECT
+            CASE WHEN provider = 'local' THEN 0 ELSE rowid END,
+            email,
+            provider,
+            CASE WHEN provider = 'local' THEN NULL ELSE COALESCE(server_url, 'https://caldav.icloud.com') END,
+            home_url,
+            task_calendar_url
+         FROM calendar_accounts;
+
+        CREATE TABLE calendars_new (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            account INTEGER NOT NULL REFERENCES calendar_accounts(id) ON DELETE CASCADE,
+            url TEXT NOT NULL,
+            display_name TEXT,
+            color TEXT,
+            ctag TEXT,
+            sync_token TEXT,
+            components TEXT NOT NULL DEFAULT '',
+            synced_at TEXT,
+            enabled BOOLEAN NOT NULL DEFAULT 1,
+            refresh_minutes INTEGER,
+            UNIQUE (account, url)
+        );
+        INSERT INTO calendars_new
+            (id, account, url, display_name, color, ctag, sync_token, components, synced_at, enabled, refresh_minutes)
+         SELECT calendars.id, accounts.id, url, display_name, color, ctag, sync_token, components, synced_at, enabled, refresh_minutes
+         FROM calendars JOIN calendar_accounts_new AS accounts ON accounts.username = calendars.account;
+
+        CREATE TABLE task_sync_links_new (
+            account INTEGER NOT NULL REFERENCES calendar_accounts(id) ON DELETE CASCADE,
+            href TEXT NOT NULL,
+            uid TEXT NOT NULL,
+            task_id INTEGER UNIQUE REFERENCES tasks(id) ON DELETE SET NULL,
+            etag TEXT,
+            fingerprint TEXT,
+            data TEXT NOT NULL,
+            ignored BOOLEAN NOT NULL DEFAULT 0,
+            PRIMARY KEY (account, href)
+        );
+        INSERT INTO task_sync_links_new (account, href, uid, task_id, etag, fingerprint, data, ignored)
+         SELECT accounts.id, href, uid, task_id, etag, fingerprint, data, ignored
+         FROM task_sync_links JOIN calendar_accounts_new AS accounts ON accounts.username = task_sync_links.account;
+
+        DROP TABLE task_sync_links;
+        DROP TABLE calendars;
+        DROP TABLE calendar_accounts;
+        ALTER TABLE calendar_accounts_new RENAME TO calendar_accounts;
+        ALTER TABLE calendars_new RENAME TO calendars;
+        ALTER TABLE task_sync_links_new RENAME TO task_sync_links;",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a database as the given version left it
+    fn database_at(version: i32) -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        for migration in MIGRATIONS.iter().filter(|m| m.version <= version) {
+            let tx = conn.transaction().unwrap();
+            (migration.up)(&tx).unwrap();
+            tx.pragma_update(None, "user_version", migration.version).unwrap();
+            tx.commit().unwrap();
+        }
+        conn.pragma_update(None, "foreign_keys", true).unwrap();
+        conn
+    }
+
+    #[test]
+    fn calendar_accounts_keep_their_calendars_when_they_get_ids() {
+        let mut conn = database_at(11);
+        conn.execute_batch(
+            "INSERT INTO calendar_accounts (email, provider, server_url, home_url, task_calendar_url)
+             VALUES ('alice', 'caldav', 'https://dav.example.com', 'https://dav.example.com/home/', 'https://dav.example.com/home/tasks/');
+            INSERT INTO calendar_accounts (email, provider) VALUES ('bob@icloud.com', 'icloud');
+            INSERT INTO calendars (account, url, components) VALUES ('alice', 'https://dav.example.com/home/tasks/', 'VTODO');
+            INSERT INTO calendars (account, url, refresh_minutes) VALUES ('local', 'https://example.com/holidays.ics', 360);
+            INSERT INTO calendar_objects (calendar_id, href, data)
+             SELECT id, url || 'a.ics', 'BEGIN:VCALENDAR' FROM calendars;
+            INSERT INTO task_sync_links (account, href, uid, data)
+             VALUES ('alice', 'https://dav.example.com/home/tasks/a.ics', 'a', 'BEGIN:VCALENDAR');",
+        )
+        .unwrap();
+
+        let backups = tempfile::tempdir().unwrap();
+        migrate(&mut conn, backups.path()).unwrap();
+
+        let accounts: Vec<(i64, String, Option<String>)> = conn
+            .prepare("SELECT id, username, server_url FROM calendar_accounts ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(accounts.len(), 3);
+        assert_eq!(accounts[0], (0, "local".to_string(), None));
+        let alice = accounts.iter().find(|a| a.1 == "alice").unwrap().0;
+        let bob = accounts.iter().find(|a| a.1 == "bob@icloud.com").unwrap();
+        assert_eq!(bob.2.as_deref(), Some("https://caldav.icloud.com"));
+
+        let calendars: Vec<(i64, i64)> = conn
+            .prepare(
+                "SELECT calendars.account, COUNT(calendar_objects.href) FROM calendars
+                 LEFT JOIN calendar_objects ON calendar_objects.calendar_id = calendars.id
+                 GROUP BY calendars.id ORDER BY calendars.account",
+            )
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(calendars, [(0, 1), (alice, 1)]);
+
+        let link_account: i64 = conn
+            .query_row("SELECT account FROM task_sync_links", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(link_account, alice);
+        let foreign_keys: bool = conn.query_row("PRAGMA foreign_keys", [], |row| row.get(0)).unwrap();
+        assert!(foreign_keys);
+
+        // the same username on another server is a separate account
+        conn.execute(
+            "INSERT INTO calendar_accounts (username, server_url) VALUES ('alice', 'https://other.example.com')",
+            [],
+        )
+        .unwrap();
+        assert!(conn
+            .execute(
+                "INSERT INTO calendar_accounts (username, server_url) VALUES ('alice', 'https://dav.example.com')",
+                [],
+            )
+            .is_err());
+
+        // removing an account still takes its calendars along
+        conn.execute("DELETE FROM calendar_accounts WHERE id = ?1", params![alice]).unwrap();
+        let left: i64 = conn
+            .query_row("SELECT COUNT(*) FROM calendar_objects", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(left, 1);
+    }
+}
@@ -0,0 +1,214 @@
+use chrono::{Duration, Local, NaiveDate, TimeZone, Utc};
+use rusqlite::{params_from_iter, types::Value, Connection};
+
+use crate::error::NoWorkError;
+use crate::tasks::load_tasks;
+use crate::types::{DateRange, TaskPage, TaskQuery, TaskSortKey};
+
+// Accumulates WHERE clauses and their bound values
+#[derive(Default)]
+struct Filter {
+    clauses: Vec<String>,
+    values: Vec<Value>,
+}
+
+impl Filter {
+    fn push(&mut self, clause: impl Into<String>, values: impl IntoIterator<Item = Value>) {
+        self.clauses.push(clause.into());
+        self.values.extend(values);
+    }
+
+    // dates are stored as RFC3339 text with mixed offsets/precision,
+    // julianday normalises both sides before comparing
+    fn date_range(&mut self, column: &str, range: &DateRange) {
+        if let Some(start) = range.start {
+            self.push(
+                format!("julianday({}) >= julianday(?)", column),
+                [Value::Text(start.to_rfc3339())],
+            );
+        }
+        if let Some(end) = range.end {
+            self.push(
+                format!("julianday({}) < julianday(?)", column),
+                [Value::Text(end.to_rfc3339())],
+            );
+        }
+    }
+
+    fn where_sql(&self) -> String {
+        if self.clauses.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", self.clauses.join(" AND "))
+        }
+    }
+}
+
+fn placeholders(count: usize) -> String {
+    vec!["?"; count].join(", ")
+}
+
+fn id_values(ids: &[i64]) -> Vec<Value> {
+    let mut ids = ids.to_vec();
+    ids.sort_unstable();
+    ids.dedup();
+    ids.into_iter().map(Value::Integer).collect()
+}
+
+fn build_filter(query: &TaskQuery) -> Filter {
+    let mut filter = Filter::default();
+
+    if !query.include_subtasks {
+        filter.push("tasks.parent_id IS NULL", []);
+    }
+
+    let any = id_values(&query.tags.any);
+    if !any.is_empty() {
+        filter.push(
+            format!(
+                "EXISTS (SELECT 1 FROM task_tags WHERE task_tags.task_id = tasks.id AND task_tags.tag_id IN ({}))",
+                placeholders(any.len())
+            ),
+            any,
+        );
+    }
+
+    let all = id_values(&query.tags.all);
+    if !all.is_empty() {
+        let count = all.len() as i64;
+        filter.push(
+            format!(
+                "(SELECT COUNT(*) FROM task_tags WHERE task_tags.task_id = tasks.id AND task_tags.tag_id IN ({})) = ?",
+                placeholders(all.len())
+            ),
+            all.into_iter().chain([Value::Integer(count)]),
+        );
+    }
+
+    let none = id_values(&query.tags.none);
+    if !none.is_empty() {
+        filter.push(
+            format!(
+                "NOT EXISTS (SELECT 1 FROM task_tags WHERE task_tags.task_id = tasks.id AND task_tags.tag_id IN ({}))",
+                placeholders(none.len())
+            ),
+            none,
+        );
+    }
+
+    if !query.priorities.is_empty() {
+        let levels: Vec<Value> = query
+            .priorities
+            .iter()
+            .flatten()
+            .map(|p| Value::Integer(p.as_i32() as i64))
+            .collect();
+        let mut conditions = Vec::new();
+        if !levels.is_empty() {
+            conditions.push(format!("tasks.priority IN ({})", placeholders(levels.len())));
+        }
+        if query.priorities.iter().any(|p| p.is_none()) {
+            conditions.push("tasks.priority IS NULL".to_string());
+        }
+        filter.push(format!("({})", conditions.join(" OR ")), levels);
+    }
+
+    if let Some(due) = &query.due {
+        filter.date_range("tasks.due_date", due);
+    }
+
+    match query.has_due_date {
+        Some(true) => filter.push("tasks.due_date IS NOT NULL", []),
+        Some(false) => filter.push("tasks.due_date IS NULL", []),
+        None => {}
+    }
+
+    if let Some(overdue) = query.overdue {
+        let clause = "(tasks.completed = 0 AND tasks.due_date IS NOT NULL AND julianday(tasks.due_date) < julianday(?))";
+        let now = Value::Text(Utc::now().to_rfc3339());
+        if overdue {
+            filter.push(clause, [now]);
+        } else {
+            filter.push(format!("NOT {}", clause), [now]);
+        }
+    }
+
+    if let Some(completed) = query.completed {
+        filter.push("tasks.completed = ?", [Value::Integer(completed as i64)]);
+    }
+
+    if let Some(created) = &query.created {
+        filter.date_range("tasks.created_at", created);
+    }
+
+    if let Some(completed_between) = &query.completed_between {
+        filter.date_range("tasks.completed_at", completed_between);
+    }
+
+    filter
+}
+
+fn order_by_sql(query: &TaskQuery) -> String {
+    let mut terms = Vec::new();
+
+    for sort in &query.sort {
+        let column = match sort.key {
+            TaskSortKey::Id => "tasks.id",
+            TaskSortKey::Name => "tasks.name COLLATE NOCASE",
+            TaskSortKey::DueDate => "julianday(tasks.due_date)",
+            TaskSortKey::CreatedAt => "julianday(tasks.created_at)",
+            TaskSortKey::CompletedAt => "julianday(tasks.completed_at)",
+            TaskSortKey::Priority => "tasks.priority",
+        };
+        let direction = if sort.descending { "DESC" } else { "ASC" };
+        // missing values always sort last, whatever the direction
+        terms.push(format!("{} IS NULL, {} {}", column, column, direction));
+    }
+
+    // keep pagination stable between pages
+    terms.push("tasks.id ASC".to_string());
+
+    format!("ORDER BY {}", terms.join(", "))
+}
+
+// Runs a structured task query on an already open connection
+pub fn run_query(conn: &Connection, query: &TaskQuery) -> Result<TaskPage, NoWorkError> {
+    let filter = build_filter(query);
+    let where_sql = filter.where_sql();
+
+    let total: i64 = conn
+        .query_row(
+            &format!("SELECT COUNT(*) FROM tasks {}", where_sql),
+            params_from_iter(filter.values.iter()),
+            |row| row.get(0),
+        )?;
+
+    let mut values = filter.values;
+    let mut tail = format!("{} {}", where_sql, order_by_sql(query));
+    if query.limit.is_some() || query.offset.is_some() {
+        tail.push_str(" LIMIT ? OFFSET ?");
+        values.push(Value::Integer(query.limit.map(i64::from).unwrap_or(-1)));
+        values.push(Value::Integer(query.offset.map(i64::from).unwrap_or(0)));
+    }
+
+    let tasks = load_tasks(conn, &tail, params_from_iter(values))?;
+
+    Ok(TaskPage { tasks, total })
+}
+
+// Local midnight of first_day up to local midnight `days` later
+pub fn local_day_range(first_day: NaiveDate, days: i64) -> DateRange {
+    let midnight = |day: NaiveDate| {
+        let naive = day.and_hms_opt(0, 0, 0).unwrap();
+        Local
+            .from_local_datetime(&naive)
+            .earliest()
+            .map(|dt| dt.with_timezone(&Utc))
+            .unwrap_or_else(|| Utc.from_utc_datetime(&naive))
+    };
+
+    DateRange {
+        start: Some(midnight(first_day)),
+        end: Some(midnight(first_day + Duration::days(days))),
+    }
+}
@@ -0,0 +1,856 @@
+use chrono::{DateTime, Datelike, Duration, Local, Months, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+use serde::Serialize;
+
+use crate::types::{NewTag, Recurrence, RecurrenceMode, TaskPriority};
+
+// What a part of the input was read as
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum QuickAddKind {
+    Due,
+    Priority,
+    Tag,
+    Recurrence,
+}
+
+// A recognized part of the input, as typed, for the preview to highlight
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct QuickAddMatch {
+    pub kind: QuickAddKind,
+    pub text: String,
+}
+
+// A task typed on one line, e.g. "Submit report fri 5pm #work !high". Its
+// fields are the arguments of add_database_task, the name is what is left
+// once everything recognized is taken out. New tags get the default color.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuickAdd {
+    pub name: String,
+    pub due_date: Option<DateTime<Utc>>,
+    pub priority: Option<TaskPriority>,
+    pub tags: Vec<NewTag>,
+    pub recurrence: Option<Recurrence>,
+    pub matches: Vec<QuickAddMatch>,
+}
+
+// A word of the input. Text in double quotes is one token that is never
+// read as anything but part of the name.
+struct Token<'a> {
+    text: &'a str,
+    // lowercase without trailing punctuation, empty for quoted text
+    word: String,
+    // byte range in the input, quotes included
+    start: usize,
+    end: usize,
+}
+
+fn tokenize(input: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+
+        if c == '"' {
+            chars.next();
+            let closing = chars.by_ref().find(|&(_, c)| c == '"').map(|(i, _)| i);
+            let text_end = closing.unwrap_or(input.len());
+            if text_end > start + 1 {
+                tokens.push(Token {
+                    text: &input[start + 1..text_end],
+                    word: String::new(),
+                    start,
+                    end: closing.map_or(input.len(), |i| i + 1),
+                });
+            }
+            continue;
+        }
+
+        let mut end = input.len();
+        while let Some(&(i, c)) = chars.peek() {
+            if c.is_whitespace() || c == '"' {
+                end = i;
+                break;
+            }
+            chars.next();
+        }
+        let text = &input[start..end];
+        tokens.push(Token {
+            text,
+            word: text.trim_end_matches([',', '.', ';']).to_lowercase(),
+            start,
+            end,
+        });
+    }
+
+    tokens
+}
+
+// A due date or time of day read from the input, either may be missing
+#[derive(Clone, Copy, Default)]
+struct When {
+    date: Option<NaiveDate>,
+    time: Option<NaiveTime>,
+}
+
+// A recurrence phrase, with what the first occurrence must fall on
+struct Repeat {
+    rule: String,
+    mode: RecurrenceMode,
+    weekdays: Vec<Weekday>,
+    month_day: Option<u32>,
+}
+
+impl Repeat {
+    fn allows(&self, day: NaiveDate) -> bool {
+        (self.weekdays.is_empty() || self.weekdays.contains(&day.weekday()))
+            && self.month_day.is_none_or(|n| day.day() == n)
+    }
+}
+
+// Reads input relative to the local time now
+pub fn parse(input: &str) -> QuickAdd {
+    parse_at(input, Local::now().naive_local())
+}
+
+// Tags and the first priority, due date and recurrence are taken out of the
+// input, anything after those stays in the name. Weekdays mean the next one,
+// today included, "next fri" the one a week later. A date without a time is
+// due at midnight, a time without a date today or, once it has passed,
+// tomorrow. A recurrence without a date is due on its first occurrence.
+pub fn parse_at(input: &str, now: NaiveDateTime) -> QuickAdd {
+    let tokens = tokenize(input);
+    let words: Vec<&str> = tokens.iter().map(|t| t.word.as_str()).collect();
+    let mut used = vec![false; tokens.len()];
+    let mut matches = Vec::new();
+
+    let mut tags: Vec<NewTag> = Vec::new();
+    let mut priority = None;
+    let mut when = None;
+    let mut repeat = None;
+
+    let mut i = 0;
+    while i < tokens.len() {
+        let found = if words[i].is_empty() {
+            None
+        } else if let Some(name) = tag_name(tokens[i].text) {
+            if !tags.iter().any(|tag| tag.name.eq_ignore_ascii_case(name)) {
+                tags.push(NewTag {
+                    name: name.to_string(),
+                    color: "default".to_string(),
+                });
+            }
+            Some((1, QuickAddKind::Tag))
+        } else if let Some(p) = priority.is_none().then(|| parse_priority(words[i])).flatten() {
+            priority = Some(p);
+            Some((1, QuickAddKind::Priority))
+        } else if let Some((n, r)) = repeat.is_none().then(|| parse_repeat(&words, i)).flatten() {
+            repeat = Some(r);
+            Some((n, QuickAddKind::Recurrence))
+        } else if let Some((n, w)) = when.is_none().then(|| parse_when(&words, i, now)).flatten() {
+            when = Some(w);
+            Some((n, QuickAddKind::Due))
+        } else {
+            None
+        };
+
+        match found {
+            Some((n, kind)) => {
+                used[i..i + n].fill(true);
+                matches.push(QuickAddMatch {
+                    kind,
+                    text: input[tokens[i].start..tokens[i + n - 1].end].to_string(),
+                });
+                i += n;
+            }
+            None => i += 1,
+        }
+    }
+
+    let name = tokens
+        .iter()
+        .zip(&used)
+        .filter(|(_, used)| !**used)
+        .map(|(token, _)| token.text)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let due = due_at(when.unwrap_or_default(), repeat.as_ref(), now);
+    QuickAdd {
+        name,
+        due_date: due.map(|naive| {
+            Local
+                .from_local_datetime(&naive)
+                .earliest()
+                .map(|dt| dt.with_timezone(&Utc))
+                .unwrap_or_else(|| Utc.from_utc_datetime(&naive))
+        }),
+        priority,
+        tags,
+        recurrence: repeat.map(|r| Recurrence {
+            rule: r.rule,
+            mode: r.mode,
+        }),
+        matches,
+    }
+}
+
+fn due_at(when: When, repeat: Option<&Repeat>, now: NaiveDateTime) -> Option<NaiveDateTime> {
+    let today = now.date();
+    let passed = |day: NaiveDate| day == today && when.time.is_some_and(|time| time < now.time());
+
+    let date = match (when.date, repeat) {
+        (Some(date), _) => date,
+        (None, Some(repeat)) => (0..366)
+            .map(|days| today + Duration::days(days))
+            .find(|&day| repeat.allows(day) && !passed(day))?,
+        (None, None) => {
+            when.time?;
+            if passed(today) {
+                today + Duration::days(1)
+            } else {
+                today
+            }
+        }
+    };
+    Some(date.and_time(when.time.unwrap_or(NaiveTime::MIN)))
+}
+
+// "#work" is the tag work, "#1" is not a tag
+fn tag_name(text: &str) -> Option<&str> {
+    let name = text.strip_prefix('#')?.trim_end_matches([',', '.', ';']);
+    name.chars().any(char::is_alphabetic).then_some(name)
+}
+
+fn parse_priority(word: &str) -> Option<TaskPriority> {
+    match word.strip_prefix('!')? {
+        "high" | "h" => Some(TaskPriority::High),
+        "medium" | "med" | "m" => Some(TaskPriority::Medium),
+        "low" | "l" => Some(TaskPriority::Low),
+        _ => None,
+    }
+}
+
+fn parse_weekday(word: &str) -> Option<Weekday> {
+    match word {
+        "mon" | "monday" => Some(Weekday::Mon),
+        "tue" | "tues" | "tuesday" => Some(Weekday::Tue),
+        "wed" | "wednesday" => Some(Weekday::Wed),
+        "thu" | "thur" | "thurs" | "thursday" => Some(Weekday::Thu),
+        "fri" | "friday" => Some(Weekday::Fri),
+        "sat" | "saturday" => Some(Weekday::Sat),
+        "sun" | "sunday" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+fn parse_month(word: &str) -> Option<u32> {
+    let month = match word {
+        "jan" | "january" => 1,
+        "feb" | "february" => 2,
+        "mar" | "march" => 3,
+        "apr" | "april" => 4,
+        "may" => 5,
+        "jun" | "june" => 6,
+        "jul" | "july" => 7,
+        "aug" | "august" => 8,
+        "sep" | "sept" | "september" => 9,
+        "oct" | "october" => 10,
+        "nov" | "november" => 11,
+        "dec" | "december" => 12,
+        _ => return None,
+    };
+    Some(month)
+}
+
+// "14", or "14th" with ordinal set, a day of the month
+fn parse_day(word: &str, ordinal: bool) -> Option<u32> {
+    let digits = ["st", "nd", "rd", "th"]
+        .iter()
+        .find_map(|suffix| word.strip_suffix(suffix))
+        .or_else(|| (!ordinal).then_some(word))?;
+    if digits.is_empty() || digits.len() > 2 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    digits.parse().ok().filter(|day| (1..=31).contains(day))
+}
+
+fn weekday_code(day: Weekday) -> &'static str {
+    match day {
+        Weekday::Mon => "MO",
+        Weekday::Tue => "TU",
+        Weekday::Wed => "WE",
+        Weekday::Thu => "TH",
+        Weekday::Fri => "FR",
+        Weekday::Sat => "SA",
+        Weekday::Sun => "SU",
+    }
+}
+
+// the day itself if it falls on weekday
+fn upcoming(today: NaiveDate, weekday: Weekday) -> NaiveDate {
+    let ahead = (7 + weekday.num_days_from_monday() - today.weekday().num_days_from_monday()) % 7;
+    today + Duration::days(ahead as i64)
+}
+
+// A date with the number of words it took: today, tomorrow, weekdays,
+// next week/month/year, 2026-03-14, "mar 14" and "14th march" with an
+// optional year. Dates without a year that have passed are next year's.
+fn parse_date(words: &[&str], i: usize, today: NaiveDate) -> Option<(usize, NaiveDate)> {
+    let word = *words.get(i)?;
+    let next = words.get(i + 1).copied().unwrap_or_default();
+
+    match word {
+        "today" => return Some((1, today)),
+        "tomorrow" | "tmr" | "tmrw" => return Some((1, today + Duration::days(1))),
+        "next" => {
+            let date = match next {
+                "week" => today + Duration::days(7),
+                "month" => today.checked_add_months(Months::new(1))?,
+                "year" => today.checked_add_months(Months::new(12))?,
+                _ => upcoming(today, parse_weekday(next)?) + Duration::days(7),
+            };
+            return Some((2, date));
+        }
+        "this" => return Some((2, upcoming(today, parse_weekday(next)?))),
+        _ => {}
+    }
+
+    if let Some(weekday) = parse_weekday(word) {
+        return Some((1, upcoming(today, weekday)));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(word, "%Y-%m-%d") {
+        return Some((1, date));
+    }
+
+    let (month, day) = match (parse_month(word), parse_day(word, false)) {
+        (Some(month), _) => (month, parse_day(next, false)?),
+        (None, Some(day)) => (parse_month(next)?, day),
+        (None, None) => return None,
+    };
+    let year = words
+        .get(i + 2)
+        .filter(|word| word.len() == 4)
+        .and_then(|word| word.parse::<i32>().ok());
+    match year {
+        Some(year) => Some((3, NaiveDate::from_ymd_opt(year, month, day)?)),
+        None => {
+            let date = NaiveDate::from_ymd_opt(today.year(), month, day)?;
+            if date >= today {
+                Some((2, date))
+            } else {
+                Some((2, NaiveDate::from_ymd_opt(today.year() + 1, month, day)?))
+            }
+        }
+    }
+}
+
+// "5pm", "5:30 pm", "17:00" or "noon"
+fn parse_time(words: &[&str], i: usize) -> Option<(usize, NaiveTime)> {
+    let word = *words.get(i)?;
+    if word == "noon" {
+        return Some((1, NaiveTime::from_hms_opt(12, 0, 0)?));
+    }
+    match words.get(i + 1) {
+        Some(&suffix) if suffix == "am" || suffix == "pm" => Some((2, clock(&format!("{}{}", word, suffix))?)),
+        _ => Some((1, clock(word)?)),
+    }
+}
+
+fn clock(word: &str) -> Option<NaiveTime> {
+    let (digits, pm) = match (word.strip_suffix("am"), word.strip_suffix("pm")) {
+        (Some(digits), _) => (digits, Some(false)),
+        (_, Some(digits)) => (digits, Some(true)),
+        _ => (word, None),
+    };
+    let (hour, minute) = match digits.split_once(':') {
+        Some((hour, minute)) if minute.len() == 2 => (hour, minute.parse().ok()?),
+        Some(_) => return None,
+        // without am or pm a time needs its minutes, "5" is just a number
+        None if pm.is_some() => (digits, 0),
+        None => return None,
+    };
+    if hour.is_empty() || hour.len() > 2 || !hour.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let hour: u32 = hour.parse().ok()?;
+    let hour = match pm {
+        Some(_) if !(1..=12).contains(&hour) => return None,
+        Some(true) => hour % 12 + 12,
+        Some(false) => hour % 12,
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+// "in 3 days", "in a week", "in 2 hours"
+fn parse_relative(words: &[&str], i: usize, now: NaiveDateTime) -> Option<(usize, When)> {
+    if *words.get(i)? != "in" {
+        return None;
+    }
+    let amount: u32 = match *words.get(i + 1)? {
+        "a" | "an" | "one" => 1,
+        number => number.parse().ok().filter(|n| *n > 0)?,
+    };
+    let at = |dt: NaiveDateTime| When {
+        date: Some(dt.date()),
+        time: Some(dt.time()),
+    };
+    let on = |date: NaiveDate| When {
+        date: Some(date),
+        time: None,
+    };
+    let today = now.date();
+
+    let when = match *words.get(i + 2)? {
+        "min" | "mins" | "minute" | "minutes" => at(now + Duration::minutes(amount as i64)),
+        "hour" | "hours" | "hr" | "hrs" => at(now + Duration::hours(amount as i64)),
+        "day" | "days" => on(today + Duration::days(amount as i64)),
+        "week" | "weeks" => on(today + Duration::weeks(amount as i64)),
+        "month" | "months" => on(today.checked_add_months(Months::new(amount))?),
+        "year" | "years" => on(today.checked_add_months(Months::new(amount.checked_mul(12)?))?),
+        _ => return None,
+    };
+    Some((3, when))
+}
+
+// A due date, time or both, optionally after "on", "at", "by", "due" or
+// "from", the last for recurrences:
+// "fri 5pm", "tomorrow at 9:30", "at noon on mar 3", "in 2 weeks"
+fn parse_when(words: &[&str], i: usize, now: NaiveDateTime) -> Option<(usize, When)> {
+    let today = now.date();
+    let start = match words[i] {
+        "on" | "at" | "by" | "due" | "from" => i + 1,
+        _ => i,
+    };
+    // the joining word only counts with a date or time after it
+    let skip = |j: usize, joiner: &str| if words.get(j) == Some(&joiner) { j + 1 } else { j };
+
+    if let Some((n, when)) = parse_relative(words, start, now) {
+        return Some((start + n - i, when));
+    }
+
+    if let Some((n, date)) = parse_date(words, start, today) {
+        let end = start + n;
+        let time_at = skip(end, "at");
+        return Some(match parse_time(words, time_at) {
+            Some((m, time)) => (time_at + m - i, When { date: Some(date), time: Some(time) }),
+            None => (end - i, When { date: Some(date), time: None }),
+        });
+    }
+
+    let (n, time) = parse_time(words, start)?;
+    let end = start + n;
+    let date_at = skip(end, "on");
+    Some(match parse_date(words, date_at, today) {
+        Some((m, date)) => (date_at + m - i, When { date: Some(date), time: Some(time) }),
+        None => (end - i, When { date: None, time: Some(time) }),
+    })
+}
+
+// "every day", "every other week", "every 3 months", "every mon and thu",
+// "every weekday", "every 1st", "every month on the 15th", each optionally
+// followed by "after completion" to count from when the task is done
+fn parse_repeat(words: &[&str], i: usize) -> Option<(usize, Repeat)> {
+    if words[i] != "every" {
+        return None;
+    }
+    let mut j = i + 1;
+    let interval = match *words.get(j)? {
+        "other" => 2,
+        word => match word.parse::<u32>() {
+            Ok(n) if n > 0 => n,
+            Ok(_) => return None,
+            Err(_) => 1,
+        },
+    };
+    if interval > 1 || words[j] == "1" {
+        j += 1;
+    }
+
+    let mut weekdays = Vec::new();
+    let mut month_day = None;
+    let freq = match *words.get(j)? {
+        "day" | "days" => "DAILY",
+        "week" | "weeks" => "WEEKLY",
+        "month" | "months" => {
+            // "every month on the 15th"
+            let on = (words.get(j + 1) == Some(&"on")).then_some(j + 2);
+            let day_at = on.map(|k| if words.get(k) == Some(&"the") { k + 1 } else { k });
+            if let Some((k, day)) = day_at.and_then(|k| Some((k, parse_day(words.get(k)?, true)?))) {
+                month_day = Some(day);
+                j = k;
+            }
+            "MONTHLY"
+        }
+        "year" | "years" => "YEARLY",
+        _ if interval > 1 => return None,
+        "weekday" | "weekdays" | "workday" | "workdays" => {
+            weekdays = vec![Weekday::Mon, Weekday::Tue, Weekday::Wed, Weekday::Thu, Weekday::Fri];
+            "WEEKLY"
+        }
+        "weekend" | "weekends" => {
+            weekdays = vec![Weekday::Sat, Weekday::Sun];
+            "WEEKLY"
+        }
+        word => {
+            if let Some(day) = parse_day(word, true) {
+                month_day = Some(day);
+                "MONTHLY"
+            } else {
+                // "mon, wed and fri"
+                weekdays.push(parse_weekday(word)?);
+                loop {
+                    let k = if matches!(words.get(j + 1), Some(&"and") | Some(&"&")) { j + 2 } else { j + 1 };
+                    let Some(day) = words.get(k).and_then(|word| parse_weekday(word)) else {
+                        break;
+                    };
+                    if !weekdays.contains(&day) {
+                        weekdays.push(day);
+                    }
+                    j = k;
+                }
+                "WEEKLY"
+            }
+        }
+    };
+
+    let mut rule = format!("FREQ={}", freq);
+    if interval > 1 {
+        rule += &format!(";INTERVAL={}", interval);
+    }
+    if !weekdays.is_empty() {
+        let days: Vec<&str> = weekdays.iter().map(|day| weekday_code(*day)).collect();
+        rule += &format!(";BYDAY={}", days.join(","));
+    }
+    if let Some(day) = month_day {
+        rule += &format!(";BYMONTHDAY={}", day);
+    }
+
+    let mut end = j + 1;
+    let mut mode = RecurrenceMode::FixedSchedule;
+    if words.get(end) == Some(&"after") && words.get(end + 1) == Some(&"completion") {
+        mode = RecurrenceMode::AfterCompletion;
+        end += 2;
+    }
+
+    Some((
+        end - i,
+        Repeat {
+            rule,
+            mode,
+            weekdays,
+            month_day,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Wednesday 2026-03-11, 10:00 local time
+    fn now() -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2026, 3, 11).unwrap().and_hms_opt(10, 0, 0).unwrap()
+    }
+
+    fn at(month: u32, day: u32, hour: u32, minute: u32) -> Option<NaiveDateTime> {
+        NaiveDate::from_ymd_opt(2026, month, day).unwrap().and_hms_opt(hour, minute, 0)
+    }
+
+    fn due(parsed: &QuickAdd) -> Option<NaiveDateTime> {
+        parsed.due_date.map(|due| due.with_timezone(&Local).naive_local())
+    }
+
+    fn rule(parsed: &QuickAdd) -> Option<&str> {
+        parsed.recurrence.as_ref().map(|r| r.rule.as_str())
+    }
+
+    fn tag_names(parsed: &QuickAdd) -> Vec<&str> {
+        parsed.tags.iter().map(|tag| tag.name.as_str()).collect()
+    }
+
+    fn priority(parsed: &QuickAdd) -> Option<i32> {
+        parsed.priority.as_ref().map(TaskPriority::as_i32)
+    }
+
+    #[test]
+    fn parses_the_full_example() {
+        let parsed = parse_at("Submit report fri 5pm #work !high", now());
+        assert_eq!(parsed.name, "Submit report");
+        assert_eq!(due(&parsed), at(3, 13, 17, 0));
+        assert_eq!(tag_names(&parsed), ["work"]);
+        assert_eq!(parsed.tags[0].color, "default");
+        assert_eq!(priority(&parsed), Some(TaskPriority::High.as_i32()));
+        assert!(parsed.recurrence.is_none());
+        assert_eq!(
+            parsed.matches,
+            [
+                QuickAddMatch {
+                    kind: QuickAddKind::Due,
+                    text: "fri 5pm".to_string()
+                },
+                QuickAddMatch {
+                    kind: QuickAddKind::Tag,
+                    text: "#work".to_string()
+                },
+                QuickAddMatch {
+                    kind: QuickAddKind::Priority,
+                    text: "!high".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn monthly_on_an_ordinal_is_due_on_the_next_one() {
+        let parsed = parse_at("pay rent every 1st", now());
+        assert_eq!(parsed.name, "pay rent");
+        assert_eq!(rule(&parsed), Some("FREQ=MONTHLY;BYMONTHDAY=1"));
+        assert!(matches!(parsed.recurrence.as_ref().unwrap().mode, RecurrenceMode::FixedSchedule));
+        assert_eq!(due(&parsed), at(4, 1, 0, 0));
+    }
+
+    #[test]
+    fn plain_text_is_all_name() {
+        let parsed = parse_at("  Water   the plants ", now());
+        assert_eq!(parsed.name, "Water the plants");
+        assert!(parsed.due_date.is_none());
+        assert!(parsed.priority.is_none());
+        assert!(parsed.tags.is_empty());
+        assert!(parsed.recurrence.is_none());
+        assert!(parsed.matches.is_empty());
+    }
+
+    #[test]
+    fn relative_day_words() {
+        assert_eq!(due(&parse_at("a today", now())), at(3, 11, 0, 0));
+        assert_eq!(due(&parse_at("a tomorrow", now())), at(3, 12, 0, 0));
+        assert_eq!(due(&parse_at("a tmrw", now())), at(3, 12, 0, 0));
+        assert_eq!(due(&parse_at("a Tomorrow.", now())), at(3, 12, 0, 0));
+        assert_eq!(due(&parse_at("a next week", now())), at(3, 18, 0, 0));
+        assert_eq!(due(&parse_at("a next month", now())), at(4, 11, 0, 0));
+        assert_eq!(
+            due(&parse_at("a next year", now())),
+            NaiveDate::from_ymd_opt(2027, 3, 11).unwrap().and_hms_opt(0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn weekdays_are_the_upcoming_one() {
+        // today is a Wednesday
+        assert_eq!(due(&parse_at("a wed", now())), at(3, 11, 0, 0));
+        assert_eq!(due(&parse_at("a thursday", now())), at(3, 12, 0, 0));
+        assert_eq!(due(&parse_at("a mon", now())), at(3, 16, 0, 0));
+        assert_eq!(due(&parse_at("a this sat", now())), at(3, 14, 0, 0));
+        assert_eq!(due(&parse_at("a next fri", now())), at(3, 20, 0, 0));
+        assert_eq!(due(&parse_at("a next wed", now())), at(3, 18, 0, 0));
+    }
+
+    #[test]
+    fn times_of_day() {
+        assert_eq!(due(&parse_at("a tomorrow 9am", now())), at(3, 12, 9, 0));
+        assert_eq!(due(&parse_at("a tomorrow at 9:30", now())), at(3, 12, 9, 30));
+        assert_eq!(due(&parse_at("a fri 5:45 PM", now())), at(3, 13, 17, 45));
+        assert_eq!(due(&parse_at("a fri at noon", now())), at(3, 13, 12, 0));
+        assert_eq!(due(&parse_at("a tomorrow 12am", now())), at(3, 12, 0, 0));
+        assert_eq!(due(&parse_at("a tomorrow 12pm", now())), at(3, 12, 12, 0));
+        assert_eq!(due(&parse_at("a at 5pm on fri", now())), at(3, 13, 17, 0));
+        assert_eq!(due(&parse_at("a by 17:00 tomorrow", now())), at(3, 12, 17, 0));
+    }
+
+    #[test]
+    fn a_time_alone_is_the_next_time_it_comes() {
+        assert_eq!(due(&parse_at("call mum at 3pm", now())), at(3, 11, 15, 0));
+        assert_eq!(due(&parse_at("call mum at 9am", now())), at(3, 12, 9, 0));
+        assert_eq!(parse_at("call mum at 3pm", now()).name, "call mum");
+    }
+
+    #[test]
+    fn absolute_dates() {
+        assert_eq!(due(&parse_at("a 2026-05-02", now())), at(5, 2, 0, 0));
+        assert_eq!(due(&parse_at("a on mar 14", now())), at(3, 14, 0, 0));
+        assert_eq!(due(&parse_at("a 14th march", now())), at(3, 14, 0, 0));
+        assert_eq!(due(&parse_at("a December 3rd at 8pm", now())), at(12, 3, 20, 0));
+        assert_eq!(
+            due(&parse_at("a jan 5 2028", now())),
+            NaiveDate::from_ymd_opt(2028, 1, 5).unwrap().and_hms_opt(0, 0, 0)
+        );
+        // passed this year, so next year's
+        assert_eq!(
+            due(&parse_at("a feb 1", now())),
+            NaiveDate::from_ymd_opt(2027, 2, 1).unwrap().and_hms_opt(0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn impossible_dates_stay_in_the_name() {
+        let parsed = parse_at("a feb 30", now());
+        assert_eq!(parsed.name, "a feb 30");
+        assert!(parsed.due_date.is_none());
+
+        let parsed = parse_at("a 2026-13-01", now());
+        assert_eq!(parsed.name, "a 2026-13-01");
+        assert!(parsed.due_date.is_none());
+
+        let parsed = parse_at("a at 25:00", now());
+        assert_eq!(parsed.name, "a at 25:00");
+        assert!(parsed.due_date.is_none());
+    }
+
+    #[test]
+    fn durations_from_now() {
+        assert_eq!(due(&parse_at("a in 3 days", now())), at(3, 14, 0, 0));
+        assert_eq!(due(&parse_at("a in a week", now())), at(3, 18, 0, 0));
+        assert_eq!(due(&parse_at("a in 2 months", now())), at(5, 11, 0, 0));
+        assert_eq!(due(&parse_at("a in 2 hours", now())), at(3, 11, 12, 0));
+        assert_eq!(due(&parse_at("a in 30 mins", now())), at(3, 11, 10, 30));
+        assert_eq!(due(&parse_at("a due in 1 day", now())), at(3, 12, 0, 0));
+
+        let parsed = parse_at("fly in 3 planes", now());
+        assert_eq!(parsed.name, "fly in 3 planes");
+        assert!(parsed.due_date.is_none());
+    }
+
+    #[test]
+    fn connectors_without_a_date_stay_in_the_name() {
+        let parsed = parse_at("meet at the office", now());
+        assert_eq!(parsed.name, "meet at the office");
+        assert!(parsed.due_date.is_none());
+
+        let parsed = parse_at("read chapter 5", now());
+        assert_eq!(parsed.name, "read chapter 5");
+        assert!(parsed.due_date.is_none());
+    }
+
+    #[test]
+    fn only_the_first_date_is_used() {
+        let parsed = parse_at("move tuesday meeting to thursday", now());
+        assert_eq!(due(&parsed), at(3, 17, 0, 0));
+        assert_eq!(parsed.name, "move meeting to thursday");
+    }
+
+    #[test]
+    fn simple_recurrences() {
+        assert_eq!(rule(&parse_at("a every day", now())), Some("FREQ=DAILY"));
+        assert_eq!(rule(&parse_at("a every week", now())), Some("FREQ=WEEKLY"));
+        assert_eq!(rule(&parse_at("a every month", now())), Some("FREQ=MONTHLY"));
+        assert_eq!(rule(&parse_at("a every year", now())), Some("FREQ=YEARLY"));
+        assert_eq!(rule(&parse_at("a every 1 day", now())), Some("FREQ=DAILY"));
+        assert_eq!(rule(&parse_at("a every other week", now())), Some("FREQ=WEEKLY;INTERVAL=2"));
+        assert_eq!(rule(&parse_at("a every 3 months", now())), Some("FREQ=MONTHLY;INTERVAL=3"));
+        // starts today
+        assert_eq!(due(&parse_at("a every day", now())), at(3, 11, 0, 0));
+    }
+
+    #[test]
+    fn weekday_recurrences() {
+        let parsed = parse_at("standup every weekday at 9:30", now());
+        assert_eq!(parsed.name, "standup");
+        assert_eq!(rule(&parsed), Some("FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR"));
+        // 9:30 has passed today
+        assert_eq!(due(&parsed), at(3, 12, 9, 30));
+
+        let parsed = parse_at("gym every mon, wed and fri", now());
+        assert_eq!(parsed.name, "gym");
+        assert_eq!(rule(&parsed), Some("FREQ=WEEKLY;BYDAY=MO,WE,FR"));
+        assert_eq!(due(&parsed), at(3, 11, 0, 0));
+
+        let parsed = parse_at("laundry every saturday", now());
+        assert_eq!(rule(&parsed), Some("FREQ=WEEKLY;BYDAY=SA"));
+        assert_eq!(due(&parsed), at(3, 14, 0, 0));
+
+        let parsed = parse_at("brunch every weekend", now());
+        assert_eq!(rule(&parsed), Some("FREQ=WEEKLY;BYDAY=SA,SU"));
+        assert_eq!(due(&parsed), at(3, 14, 0, 0));
+    }
+
+    #[test]
+    fn monthly_recurrences() {
+        let parsed = parse_at("invoice every month on the 15th", now());
+        assert_eq!(parsed.name, "invoice");
+        assert_eq!(rule(&parsed), Some("FREQ=MONTHLY;BYMONTHDAY=15"));
+        assert_eq!(due(&parsed), at(3, 15, 0, 0));
+
+        let parsed = parse_at("bills every 11th at 8am", now());
+        assert_eq!(rule(&parsed), Some("FREQ=MONTHLY;BYMONTHDAY=11"));
+        // 8am today has passed, so next month's
+        assert_eq!(due(&parsed), at(4, 11, 8, 0));
+    }
+
+    #[test]
+    fn recurrence_with_a_start_date() {
+        let parsed = parse_at("review every 2 weeks from next mon", now());
+        assert_eq!(rule(&parsed), Some("FREQ=WEEKLY;INTERVAL=2"));
+        assert_eq!(due(&parsed), at(3, 23, 0, 0));
+        assert_eq!(parsed.name, "review");
+    }
+
+    #[test]
+    fn after_completion_recurrences() {
+        let parsed = parse_at("water plants every 3 days after completion", now());
+        assert_eq!(parsed.name, "water plants");
+        assert_eq!(rule(&parsed), Some("FREQ=DAILY;INTERVAL=3"));
+        assert!(matches!(parsed.recurrence.as_ref().unwrap().mode, RecurrenceMode::AfterCompletion));
+    }
+
+    #[test]
+    fn everyday_words_are_not_recurrences() {
+        for input in ["every little thing", "every 0 days", "every other thing"] {
+            let parsed = parse_at(input, now());
+            assert_eq!(parsed.name, input);
+            assert!(parsed.recurrence.is_none());
+        }
+    }
+
+    #[test]
+    fn priorities() {
+        assert_eq!(priority(&parse_at("a !h", now())), Some(TaskPriority::High.as_i32()));
+        assert_eq!(priority(&parse_at("a !med", now())), Some(TaskPriority::Medium.as_i32()));
+        assert_eq!(priority(&parse_at("a !Medium", now())), Some(TaskPriority::Medium.as_i32()));
+        assert_eq!(priority(&parse_at("a !low", now())), Some(TaskPriority::Low.as_i32()));
+
+        let parsed = parse_at("a !low !high", now());
+        assert_eq!(priority(&parsed), Some(TaskPriority::Low.as_i32()));
+        assert_eq!(parsed.name, "a !high");
+
+        let parsed = parse_at("wow! !urgent", now());
+        assert!(parsed.priority.is_none());
+        assert_eq!(parsed.name, "wow! !urgent");
+    }
+
+    #[test]
+    fn tags() {
+        let parsed = parse_at("#home fix sink #Plumbing, #home #HOME", now());
+        assert_eq!(parsed.name, "fix sink");
+        assert_eq!(tag_names(&parsed), ["home", "Plumbing"]);
+
+        let parsed = parse_at("#1 fan of # things", now());
+        assert_eq!(parsed.name, "#1 fan of # things");
+        assert!(parsed.tags.is_empty());
+    }
+
+    #[test]
+    fn quoted_text_is_never_parsed() {
+        let parsed = parse_at("\"Friday at 5pm\" tickets #music tomorrow", now());
+        assert_eq!(parsed.name, "Friday at 5pm tickets");
+        assert_eq!(due(&parsed), at(3, 12, 0, 0));
+        assert_eq!(tag_names(&parsed), ["music"]);
+
+        let parsed = parse_at("read \"every day", now());
+        assert_eq!(parsed.name, "read every day");
+        assert!(parsed.recurrence.is_none());
+    }
+
+    #[test]
+    fn matches_keep_the_typed_text() {
+        let parsed = parse_at("Ship it On Fri At 5PM, every Other week", now());
+        let texts: Vec<&str> = parsed.matches.iter().map(|m| m.text.as_str()).collect();
+        assert_eq!(texts, ["On Fri At 5PM,", "every Other week"]);
+        assert_eq!(parsed.name, "Ship it");
+    }
+}
@@ -0,0 +1,297 @@
+use chrono::{DateTime, Duration, Local, TimeZone, Timelike, Utc};
+use rrule::{RRule, Tz, Unvalidated};
+use rusqlite::{params, Connection};
+
+use crate::error::NoWorkError;
+use crate::tasks::load_task;
+use crate::types::{Recurrence, RecurrenceMode, Task};
+
+// Strips an optional "RRULE:" prefix and checks that the rule parses and validates
+pub fn validate(recurrence: Recurrence) -> Result<Recurrence, NoWorkError> {
+    let rule = recurrence.rule.trim().to_uppercase();
+    let rule = rule.strip_prefix("RRULE:").unwrap_or(&rule).to_string();
+
+    let now = Local::now().with_timezone(&Tz::LOCAL);
+    rule.parse::<RRule<Unvalidated>>()?
+        .build(now)?;
+
+    Ok(Recurrence {
+        rule,
+        mode: recurrence.mode,
+    })
+}
+
+// COUNT of a rule, the occurrences left including the task that carries it
+fn count(rule: &str) -> Option<u32> {
+    rule.split(';')
+        .find_map(|part| part.strip_prefix("COUNT="))
+        .and_then(|n| n.parse().ok())
+}
+
+// The rule with its COUNT replaced
+fn with_count(rule: &str, count: u32) -> String {
+    rule.split(';')
+        .map(|part| if part.starts_with("COUNT=") { format!("COUNT={}", count) } else { part.to_string() })
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+// Computes the due date of the occurrence following a completed task.
+// Returns None once the rule is exhausted (COUNT/UNTIL).
+pub fn next_due_date(
+    recurrence: &Recurrence,
+    due_date: Option<DateTime<Utc>>,
+    completed_at: DateTime<Utc>,
+) -> Result<Option<DateTime<Utc>>, NoWorkError> {
+    // the set below starts over at every occurrence, so COUNT is what is left
+    if count(&recurrence.rule).is_some_and(|n| n <= 1) {
+        return Ok(None);
+    }
+
+    // rules are expanded in local time so "every monday" means the user's monday
+    let completed_local = completed_at.with_timezone(&Local);
+
+    let (dt_start, after) = match (recurrence.mode, due_date) {
+        (RecurrenceMode::FixedSchedule, Some(due)) => (due.with_timezone(&Local), due.max(completed_at)),
+        (RecurrenceMode::AfterCompletion, Some(due)) => {
+            // keep the time of day the task was due at
+            let due_local = due.with_timezone(&Local);
+            let start = completed_local
+                .date_naive()
+                .and_hms_opt(due_local.hour(), due_local.minute(), due_local.second())
+                .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+                .unwrap_or(completed_local);
+            (start, start.with_timezone(&Utc))
+        }
+        (_, None) => (completed_local, completed_at),
+    };
+
+    let set = recurrence
+        .rule
+        .parse::<RRule<Unvalidated>>()?
+        .build(dt_start.with_timezone(&Tz::LOCAL))?;
+
+    // the rrule crate's lower bound is inclusive, nudge it so the anchor itself is skipped
+    let after = (after + Duration::seconds(1)).with_timezone(&Tz::LOCAL);
+    let next = set.after(after).all(1).dates.into_iter().next();
+
+    Ok(next.map(|dt| dt.with_timezone(&Utc)))
+}
+
+// Inserts the next occurrence of a just completed recurring task, copying its
+// priority, tags and checklist. The rule moves over to the new task so the
+// completed one can never spawn twice, with one less COUNT as the completed
+// task used one up. Returns the id of the new task, if any.
+pub fn spawn_next_occurrence(conn: &Connection, task_id: i32) -> Result<Option<i64>, NoWorkError> {
+    let task = load_task(conn, task_id)?;
+    let Some(recurrence) = task.recurrence else {
+        return Ok(None);
+    };
+
+    let completed_at = task.completed_at.unwrap_or_else(Utc::now);
+    let Some(next_due) = next_due_date(&recurrence, task.due_date, completed_at)? else {
+        return Ok(None);
+    };
+    let rule = match count(&recurrence.rule) {
+        Some(n) => with_count(&recurrence.rule, n - 1),
+        None => recurrence.rule,
+    };
+
+    conn.execute(
+        "INSERT INTO tasks (name, due_date, priority, recurrence_rule, recurrence_mode)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            task.name,
+            next_due.to_rfc3339(),
+            task.priority.map(|p| p.as_i32()),
+            rule,
+            recurrence.mode.as_i32()
+        ],
+    )?;
+
+    let new_id = conn.last_insert_rowid();
+
+    conn.execute(
+        "INSERT INTO task_tags (task_id, tag_id)
+         SELECT ?1, tag_id FROM task_tags WHERE task_id = ?2",
+        params![new_id, task_id],
+    )?;
+
+    // the next occurrence starts with a fresh copy of the checklist
+    conn.execute(
+        "INSERT INTO tasks (name, parent_id, position)
+         SELECT name, ?1, position FROM tasks WHERE parent_id = ?2",
+        params![new_id, task_id],
+    )?;
+
+    conn.execute(
+        "UPDATE tasks SET recurrence_rule = NULL, recurrence_mode = NULL WHERE id = ?1",
+        params![task_id],
+    )?;
+
+    Ok(Some(new_id))
+}
+
+// Replaces a task's recurrence, or removes it when None
+pub fn update_task_recurrence(
+    conn: &Connection,
+    task_id: i32,
+    new_recurrence: Option<Recurrence>,
+) -> Result<Task, NoWorkError> {
+    let new_recurrence = new_recurrence.map(validate).transpose()?;
+
+    let rule = new_recurrence.as_ref().map(|r| r.rule.clone());
+    let mode = new_recurrence.as_ref().map(|r| r.mode.as_i32());
+
+    let rows = conn
+        .execute(
+            "UPDATE tasks SET recurrence_rule = ?1, recurrence_mode = ?2 WHERE id = ?3",
+            params![rule, mode, task_id],
+        )?;
+
+    if rows == 0 {
+        return Err(NoWorkError::TaskNotFound);
+    }
+
+    load_task(conn, task_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations;
+    use crate::tasks::{add_task, complete_task};
+    use crate::types::NewTask;
+    use chrono::NaiveDate;
+    use std::path::Path;
+
+    fn local(year: i32, month: u32, day: u32, hour: u32) -> DateTime<Utc> {
+        let naive = NaiveDate::from_ymd_opt(year, month, day).unwrap().and_hms_opt(hour, 0, 0).unwrap();
+        Local.from_local_datetime(&naive).earliest().unwrap().with_timezone(&Utc)
+    }
+
+    fn recurrence(rule: &str, mode: RecurrenceMode) -> Recurrence {
+        validate(Recurrence {
+            rule: rule.to_string(),
+            mode,
+        })
+        .unwrap()
+    }
+
+    fn database() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "foreign_keys", true).unwrap();
+        migrations::migrate(&mut conn, Path::new("unused")).unwrap();
+        conn
+    }
+
+    // the incomplete task that came out of completing task_id, if any
+    fn successor(conn: &Connection, task_id: i32) -> Option<Task> {
+        let id: Option<i32> = conn
+            .query_row(
+                "SELECT id FROM tasks WHERE id > ?1 AND parent_id IS NULL AND completed = 0",
+                params![task_id],
+                |row| row.get(0),
+            )
+            .ok();
+        id.map(|id| load_task(conn, id).unwrap())
+    }
+
+    #[test]
+    fn validate_strips_the_prefix_and_rejects_garbage() {
+        let parsed = recurrence("rrule:freq=weekly;byday=mo", RecurrenceMode::FixedSchedule);
+        assert_eq!(parsed.rule, "FREQ=WEEKLY;BYDAY=MO");
+
+        let invalid = Recurrence {
+            rule: "FREQ=SOMETIMES".to_string(),
+            mode: RecurrenceMode::FixedSchedule,
+        };
+        assert!(validate(invalid).is_err());
+    }
+
+    #[test]
+    fn fixed_schedule_follows_the_due_date() {
+        let weekly = recurrence("FREQ=WEEKLY", RecurrenceMode::FixedSchedule);
+        // completed early, the next one is still a week after the due date
+        let next = next_due_date(&weekly, Some(local(2026, 3, 2, 9)), local(2026, 2, 27, 12)).unwrap();
+        assert_eq!(next, Some(local(2026, 3, 9, 9)));
+
+        // completed late, occurrences that have already passed are skipped
+        let next = next_due_date(&weekly, Some(local(2026, 3, 2, 9)), local(2026, 3, 20, 12)).unwrap();
+        assert_eq!(next, Some(local(2026, 3, 23, 9)));
+    }
+
+    #[test]
+    fn after_completion_counts_from_the_completion_day() {
+        let every_three_days = recurrence("FREQ=DAILY;INTERVAL=3", RecurrenceMode::AfterCompletion);
+        // keeps the time of day of the due date
+        let next = next_due_date(&every_three_days, Some(local(2026, 3, 2, 9)), local(2026, 3, 4, 15)).unwrap();
+        assert_eq!(next, Some(local(2026, 3, 7, 9)));
+
+        // without a due date it counts from the completion itself
+        let completed = local(2026, 3, 4, 15);
+        let next = next_due_date(&every_three_days, None, completed).unwrap();
+        assert_eq!(next, Some(completed + Duration::days(3)));
+    }
+
+    #[test]
+    fn until_ends_the_series() {
+        let rule = format!("FREQ=WEEKLY;UNTIL={}", local(2040, 1, 10, 0).format("%Y%m%dT%H%M%SZ"));
+        let weekly = recurrence(&rule, RecurrenceMode::FixedSchedule);
+
+        let next = next_due_date(&weekly, Some(local(2040, 1, 2, 9)), local(2040, 1, 2, 10)).unwrap();
+        assert_eq!(next, Some(local(2040, 1, 9, 9)));
+        let next = next_due_date(&weekly, Some(local(2040, 1, 9, 9)), local(2040, 1, 9, 10)).unwrap();
+        assert_eq!(next, None);
+    }
+
+    #[test]
+    fn count_goes_down_with_every_occurrence() {
+        let mut conn = database();
+        let task = NewTask {
+            name: "Water plants".to_string(),
+            // far ahead so completing now does not skip occurrences
+            due_date: Some(local(2040, 1, 2, 9)),
+            priority: None,
+            tags: None,
+            recurrence: Some(recurrence("FREQ=WEEKLY;COUNT=3", RecurrenceMode::FixedSchedule)),
+        };
+        let first = add_task(&mut conn, task).unwrap();
+
+        complete_task(&mut conn, first, false).unwrap();
+        let second = successor(&conn, first).unwrap();
+        assert_eq!(second.due_date, Some(local(2040, 1, 9, 9)));
+        assert_eq!(second.recurrence.as_ref().unwrap().rule, "FREQ=WEEKLY;COUNT=2");
+        assert!(load_task(&conn, first).unwrap().recurrence.is_none());
+
+        complete_task(&mut conn, second.id, false).unwrap();
+        let third = successor(&conn, second.id).unwrap();
+        assert_eq!(third.due_date, Some(local(2040, 1, 16, 9)));
+        assert_eq!(third.recurrence.as_ref().unwrap().rule, "FREQ=WEEKLY;COUNT=1");
+
+        // the third of three is the last
+        complete_task(&mut conn, third.id, false).unwrap();
+        assert!(successor(&conn, third.id).is_none());
+        let total: i64 = conn.query_row("SELECT COUNT(*) FROM tasks", [], |row| row.get(0)).unwrap();
+        assert_eq!(total, 3);
+    }
+
+    #[test]
+    fn completing_twice_spawns_once() {
+        let mut conn = database();
+        let task = NewTask {
+            name: "Standup".to_string(),
+            due_date: Some(local(2040, 1, 2, 9)),
+            priority: None,
+            tags: None,
+            recurrence: Some(recurrence("FREQ=DAILY", RecurrenceMode::FixedSchedule)),
+        };
+        let first = add_task(&mut conn, task).unwrap();
+
+        complete_task(&mut conn, first, false).unwrap();
+        complete_task(&mut conn, first, false).unwrap();
+        let total: i64 = conn.query_row("SELECT COUNT(*) FROM tasks", [], |row| row.get(0)).unwrap();
+        assert_eq!(total, 2);
+        assert_eq!(successor(&conn, first).unwrap().due_date, Some(local(2040, 1, 3, 9)));
+    }
+}
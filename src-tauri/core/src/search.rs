@@ -0,0 +1,169 @@
+use rusqlite::{params_from_iter, types::Value, Connection};
+use std::collections::HashMap;
+
+use crate::error::NoWorkError;
+use crate::tasks::load_tasks;
+use crate::types::{SearchQuery, SearchResult, Task};
+
+const DEFAULT_SEARCH_LIMIT: u32 = 50;
+
+// snippet() brackets matches with these private use characters, they become
+// <mark> tags only after the task text around them is escaped
+const MATCH_START: char = '\u{E000}';
+const MATCH_END: char = '\u{E001}';
+
+// The snippet as HTML that is safe to render, with the matches in <mark>
+fn highlight(snippet: &str) -> String {
+    let mut html = String::with_capacity(snippet.len() + 16);
+    for c in snippet.chars() {
+        match c {
+            MATCH_START => html.push_str("<mark>"),
+            MATCH_END => html.push_str("</mark>"),
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            c => html.push(c),
+        }
+    }
+    html
+}
+
+// Turns free text into an FTS5 query where every word is a quoted prefix term,
+// so user input can never be interpreted as FTS5 syntax.
+fn to_match_expression(text: &str) -> Option<String> {
+    let terms: Vec<String> = text
+        .split_whitespace()
+        .map(|term| term.replace('"', ""))
+        .filter(|term| !term.is_empty())
+        .map(|term| format!("\"{}\"*", term))
+        .collect();
+
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+// Ranked full-text search over task names
+pub fn search_tasks(conn: &Connection, query: &SearchQuery) -> Result<Vec<SearchResult>, NoWorkError> {
+    let Some(match_expr) = to_match_expression(&query.text) else {
+        return Ok(Vec::new());
+    };
+
+    let mut sql = String::from(
+        "SELECT tasks.id,
+                snippet(tasks_fts, 0, char(57344), char(57345), '…', 16),
+                bm25(tasks_fts) AS rank
+         FROM tasks_fts
+         JOIN tasks ON tasks.id = tasks_fts.rowid
+         WHERE tasks_fts MATCH ?",
+    );
+    let mut values: Vec<Value> = vec![Value::Text(match_expr)];
+
+    if !query.tag_ids.is_empty() {
+        let placeholders = vec!["?"; query.tag_ids.len()].join(", ");
+        sql.push_str(&format!(
+            " AND EXISTS (SELECT 1 FROM task_tags WHERE task_tags.task_id = tasks.id AND task_tags.tag_id IN ({}))",
+            placeholders
+        ));
+        values.extend(query.tag_ids.iter().map(|id| Value::Integer(*id)));
+    }
+
+    if !query.priorities.is_empty() {
+        let levels: Vec<i64> = query
+            .priorities
+            .iter()
+            .flatten()
+            .map(|p| p.as_i32() as i64)
+            .collect();
+        let mut conditions = Vec::new();
+        if !levels.is_empty() {
+            conditions.push(format!("tasks.priority IN ({})", vec!["?"; levels.len()].join(", ")));
+            values.extend(levels.into_iter().map(Value::Integer));
+        }
+        if query.priorities.iter().any(|p| p.is_none()) {
+            conditions.push("tasks.priority IS NULL".to_string());
+        }
+        sql.push_str(&format!(" AND ({})", conditions.join(" OR ")));
+    }
+
+    if let Some(completed) = query.completed {
+        sql.push_str(" AND tasks.completed = ?");
+        values.push(Value::Integer(completed as i64));
+    }
+
+    sql.push_str(" ORDER BY rank LIMIT ?");
+    values.push(Value::Integer(query.limit.unwrap_or(DEFAULT_SEARCH_LIMIT) as i64));
+
+    let mut stmt = conn.prepare(&sql)?;
+
+    let rows = stmt
+        .query_map(params_from_iter(values), |row| {
+            Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?, row.get::<_, f64>(2)?))
+        })?;
+
+    let hits = rows
+        .collect::<rusqlite::Result<Vec<(i32, String, f64)>>>()?;
+
+    // load all matched tasks in one go, then put them back in rank order
+    let ids: Vec<i32> = hits.iter().map(|(id, _, _)| *id).collect();
+    let ids_json = serde_json::to_string(&ids)?;
+    let mut tasks: HashMap<i32, Task> = load_tasks(
+        conn,
+        "WHERE tasks.id IN (SELECT value FROM json_each(?1))",
+        [ids_json],
+    )?
+    .into_iter()
+    .map(|task| (task.id, task))
+    .collect();
+
+    Ok(hits
+        .into_iter()
+        .filter_map(|(task_id, snippet, rank)| {
+            tasks.remove(&task_id).map(|task| SearchResult {
+                task,
+                snippet: highlight(&snippet),
+                rank,
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highlight_escapes_the_task_text() {
+        let snippet = format!("<img src=x onerror=\"alert('{}hi{}')\"> & more", MATCH_START, MATCH_END);
+        assert_eq!(
+            highlight(&snippet),
+            "&lt;img src=x onerror=&quot;alert(&#39;<mark>hi</mark>&#39;)&quot;&gt; &amp; more"
+        );
+    }
+
+    #[test]
+    fn snippet_markers_survive_fts() {
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::migrations::migrate(&mut conn, std::path::Path::new("unused")).unwrap();
+        conn.execute("INSERT INTO tasks (name) VALUES ('<b>bold</b> plan')", []).unwrap();
+
+        let snippet: String = conn
+            .query_row(
+                "SELECT snippet(tasks_fts, 0, char(57344), char(57345), '…', 16) FROM tasks_fts WHERE tasks_fts MATCH '\"plan\"*'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(highlight(&snippet), "&lt;b&gt;bold&lt;/b&gt; <mark>plan</mark>");
+    }
+
+    #[test]
+    fn match_expression_quotes_every_word() {
+        assert_eq!(to_match_expression("buy \"milk OR"), Some("\"buy\"* \"milk\"* \"OR\"*".to_string()));
+        assert_eq!(to_match_expression("  \" "), None);
+    }
+}
@@ -0,0 +1,77 @@
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::error::NoWorkError;
+use crate::tasks::{load_subtasks, load_task};
+use crate::types::Task;
+
+// Add a subtask to the end of a task's checklist
+pub fn add_subtask(conn: &mut Connection, parent_id: i32, name: &str) -> Result<Task, NoWorkError> {
+    let tx = conn.transaction()?;
+
+    let grandparent: Option<Option<i32>> = tx
+        .query_row(
+            "SELECT parent_id FROM tasks WHERE id = ?1",
+            params![parent_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+
+    match grandparent {
+        None => return Err(NoWorkError::TaskNotFound),
+        Some(Some(_)) => return Err(NoWorkError::validation("Subtasks cannot have subtasks of their own")),
+        Some(None) => {}
+    }
+
+    tx.execute(
+        "INSERT INTO tasks (name, parent_id, position)
+         VALUES (?1, ?2, (SELECT COALESCE(MAX(position) + 1, 0) FROM tasks WHERE parent_id = ?2))",
+        params![name, parent_id],
+    )?;
+
+    let subtask_id = tx.last_insert_rowid() as i32;
+    let subtask = load_task(&tx, subtask_id)?;
+
+    tx.commit()?;
+    Ok(subtask)
+}
+
+// Reorder a checklist, subtask_ids must list every subtask of the parent exactly once
+pub fn reorder_subtasks(conn: &mut Connection, parent_id: i32, subtask_ids: &[i32]) -> Result<Vec<Task>, NoWorkError> {
+    let tx = conn.transaction()?;
+
+    let mut current: Vec<i32> = load_subtasks(&tx, parent_id)?.iter().map(|t| t.id).collect();
+    let mut requested = subtask_ids.to_vec();
+    current.sort_unstable();
+    requested.sort_unstable();
+    if current != requested {
+        return Err(NoWorkError::validation("Subtask ids do not match the subtasks of this task"));
+    }
+
+    for (position, subtask_id) in subtask_ids.iter().enumerate() {
+        tx.execute(
+            "UPDATE tasks SET position = ?1 WHERE id = ?2",
+            params![position as i32, subtask_id],
+        )?;
+    }
+
+    let subtasks = load_subtasks(&tx, parent_id)?;
+    tx.commit()?;
+    Ok(subtasks)
+}
+
+// Tick or untick a checklist item
+pub fn set_subtask_completed(conn: &Connection, subtask_id: i32, completed: bool) -> Result<Task, NoWorkError> {
+    let rows = conn.execute(
+        "UPDATE tasks
+         SET completed = ?1,
+             completed_at = CASE WHEN ?1 THEN strftime('%Y-%m-%dT%H:%M:%fZ', 'now') ELSE NULL END
+         WHERE id = ?2 AND parent_id IS NOT NULL",
+        params![completed, subtask_id],
+    )?;
+
+    if rows == 0 {
+        return Err(NoWorkError::SubtaskNotFound);
+    }
+
+    load_task(conn, subtask_id)
+}
@@ -0,0 +1,709 @@
+use rusqlite::{
+    params,
+    types::{Type, Value as SqlValue},
+    Connection, OptionalExtension, Transaction,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::time::Duration;
+
+use crate::database::Database;
+use crate::error::NoWorkError;
+
+// Field by field sync of tasks and tags between devices through a server.
+// This module is the server half and the merge both halves share. The app
+// does not sync this way yet, with a server set up it keeps its tasks there
+// directly, so pushing and pulling from a device is left to other clients.
+
+// changes sent back per sync request, the device asks again while more is set
+const PAGE_SIZE: i64 = 1000;
+
+// a device that has not synced for this long no longer holds back pruning
+const STALE_DEVICE_DAYS: i64 = 90;
+
+// how often the server prunes its change log
+const PRUNE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+// task fields that map onto a column of the same name, see the sync change
+// log migration. "parent", "deleted" and "tag:<name>" are handled apart.
+const TASK_COLUMNS: &[&str] = &[
+    "name",
+    "due_date",
+    "created_at",
+    "completed",
+    "completed_at",
+    "priority",
+    "recurrence_rule",
+    "recurrence_mode",
+    "position",
+];
+
+// A new value for one field of a task (by uid) or tag (by name). seq numbers
+// the changes of the device that made it, and together with device_id is
+// what tells changes apart.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Change {
+    pub entity: String,
+    pub uid: String,
+    pub field: String,
+    pub value: Value,
+    pub updated_at: String,
+    pub device_id: String,
+    pub seq: i64,
+}
+
+// Sent by a device to the server: the changes it made since its last sync,
+// and the server cursor it has received everything up to
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncRequest {
+    pub device_id: String,
+    #[serde(default)]
+    pub since: i64,
+    #[serde(default)]
+    pub changes: Vec<Change>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncResponse {
+    pub changes: Vec<Change>,
+    // the since of the next request
+    pub cursor: i64,
+    pub more: bool,
+}
+
+pub fn device_id(conn: &Connection) -> Result<String, NoWorkError> {
+    Ok(conn.query_row("SELECT device_id FROM sync_device WHERE id = 1", [], |row| row.get(0))?)
+}
+
+// columns read by read_change, in order, the first being the local seq
+const CHANGE_COLUMNS: &str = "seq, entity, uid, field, value, updated_at, device_id, COALESCE(origin_seq, seq)";
+
+fn read_change(row: &rusqlite::Row) -> rusqlite::Result<(i64, Change)> {
+    let value: String = row.get(4)?;
+    let value = serde_json::from_str(&value)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(4, Type::Text, Box::new(e)))?;
+    Ok((
+        row.get(0)?,
+        Change {
+            entity: row.get(1)?,
+            uid: row.get(2)?,
+            field: row.get(3)?,
+            value,
+            updated_at: row.get(5)?,
+            device_id: row.get(6)?,
+            seq: row.get(7)?,
+        },
+    ))
+}
+
+fn query_changes<P: rusqlite::Params>(conn: &Connection, sql: &str, params: P) -> Result<Vec<(i64, Change)>, NoWorkError> {
+    let mut stmt = conn.prepare_cached(sql)?;
+    let rows = stmt
+        .query_map(params, read_change)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(rows)
+}
+
+// Changes made on this device after seq after, what it pushes to the server
+pub fn local_changes(conn: &Connection, after: i64) -> Result<Vec<Change>, NoWorkError> {
+    let rows = query_changes(
+        conn,
+        &format!(
+            "SELECT {} FROM change_log WHERE seq > ?1 AND origin_seq IS NULL ORDER BY seq",
+            CHANGE_COLUMNS
+        ),
+        params![after],
+    )?;
+    Ok(rows.into_iter().map(|(_, change)| change).collect())
+}
+
+// The next page of changes after the cursor since, leaving out those made
+// by the device asking
+pub fn changes_since(conn: &Connection, since: i64, device_id: &str) -> Result<SyncResponse, NoWorkError> {
+    let mut rows = query_changes(
+        conn,
+        &format!(
+            "SELECT {} FROM change_log WHERE seq > ?1 AND device_id != ?2 ORDER BY seq LIMIT ?3",
+            CHANGE_COLUMNS
+        ),
+        params![since, device_id, PAGE_SIZE + 1],
+    )?;
+
+    let more = rows.len() as i64 > PAGE_SIZE;
+    rows.truncate(PAGE_SIZE as usize);
+    // past the asking device's own changes too once everything was sent
+    let cursor = match rows.last() {
+        Some((seq, _)) if more => *seq,
+        _ => conn.query_row("SELECT COALESCE(MAX(seq), ?1) FROM change_log", params![since], |row| row.get(0))?,
+    };
+
+    Ok(SyncResponse {
+        changes: rows.into_iter().map(|(_, change)| change).collect(),
+        cursor,
+        more,
+    })
+}
+
+// Handles a sync request on the server: stores what the device sent, then
+// answers with what it has not seen yet
+pub fn serve(conn: &mut Connection, request: SyncRequest) -> Result<SyncResponse, NoWorkError> {
+    if request.device_id.is_empty() {
+        return Err(NoWorkError::validation("The sync request has no device id"));
+    }
+    if request.device_id == device_id(conn)? {
+        return Err(NoWorkError::validation(
+            "This device has the same sync id as the server, its database was probably copied from it",
+        ));
+    }
+
+    apply_changes(conn, &request.changes)?;
+    // since is how far the device has received, it never goes back
+    conn.execute(
+        "INSERT INTO sync_devices (device_id, acked_seq, last_sync_at)
+         VALUES (?1, ?2, strftime('%Y-%m-%dT%H:%M:%fZ', 'now'))
+         ON CONFLICT(device_id) DO UPDATE SET
+            acked_seq = MAX(acked_seq, excluded.acked_seq),
+            last_sync_at = excluded.last_sync_at",
+        params![request.device_id, request.since],
+    )?;
+    changes_since(conn, request.since, &request.device_id)
+}
+
+// Drops change log entries every device has received and no device will
+// need again: values a later change of the same field has replaced, and the
+// fields of deleted tasks. The latest value of each field and the tombstones
+// of deleted tasks stay, so a device syncing for the first time still ends up
+// with the same tasks. Returns how many entries went.
+pub fn prune(conn: &mut Connection) -> Result<usize, NoWorkError> {
+    let tx = conn.transaction()?;
+    // with no device syncing every entry counts as received
+    let horizon: Option<i64> = tx.query_row(
+        "SELECT COALESCE(
+            (SELECT MIN(acked_seq) FROM sync_devices WHERE last_sync_at > strftime('%Y-%m-%dT%H:%M:%fZ', 'now', ?1)),
+            (SELECT MAX(seq) FROM change_log))",
+        params![format!("-{} days", STALE_DEVICE_DAYS)],
+        |row| row.get(0),
+    )?;
+    let Some(horizon) = horizon else {
+        return Ok(0);
+    };
+
+    let replaced = tx.execute(
+        "DELETE FROM change_log WHERE seq <= ?1 AND seq NOT IN (
+            SELECT seq FROM (
+                SELECT seq, ROW_NUMBER() OVER (
+                    PARTITION BY entity, uid, field ORDER BY updated_at DESC, device_id DESC, COALESCE(origin_seq, seq) DESC
+                ) AS rank
+                FROM change_log
+            ) WHERE rank = 1
+         )",
+        params![horizon],
+    )?;
+    let deleted = tx.execute(
+        "DELETE FROM change_log WHERE seq <= ?1 AND entity = 'task' AND field != 'deleted' AND uid IN (
+            SELECT uid FROM change_log WHERE entity = 'task' AND field = 'deleted' AND value = 'true' AND seq <= ?1
+         )",
+        params![horizon],
+    )?;
+    tx.commit()?;
+    Ok(replaced + deleted)
+}
+
+// Prunes the change log once a day for as long as the server runs
+pub async fn run_daily_pruning(db: Database) {
+    loop {
+        match db.run(prune).await {
+            Ok(0) => {}
+            Ok(pruned) => log::info!("Pruned {} change log entries", pruned),
+            Err(e) => log::error!("Pruning the change log failed: {}", e),
+        }
+        tokio::time::sleep(PRUNE_INTERVAL).await;
+    }
+}
+
+// Stores changes received from another device and writes every field they
+// touch back to its winning value: the change with the latest updated_at,
+// ties going to the greater device_id, then seq. A deleted task stays
+// deleted, a tag can be created again. Changes already stored are skipped,
+// so sending the same ones twice is harmless. Returns how many were new.
+pub fn apply_changes(conn: &mut Connection, changes: &[Change]) -> Result<usize, NoWorkError> {
+    let own_device = device_id(conn)?;
+    let tx = conn.transaction()?;
+    // keeps the triggers from logging the writes below as changes of this device
+    tx.execute("UPDATE sync_device SET applying = 1", [])?;
+
+    let mut touched = BTreeSet::new();
+    let mut new_changes = 0;
+    for change in changes.iter().filter(|c| c.device_id != own_device) {
+        if !matches!(change.entity.as_str(), "task" | "tag") {
+            return Err(NoWorkError::validation(format!("Unknown kind of change: {}", change.entity)));
+        }
+        if change.uid.is_empty() || change.device_id.is_empty() || change.updated_at.is_empty() {
+            return Err(NoWorkError::validation("A change is missing its uid, device or time"));
+        }
+
+        let stored = tx.execute(
+            "INSERT OR IGNORE INTO change_log (entity, uid, field, value, updated_at, device_id, origin_seq)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                change.entity,
+                change.uid,
+                change.field,
+                change.value.to_string(),
+                change.updated_at,
+                change.device_id,
+                change.seq
+            ],
+        )?;
+        if stored > 0 {
+            new_changes += 1;
+            touched.insert((change.entity.clone(), change.uid.clone()));
+        }
+    }
+
+    // tags first so task tags can find them, and every task row before any
+    // field so subtasks can find a parent that arrived in the same batch
+    let (tags, tasks): (Vec<_>, Vec<_>) = touched.iter().partition(|(entity, _)| entity == "tag");
+    for (_, name) in &tags {
+        write_tag(&tx, name)?;
+    }
+    let mut task_fields = Vec::new();
+    for (_, uid) in &tasks {
+        let fields = winners(&tx, "task", uid)?;
+        if !is_deleted(&fields) && task_id(&tx, uid)?.is_none() {
+            tx.execute("INSERT INTO tasks (uid, name) VALUES (?1, '')", params![uid])?;
+        }
+        task_fields.push((uid, fields));
+    }
+    for (uid, fields) in task_fields {
+        write_task(&tx, uid, fields)?;
+    }
+
+    tx.execute("UPDATE sync_device SET applying = 0", [])?;
+    tx.commit()?;
+    Ok(new_changes)
+}
+
+// field -> (value, updated_at) of the change that wins it
+fn winners(tx: &Transaction, entity: &str, uid: &str) -> Result<HashMap<String, (Value, String)>, NoWorkError> {
+    let mut stmt = tx.prepare_cached(
+        "SELECT field, value, updated_at FROM (
+            SELECT field, value, updated_at, ROW_NUMBER() OVER (
+                PARTITION BY field ORDER BY updated_at DESC, device_id DESC, COALESCE(origin_seq, seq) DESC
+            ) AS rank
+            FROM change_log WHERE entity = ?1 AND uid = ?2
+         ) WHERE rank = 1",
+    )?;
+    let rows = stmt
+        .query_map(params![entity, uid], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    rows.into_iter()
+        .map(|(field, value, updated_at)| Ok((field, (serde_json::from_str(&value)?, updated_at))))
+        .collect()
+}
+
+fn is_deleted(fields: &HashMap<String, (Value, String)>) -> bool {
+    matches!(fields.get("deleted"), Some((Value::Bool(true), _)))
+}
+
+fn last_update(fields: &HashMap<String, (Value, String)>) -> Option<&str> {
+    fields.values().map(|(_, updated_at)| updated_at.as_str()).max()
+}
+
+fn task_id(tx: &Transaction, uid: &str) -> Result<Option<i64>, NoWorkError> {
+    Ok(tx
+        .query_row("SELECT id FROM tasks WHERE uid = ?1", params![uid], |row| row.get(0))
+        .optional()?)
+}
+
+// JSON values as stored in tasks and tags, booleans being 0 or 1
+fn sql_value(value: &Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(*b as i64),
+        Value::Number(n) => match n.as_i64() {
+            Some(i) => SqlValue::Integer(i),
+            None => SqlValue::Real(n.as_f64().unwrap_or_default()),
+        },
+        Value::String(s) => SqlValue::Text(s.clone()),
+        other => SqlValue::Text(other.to_string()),
+    }
+}
+
+fn write_tag(tx: &Transaction, name: &str) -> Result<(), NoWorkError> {
+    let fields = winners(tx, "tag", name)?;
+    if is_deleted(&fields) {
+        tx.execute("DELETE FROM tags WHERE name = ?1", params![name])?;
+        return Ok(());
+    }
+
+    let color = match fields.get("color") {
+        Some((value, _)) => sql_value(value),
+        None => SqlValue::Text("default".to_string()),
+    };
+    tx.execute(
+        "INSERT INTO tags (name, color, updated_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT(name) DO UPDATE SET color = excluded.color, updated_at = excluded.updated_at",
+        params![name, color, last_update(&fields)],
+    )?;
+    Ok(())
+}
+
+fn write_task(tx: &Transaction, uid: &str, fields: HashMap<String, (Value, String)>) -> Result<(), NoWorkError> {
+    if is_deleted(&fields) {
+        tx.execute("DELETE FROM tasks WHERE uid = ?1", params![uid])?;
+        return Ok(());
+    }
+    let Some(id) = task_id(tx, uid)? else {
+        return Ok(());
+    };
+
+    for (field, (value, _)) in &fields {
+        if TASK_COLUMNS.contains(&field.as_str()) {
+            tx.execute(
+                &format!("UPDATE tasks SET {} = ?1 WHERE id = ?2", field),
+                params![sql_value(value), id],
+            )?;
+        } else if field == "parent" {
+            let parent = match value.as_str() {
+                Some(parent_uid) => match task_id(tx, parent_uid)? {
+                    Some(parent_id) => Some(parent_id),
+                    // subtasks go with their parent
+                    None => {
+                        tx.execute("DELETE FROM tasks WHERE id = ?1", params![id])?;
+                        return Ok(());
+                    }
+                },
+                None => None,
+            };
+            tx.execute("UPDATE tasks SET parent_id = ?1 WHERE id = ?2", params![parent, id])?;
+        } else if let Some(tag_name) = field.strip_prefix("tag:") {
+            if value == &Value::Bool(true) {
+                tx.execute(
+                    "INSERT OR IGNORE INTO task_tags (task_id, tag_id) SELECT ?1, id FROM tags WHERE name = ?2",
+                    params![id, tag_name],
+                )?;
+            } else {
+                tx.execute(
+                    "DELETE FROM task_tags WHERE task_id = ?1 AND tag_id = (SELECT id FROM tags WHERE name = ?2)",
+                    params![id, tag_name],
+                )?;
+            }
+        }
+    }
+
+    tx.execute(
+        "UPDATE tasks SET updated_at = ?1 WHERE id = ?2",
+        params![last_update(&fields), id],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{NewTag, NewTask, TaskChanges, TaskPriority};
+    use crate::{migrations, tags, tasks};
+    use std::path::Path;
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    // a device's database and how far it has synced with the server
+    struct Replica {
+        conn: Connection,
+        pushed: i64,
+        pulled: i64,
+    }
+
+    fn database() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "foreign_keys", true).unwrap();
+        migrations::migrate(&mut conn, Path::new("unused")).unwrap();
+        conn
+    }
+
+    fn replica() -> Replica {
+        Replica {
+            conn: database(),
+            pushed: 0,
+            pulled: 0,
+        }
+    }
+
+    fn sync(server: &mut Connection, device: &mut Replica) {
+        loop {
+            let last_seq: i64 = device
+                .conn
+                .query_row("SELECT COALESCE(MAX(seq), 0) FROM change_log", [], |row| row.get(0))
+                .unwrap();
+            let request = SyncRequest {
+                device_id: device_id(&device.conn).unwrap(),
+                since: device.pulled,
+                changes: local_changes(&device.conn, device.pushed).unwrap(),
+            };
+            let response = serve(server, request).unwrap();
+            apply_changes(&mut device.conn, &response.changes).unwrap();
+            device.pushed = last_seq;
+            device.pulled = response.cursor;
+            if !response.more {
+                break;
+            }
+        }
+    }
+
+    // every device syncs twice so each has seen what the others sent
+    fn sync_all(server: &mut Connection, devices: &mut [&mut Replica]) {
+        for _ in 0..2 {
+            for device in devices.iter_mut() {
+                sync(server, device);
+            }
+        }
+    }
+
+    fn new_task(conn: &mut Connection, name: &str, tags: Vec<NewTag>) -> i32 {
+        tasks::add_task(
+            conn,
+            NewTask {
+                name: name.to_string(),
+                due_date: None,
+                priority: None,
+                tags: Some(tags),
+                recurrence: None,
+            },
+        )
+        .unwrap()
+    }
+
+    fn tag(name: &str, color: &str) -> NewTag {
+        NewTag {
+            name: name.to_string(),
+            color: color.to_string(),
+        }
+    }
+
+    fn id_of(conn: &Connection, name: &str) -> i32 {
+        conn.query_row("SELECT id FROM tasks WHERE name = ?1", params![name], |row| row.get(0))
+            .unwrap()
+    }
+
+    fn rename(conn: &mut Connection, task_id: i32, name: &str) {
+        let changes = TaskChanges {
+            name: Some(name.to_string()),
+            ..Default::default()
+        };
+        tasks::update_task(conn, task_id, changes).unwrap();
+    }
+
+    // uid, name, priority, completed, parent uid and tags of a task
+    type TaskState = (String, String, Option<i32>, bool, Option<String>, String);
+
+    fn snapshot(conn: &Connection) -> Vec<TaskState> {
+        let mut stmt = conn
+            .prepare(
+                "SELECT tasks.uid, tasks.name, tasks.priority, tasks.completed, parent.uid,
+                        COALESCE((SELECT group_concat(name, ',') FROM (
+                            SELECT tags.name FROM task_tags JOIN tags ON tags.id = task_tags.tag_id
+                            WHERE task_tags.task_id = tasks.id ORDER BY tags.name)), '')
+                 FROM tasks LEFT JOIN tasks AS parent ON parent.id = tasks.parent_id
+                 ORDER BY tasks.uid",
+            )
+            .unwrap();
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn edits_to_different_fields_are_merged() {
+        let mut server = database();
+        let (mut a, mut b) = (replica(), replica());
+        new_task(&mut a.conn, "Water plants", vec![]);
+        sync_all(&mut server, &mut [&mut a, &mut b]);
+
+        let (on_a, on_b) = (id_of(&a.conn, "Water plants"), id_of(&b.conn, "Water plants"));
+        rename(&mut a.conn, on_a, "Water the plants");
+        let changes = TaskChanges {
+            priority: Some(Some(TaskPriority::High)),
+            ..Default::default()
+        };
+        tasks::update_task(&mut b.conn, on_b, changes).unwrap();
+        sync_all(&mut server, &mut [&mut a, &mut b]);
+
+        let merged = snapshot(&a.conn);
+        assert_eq!(merged.len(), 1);
+        assert_eq!(merged[0].1, "Water the plants");
+        assert_eq!(merged[0].2, Some(2));
+        assert_eq!(merged, snapshot(&b.conn));
+        assert_eq!(merged, snapshot(&server));
+    }
+
+    #[test]
+    fn later_edit_of_the_same_field_wins() {
+        let mut server = database();
+        let (mut a, mut b) = (replica(), replica());
+        new_task(&mut a.conn, "Call mom", vec![]);
+        sync_all(&mut server, &mut [&mut a, &mut b]);
+
+        let (on_a, on_b) = (id_of(&a.conn, "Call mom"), id_of(&b.conn, "Call mom"));
+        rename(&mut a.conn, on_a, "Call mom today");
+        sleep(Duration::from_millis(10));
+        rename(&mut b.conn, on_b, "Call mom tomorrow");
+        // the later edit wins whichever device syncs first
+        sync_all(&mut server, &mut [&mut b, &mut a]);
+
+        assert_eq!(snapshot(&a.conn)[0].1, "Call mom tomorrow");
+        assert_eq!(snapshot(&a.conn), snapshot(&b.conn));
+    }
+
+    #[test]
+    fn simultaneous_edits_are_resolved_the_same_everywhere() {
+        let mut x = database();
+        let mut y = database();
+        let change = |device: &str, name: &str| Change {
+            entity: "task".to_string(),
+            uid: "t1".to_string(),
+            field: "name".to_string(),
+            value: Value::String(name.to_string()),
+            updated_at: "2026-05-01T10:00:00.000Z".to_string(),
+            device_id: device.to_string(),
+            seq: 1,
+        };
+        let first = change("device-a", "From A");
+        let second = change("device-b", "From B");
+
+        apply_changes(&mut x, &[first.clone(), second.clone()]).unwrap();
+        apply_changes(&mut y, &[second]).unwrap();
+        apply_changes(&mut y, &[first]).unwrap();
+
+        // same time, the greater device id wins
+        assert_eq!(snapshot(&x)[0].1, "From B");
+        assert_eq!(snapshot(&x), snapshot(&y));
+    }
+
+    #[test]
+    fn deleted_task_stays_deleted() {
+        let mut server = database();
+        let (mut a, mut b) = (replica(), replica());
+        new_task(&mut a.conn, "Old chore", vec![]);
+        sync_all(&mut server, &mut [&mut a, &mut b]);
+
+        let on_b = id_of(&b.conn, "Old chore");
+        tasks::delete_task(&a.conn, id_of(&a.conn, "Old chore")).unwrap();
+        sleep(Duration::from_millis(10));
+        rename(&mut b.conn, on_b, "Old chore, edited after the delete");
+        sync_all(&mut server, &mut [&mut a, &mut b]);
+
+        assert!(snapshot(&a.conn).is_empty());
+        assert!(snapshot(&b.conn).is_empty());
+        assert!(snapshot(&server).is_empty());
+        let tombstones: i64 = server
+            .query_row("SELECT COUNT(*) FROM change_log WHERE field = 'deleted' AND value = 'true'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(tombstones, 1);
+    }
+
+    #[test]
+    fn subtasks_and_tags_follow_their_task() {
+        let mut server = database();
+        let (mut a, mut b) = (replica(), replica());
+        let parent = new_task(&mut a.conn, "Move house", vec![tag("home", "blue")]);
+        a.conn
+            .execute("INSERT INTO tasks (name, parent_id, position) VALUES ('Pack books', ?1, 0)", params![parent])
+            .unwrap();
+        sync_all(&mut server, &mut [&mut a, &mut b]);
+
+        let synced = snapshot(&b.conn);
+        assert_eq!(synced, snapshot(&a.conn));
+        let move_house = synced.iter().find(|t| t.1 == "Move house").unwrap();
+        let pack_books = synced.iter().find(|t| t.1 == "Pack books").unwrap();
+        assert_eq!(move_house.5, "home");
+        assert_eq!(pack_books.4.as_deref(), Some(move_house.0.as_str()));
+
+        // a deleted tag comes off its tasks, and starts out on none if created again
+        tags::remove_tag(&a.conn, "home").unwrap();
+        sync_all(&mut server, &mut [&mut a, &mut b]);
+        tags::add_tag(&b.conn, tag("home", "green")).unwrap();
+        sync_all(&mut server, &mut [&mut a, &mut b]);
+
+        assert_eq!(snapshot(&a.conn), snapshot(&b.conn));
+        assert!(snapshot(&a.conn).iter().all(|t| t.5.is_empty()));
+        assert_eq!(tags::list_tags(&a.conn).unwrap()[0].color, "green");
+
+        // deleting the parent takes the subtask along everywhere
+        tasks::delete_task(&b.conn, id_of(&b.conn, "Move house")).unwrap();
+        sync_all(&mut server, &mut [&mut a, &mut b]);
+        assert!(snapshot(&a.conn).is_empty());
+    }
+
+    #[test]
+    fn changes_sent_twice_are_stored_once() {
+        let mut a = database();
+        let mut b = database();
+        new_task(&mut a, "Buy milk", vec![]);
+        let changes = local_changes(&a, 0).unwrap();
+
+        assert_eq!(apply_changes(&mut b, &changes).unwrap(), changes.len());
+        assert_eq!(apply_changes(&mut b, &changes).unwrap(), 0);
+        assert_eq!(snapshot(&a), snapshot(&b));
+        // what b received is not sent on as its own
+        assert!(local_changes(&b, 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn pruning_waits_for_every_device_and_keeps_the_tasks() {
+        let mut server = database();
+        let (mut a, mut b, mut late) = (replica(), replica(), replica());
+        new_task(&mut a.conn, "Water plants", vec![tag("home", "blue")]);
+        new_task(&mut a.conn, "Old errand", vec![]);
+        sync_all(&mut server, &mut [&mut a, &mut b, &mut late]);
+        prune(&mut server).unwrap();
+
+        let task_id = id_of(&a.conn, "Water plants");
+        rename(&mut a.conn, task_id, "Water the plants");
+        rename(&mut a.conn, task_id, "Water the garden");
+        tasks::delete_task(&a.conn, id_of(&a.conn, "Old errand")).unwrap();
+        sync_all(&mut server, &mut [&mut a, &mut b]);
+
+        // late has not received the renames yet, so the replaced one stays
+        let renamed = |conn: &Connection| -> bool {
+            conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM change_log WHERE value = '\"Water the plants\"')",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+        prune(&mut server).unwrap();
+        assert!(renamed(&server));
+
+        sync_all(&mut server, &mut [&mut a, &mut b, &mut late]);
+        let entries = |conn: &Connection| -> i64 {
+            conn.query_row("SELECT COUNT(*) FROM change_log", [], |row| row.get(0)).unwrap()
+        };
+        let before = entries(&server);
+        let pruned = prune(&mut server).unwrap();
+        assert!(pruned > 0);
+        assert_eq!(entries(&server), before - pruned as i64);
+        assert!(!renamed(&server));
+        // the tombstone stays
+        let tombstones: i64 = server
+            .query_row("SELECT COUNT(*) FROM change_log WHERE field = 'deleted' AND value = 'true'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(tombstones, 1);
+
+        let mut fresh = replica();
+        sync(&mut server, &mut fresh);
+        assert_eq!(snapshot(&fresh.conn), snapshot(&a.conn));
+        assert_eq!(snapshot(&late.conn), snapshot(&a.conn));
+        assert_eq!(snapshot(&fresh.conn)[0].1, "Water the garden");
+        assert_eq!(tags::list_tags(&fresh.conn).unwrap()[0].color, "blue");
+    }
+}
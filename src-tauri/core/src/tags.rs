@@ -0,0 +1,93 @@
+use crate::error::NoWorkError;
+use crate::types::{NewTag, Tag};
+use rusqlite::{params, Connection, OptionalExtension};
+
+pub fn add_tag(conn: &Connection, new_tag: NewTag) -> Result<Tag, NoWorkError> {
+    conn.execute(
+        "INSERT INTO tags (name, color) VALUES (?1, ?2)",
+        (&new_tag.name, &new_tag.color),
+    )?;
+
+    let new_id = conn.last_insert_rowid();
+
+    Ok(Tag {
+        id: new_id,
+        name: new_tag.name,
+        color: new_tag.color,
+    })
+}
+
+// Every tag by name
+pub fn list_tags(conn: &Connection) -> Result<Vec<Tag>, NoWorkError> {
+    let mut stmt = conn
+        .prepare_cached("SELECT id, name, color FROM tags ORDER BY name")?;
+
+    let iter = stmt
+        .query_map([], |row| {
+            Ok(Tag {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                color: row.get(2)?,
+            })
+        })?;
+
+    let mut tags = Vec::new();
+    for tag in iter {
+        tags.push(tag?);
+    }
+
+    Ok(tags)
+}
+
+// Remove tag, its task links go with it through ON DELETE CASCADE
+pub fn remove_tag(conn: &Connection, tag_name: &str) -> Result<(), NoWorkError> {
+    let rows = conn
+        .execute("DELETE FROM tags WHERE name = ?1", params![tag_name])?;
+
+    if rows == 0 {
+        return Err(NoWorkError::TagNotFound);
+    }
+
+    Ok(())
+}
+
+pub fn add_tag_to_task(conn: &Connection, task_id: i32, tag_id: i32) -> Result<(), NoWorkError> {
+    conn.execute(
+        "INSERT OR IGNORE INTO task_tags (task_id, tag_id) VALUES (?, ?)",
+        params![task_id, tag_id],
+    )?;
+    Ok(())
+}
+
+pub fn remove_tag_from_task(conn: &Connection, task_id: i32, tag_id: i32) -> Result<(), NoWorkError> {
+    conn.execute(
+        "DELETE FROM task_tags WHERE task_id = ? AND tag_id = ?",
+        params![task_id, tag_id],
+    )?;
+    Ok(())
+}
+
+pub fn update_tag_color(conn: &Connection, tag_id: i32, color: &str) -> Result<(), NoWorkError> {
+    conn.execute(
+        "UPDATE tags SET color = ?1 WHERE id = ?2",
+        params![color, tag_id],
+    )?;
+    Ok(())
+}
+
+// Same as update_tag_color for callers that know tags by name
+pub fn update_tag_color_by_name(conn: &Connection, tag_name: &str, color: &str) -> Result<Tag, NoWorkError> {
+    conn.query_row(
+        "UPDATE tags SET color = ?1 WHERE name = ?2 RETURNING id, name, color",
+        params![color, tag_name],
+        |row| {
+            Ok(Tag {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                color: row.get(2)?,
+            })
+        },
+    )
+    .optional()?
+    .ok_or(NoWorkError::TagNotFound)
+}
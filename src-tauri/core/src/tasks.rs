@@ -0,0 +1,568 @@
+use crate::error::NoWorkError;
+use crate::query::local_day_range;
+use crate::recurrence;
+use crate::types::{NewTask, Recurrence, RecurrenceMode, SubtaskProgress, Tag, Task, TaskChanges, TaskPriority, TaskQuery};
+use chrono::{DateTime, Datelike, Duration, Local, Utc};
+use rusqlite::{Connection, OptionalExtension, Params, params};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+struct TaskRow {
+    id: i32,
+    name: String,
+    due_date: Option<String>,
+    created_at: String,
+    completed: i32,
+    completed_at: Option<String>,
+    priority: Option<i32>,
+    recurrence_rule: Option<String>,
+    recurrence_mode: Option<i32>,
+    parent_id: Option<i32>,
+    position: i32,
+}
+
+// columns read by read_task_row, in order
+const TASK_COLUMNS: &str = "tasks.id, tasks.name, tasks.due_date, tasks.created_at, tasks.completed, \
+     tasks.completed_at, tasks.priority, tasks.recurrence_rule, tasks.recurrence_mode, tasks.parent_id, tasks.position";
+
+// helper to read a TaskRow from a SELECT of TASK_COLUMNS
+fn read_task_row(row: &rusqlite::Row) -> rusqlite::Result<TaskRow> {
+    Ok(TaskRow {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        due_date: row.get(2)?,
+        created_at: row.get(3)?,
+        completed: row.get(4)?,
+        completed_at: row.get(5)?,
+        priority: row.get(6)?,
+        recurrence_rule: row.get(7)?,
+        recurrence_mode: row.get(8)?,
+        parent_id: row.get(9)?,
+        position: row.get(10)?,
+    })
+}
+
+// Runs "SELECT TASK_COLUMNS FROM tasks" followed by `tail` (WHERE, ORDER BY, ...).
+// Tags and subtask progress are fetched for the whole list at once, so loading
+// any number of tasks costs three queries.
+pub fn load_tasks<P: Params>(conn: &Connection, tail: &str, params: P) -> Result<Vec<Task>, NoWorkError> {
+    let mut stmt = conn
+        .prepare_cached(&format!("SELECT {} FROM tasks {}", TASK_COLUMNS, tail))?;
+
+    let rows = stmt
+        .query_map(params, read_task_row)?
+        .collect::<rusqlite::Result<Vec<TaskRow>>>()?;
+
+    if rows.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let ids: Vec<i32> = rows.iter().map(|row| row.id).collect();
+    let ids_json = serde_json::to_string(&ids)?;
+    let mut tags = fetch_tags(conn, &ids_json)?;
+    let mut progress = fetch_subtask_progress(conn, &ids_json)?;
+
+    rows.into_iter()
+        .map(|row| {
+            let id = row.id;
+            task_from_row(row, tags.remove(&id), progress.remove(&id))
+        })
+        .collect()
+}
+
+// helper to construct a task given a sqlite row and its related data
+fn task_from_row(
+    row: TaskRow,
+    tags: Option<Vec<Tag>>,
+    subtask_progress: Option<SubtaskProgress>,
+) -> Result<Task, NoWorkError> {
+    let priority_enum = row.priority.and_then(TaskPriority::from_i32);
+    let recurrence = match (row.recurrence_rule, row.recurrence_mode.and_then(RecurrenceMode::from_i32)) {
+        (Some(rule), Some(mode)) => Some(Recurrence { rule, mode }),
+        _ => None,
+    };
+
+    Ok(Task {
+        id: row.id,
+        name: row.name,
+        due_date: parse_opt_date(row.due_date)?,
+        created_at: parse_opt_date(Some(row.created_at))?.unwrap(),
+        completed: row.completed != 0,
+        completed_at: parse_opt_date(row.completed_at)?,
+        priority: priority_enum,
+        tags,
+        recurrence,
+        parent_id: row.parent_id,
+        position: row.position,
+        subtask_progress,
+    })
+}
+
+// Helper to count completed vs total subtasks, keyed by parent id.
+// ids_json is a JSON array of task ids.
+fn fetch_subtask_progress(conn: &Connection, ids_json: &str) -> Result<HashMap<i32, SubtaskProgress>, NoWorkError> {
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT parent_id, SUM(completed != 0), COUNT(*) FROM tasks
+             WHERE parent_id IN (SELECT value FROM json_each(?1))
+             GROUP BY parent_id",
+        )?;
+
+    let rows = stmt
+        .query_map([ids_json], |row| {
+            Ok((
+                row.get::<_, i32>(0)?,
+                SubtaskProgress {
+                    completed: row.get(1)?,
+                    total: row.get(2)?,
+                },
+            ))
+        })?;
+
+    rows.collect::<rusqlite::Result<HashMap<_, _>>>()
+        .map_err(NoWorkError::from)
+}
+
+// Helper to fetch tags, keyed by task id. ids_json is a JSON array of task ids.
+fn fetch_tags(conn: &Connection, ids_json: &str) -> Result<HashMap<i32, Vec<Tag>>, NoWorkError> {
+    let mut stmt = conn
+        .prepare_cached(
+            "SELECT task_tags.task_id, tags.id, tags.name, tags.color FROM tags
+             JOIN task_tags ON tags.id = task_tags.tag_id
+             WHERE task_tags.task_id IN (SELECT value FROM json_each(?1))
+             ORDER BY task_tags.task_id, tags.id",
+        )?;
+
+    let tag_iter = stmt
+        .query_map([ids_json], |row| {
+            Ok((
+                row.get::<_, i32>(0)?,
+                Tag {
+                    id: row.get(1)?,
+                    name: row.get(2)?,
+                    color: row.get(3)?,
+                },
+            ))
+        })?;
+
+    let mut tags: HashMap<i32, Vec<Tag>> = HashMap::new();
+    for t in tag_iter {
+        let (task_id, tag) = t?;
+        tags.entry(task_id).or_default().push(tag);
+    }
+
+    Ok(tags)
+}
+
+// Helper to parse optional RFC3339 date string
+fn parse_opt_date(s: Option<String>) -> Result<Option<DateTime<Utc>>, NoWorkError> {
+    match s {
+        Some(s) => Ok(Some(
+            DateTime::parse_from_rfc3339(&s)
+                .map_err(|e| NoWorkError::Storage(e.to_string()))?
+                .with_timezone(&Utc),
+        )),
+        None => Ok(None),
+    }
+}
+
+// Fetch a single task on an already open connection
+pub fn load_task(conn: &Connection, task_id: i32) -> Result<Task, NoWorkError> {
+    load_tasks(conn, "WHERE tasks.id = ?1", params![task_id])?
+        .pop()
+        .ok_or(NoWorkError::TaskNotFound)
+}
+
+// Fetch the subtasks of a task in checklist order
+pub fn load_subtasks(conn: &Connection, parent_id: i32) -> Result<Vec<Task>, NoWorkError> {
+    load_tasks(
+        conn,
+        "WHERE tasks.parent_id = ?1 ORDER BY tasks.position, tasks.id",
+        params![parent_id],
+    )
+}
+
+// The fixed task lists of the sidebar
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum TaskView {
+    #[default]
+    All,
+    Incomplete,
+    DueToday,
+    // weeks start on sunday
+    DueThisWeek,
+}
+
+impl TaskView {
+    // as the server's view parameter spells it
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TaskView::All => "all",
+            TaskView::Incomplete => "incomplete",
+            TaskView::DueToday => "dueToday",
+            TaskView::DueThisWeek => "dueThisWeek",
+        }
+    }
+
+    pub fn query(self) -> TaskQuery {
+        let today = Local::now().date_naive();
+        match self {
+            TaskView::All => TaskQuery::default(),
+            TaskView::Incomplete => TaskQuery {
+                completed: Some(false),
+                ..Default::default()
+            },
+            TaskView::DueToday => TaskQuery {
+                due: Some(local_day_range(today, 1)),
+                ..Default::default()
+            },
+            TaskView::DueThisWeek => {
+                let weekday = today.weekday().num_days_from_sunday() as i64;
+                TaskQuery {
+                    due: Some(local_day_range(today - Duration::days(weekday), 7)),
+                    ..Default::default()
+                }
+            }
+        }
+    }
+}
+
+// Inserts a task with its tags in one transaction, returns the new id
+pub fn add_task(conn: &mut Connection, task: NewTask) -> Result<i32, NoWorkError> {
+    insert_task(conn, task, None)
+}
+
+// What adding a task under a client-chosen uid did
+#[derive(Debug, PartialEq)]
+pub enum UidInsert {
+    Created(i32),
+    // the uid was used before, this is the task it added
+    Existing(i32),
+    // the task the uid added has been deleted since
+    Deleted,
+}
+
+// Inserts a task under a uid the client chose for it, so sending the same
+// task twice (e.g. a retried request) adds it only once
+pub fn add_task_with_uid(conn: &mut Connection, task: NewTask, uid: &str) -> Result<UidInsert, NoWorkError> {
+    if uid.len() != 32 || !uid.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+        return Err(NoWorkError::validation("A task uid is 32 lowercase hex characters"));
+    }
+
+    let existing = conn
+        .query_row("SELECT id FROM tasks WHERE uid = ?1", params![uid], |row| row.get(0))
+        .optional()?;
+    if let Some(task_id) = existing {
+        return Ok(UidInsert::Existing(task_id));
+    }
+    // the change log outlives the task
+    let deleted = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM change_log WHERE entity = 'task' AND uid = ?1)",
+        params![uid],
+        |row| row.get::<_, bool>(0),
+    )?;
+    if deleted {
+        return Ok(UidInsert::Deleted);
+    }
+
+    insert_task(conn, task, Some(uid)).map(UidInsert::Created)
+}
+
+fn insert_task(conn: &mut Connection, task: NewTask, uid: Option<&str>) -> Result<i32, NoWorkError> {
+    let recurrence = task.recurrence.map(recurrence::validate).transpose()?;
+
+    let tx = conn.transaction()?;
+
+    let due_date_str = task.due_date.map(|dt| dt.to_rfc3339());
+    let priority_num = task.priority.map(|p| p.as_i32());
+    let recurrence_rule = recurrence.as_ref().map(|r| r.rule.clone());
+    let recurrence_mode = recurrence.as_ref().map(|r| r.mode.as_i32());
+    tx.execute(
+        "INSERT INTO tasks (name, due_date, priority, recurrence_rule, recurrence_mode, uid)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![task.name, due_date_str, priority_num, recurrence_rule, recurrence_mode, uid],
+    )?;
+
+    let task_id = tx.last_insert_rowid();
+
+    if let Some(tags_vec) = task.tags {
+        for tag in tags_vec {
+            tx.execute(
+                "INSERT INTO tags (name, color) VALUES (?1, ?2)
+                 ON CONFLICT(name) DO UPDATE SET color = excluded.color",
+                params![tag.name, tag.color],
+            )?;
+
+            let tag_id: i64 = tx
+                .query_row(
+                    "SELECT id FROM tags WHERE name = ?1",
+                    params![tag.name],
+                    |row| row.get(0),
+                )?;
+
+            tx.execute(
+                "INSERT OR IGNORE INTO task_tags (task_id, tag_id) VALUES (?1, ?2)",
+                params![task_id, tag_id],
+            )?;
+        }
+    }
+
+    tx.commit()?;
+    Ok(task_id as i32)
+}
+
+// Complete a task, spawning its next occurrence if it recurs.
+// With complete_subtasks set, any unfinished subtasks are completed too.
+pub fn complete_task(conn: &mut Connection, task_id: i32, complete_subtasks: bool) -> Result<(), NoWorkError> {
+    let tx = conn.transaction()?;
+
+    let rows = tx.execute(
+        "UPDATE tasks SET completed = 1, completed_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE id = ?1 AND completed = 0",
+        params![task_id],
+    )?;
+
+    if complete_subtasks {
+        tx.execute(
+            "UPDATE tasks SET completed = 1, completed_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now') WHERE parent_id = ?1 AND completed = 0",
+            params![task_id],
+        )?;
+    }
+
+    // already completed tasks must not spawn a second occurrence
+    if rows > 0 {
+        recurrence::spawn_next_occurrence(&tx, task_id)?;
+    }
+
+    tx.commit().map_err(NoWorkError::from)
+}
+
+// Delete a task, its subtasks and tag links go with it through ON DELETE CASCADE
+pub fn delete_task(conn: &Connection, task_id: i32) -> Result<(), NoWorkError> {
+    conn.execute("DELETE FROM tasks WHERE id = ?1", params![task_id])?;
+    Ok(())
+}
+
+pub fn update_task_name(conn: &Connection, task_id: i32, new_name: &str) -> Result<Task, NoWorkError> {
+    let rows = conn
+        .execute(
+            "UPDATE tasks SET name = ?1 WHERE id = ?2",
+            params![new_name, task_id],
+        )?;
+
+    if rows == 0 {
+        return Err(NoWorkError::TaskNotFound);
+    }
+
+    load_task(conn, task_id)
+}
+
+pub fn update_task_due_date(
+    conn: &Connection,
+    task_id: i32,
+    new_due_date: Option<DateTime<Utc>>,
+) -> Result<Task, NoWorkError> {
+    // Option<DateTime<Utc>> -> Option<String> (RFC3339)
+    let due_date_str = new_due_date.map(|dt| dt.to_rfc3339());
+
+    let rows = conn
+        .execute(
+            "UPDATE tasks SET due_date = ?1 WHERE id = ?2",
+            params![due_date_str, task_id],
+        )?;
+
+    if rows == 0 {
+        return Err(NoWorkError::TaskNotFound);
+    }
+
+    load_task(conn, task_id)
+}
+
+pub fn update_task_priority(
+    conn: &Connection,
+    task_id: i32,
+    new_priority: Option<TaskPriority>,
+) -> Result<Task, NoWorkError> {
+    let priority_num = new_priority.map(|p| p.as_i32());
+
+    let rows = conn
+        .execute(
+            "UPDATE tasks SET priority = ?1 WHERE id = ?2",
+            params![priority_num, task_id],
+        )?;
+
+    if rows == 0 {
+        return Err(NoWorkError::TaskNotFound);
+    }
+
+    load_task(conn, task_id)
+}
+
+// Applies every change in one transaction, so an invalid field leaves the
+// task untouched
+pub fn update_task(conn: &mut Connection, task_id: i32, changes: TaskChanges) -> Result<Task, NoWorkError> {
+    let tx = conn.transaction()?;
+    if let Some(name) = changes.name {
+        update_task_name(&tx, task_id, &name)?;
+    }
+    if let Some(due_date) = changes.due_date {
+        update_task_due_date(&tx, task_id, due_date)?;
+    }
+    if let Some(priority) = changes.priority {
+        update_task_priority(&tx, task_id, priority)?;
+    }
+    if let Some(recurrence) = changes.recurrence {
+        recurrence::update_task_recurrence(&tx, task_id, recurrence)?;
+    }
+    let task = load_task(&tx, task_id)?;
+    tx.commit()?;
+    Ok(task)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::migrations;
+    use crate::query::run_query;
+    use crate::types::TagFilter;
+    use std::path::Path;
+    use std::time::Instant;
+
+    const TAG_COUNT: i64 = 12;
+
+    fn database() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "foreign_keys", true).unwrap();
+        migrations::migrate(&mut conn, Path::new("unused")).unwrap();
+        conn
+    }
+
+    // tag ids a seeded task gets, one to three of them depending on its id
+    fn expected_tags(task_id: i64) -> Vec<i64> {
+        let mut ids = vec![task_id % TAG_COUNT + 1, task_id * 5 % TAG_COUNT + 1, task_id * 7 % TAG_COUNT + 1];
+        ids.sort_unstable();
+        ids.dedup();
+        ids.truncate((task_id % 3 + 1) as usize);
+        ids
+    }
+
+    // count top-level tasks with tags and every fifth with two subtasks, one done
+    fn seed(conn: &mut Connection, count: i64) {
+        let tx = conn.transaction().unwrap();
+        for tag in 1..=TAG_COUNT {
+            tx.execute(
+                "INSERT INTO tags (id, name, color) VALUES (?1, ?2, 'default')",
+                params![tag, format!("tag {}", tag)],
+            )
+            .unwrap();
+        }
+        for id in 1..=count {
+            tx.execute(
+                "INSERT INTO tasks (id, name, priority) VALUES (?1, ?2, ?3)",
+                params![id, format!("task {}", id), id % 4],
+            )
+            .unwrap();
+            for tag in expected_tags(id) {
+                tx.execute("INSERT INTO task_tags (task_id, tag_id) VALUES (?1, ?2)", params![id, tag])
+                    .unwrap();
+            }
+        }
+        for parent in (1..=count).step_by(5) {
+            for done in [0, 1] {
+                tx.execute(
+                    "INSERT INTO tasks (name, parent_id, completed) VALUES ('step', ?1, ?2)",
+                    params![parent, done],
+                )
+                .unwrap();
+            }
+        }
+        tx.commit().unwrap();
+    }
+
+    fn check_tags(tasks: &[Task]) {
+        for task in tasks {
+            let mut ids: Vec<i64> = task.tags.iter().flatten().map(|tag| tag.id).collect();
+            ids.sort_unstable();
+            assert_eq!(ids, expected_tags(task.id as i64), "tags of task {}", task.id);
+            for tag in task.tags.iter().flatten() {
+                assert_eq!(tag.name, format!("tag {}", tag.id));
+            }
+        }
+    }
+
+    #[test]
+    fn batched_loading_keeps_tags_and_progress_apart() {
+        let mut conn = database();
+        seed(&mut conn, 2_000);
+
+        let tasks = load_tasks(&conn, "WHERE parent_id IS NULL ORDER BY id", []).unwrap();
+        assert_eq!(tasks.len(), 2_000);
+        check_tags(&tasks);
+
+        for task in &tasks {
+            match &task.subtask_progress {
+                Some(progress) => {
+                    assert_eq!(task.id % 5, 1);
+                    assert_eq!((progress.completed, progress.total), (1, 2));
+                }
+                None => assert_ne!(task.id % 5, 1),
+            }
+        }
+    }
+
+    #[test]
+    fn a_uid_adds_its_task_once() {
+        let mut conn = database();
+        let uid = "0123456789abcdef0123456789abcdef";
+        let task = || NewTask {
+            name: "Call the plumber".to_string(),
+            due_date: None,
+            priority: None,
+            tags: None,
+            recurrence: None,
+        };
+
+        let UidInsert::Created(first) = add_task_with_uid(&mut conn, task(), uid).unwrap() else {
+            panic!("the task was not added");
+        };
+        assert_eq!(add_task_with_uid(&mut conn, task(), uid).unwrap(), UidInsert::Existing(first));
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM tasks", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 1);
+
+        conn.execute("DELETE FROM tasks WHERE id = ?1", params![first]).unwrap();
+        assert_eq!(add_task_with_uid(&mut conn, task(), uid).unwrap(), UidInsert::Deleted);
+
+        assert!(add_task_with_uid(&mut conn, task(), "not a uid").is_err());
+    }
+
+    // cargo test -p nowork-core --release -- --ignored --nocapture load_time
+    #[test]
+    #[ignore]
+    fn load_time_for_large_databases() {
+        let mut conn = database();
+        seed(&mut conn, 20_000);
+
+        let start = Instant::now();
+        let tasks = load_tasks(&conn, "WHERE parent_id IS NULL", []).unwrap();
+        let load_time = start.elapsed();
+        assert_eq!(tasks.len(), 20_000);
+        check_tags(&tasks);
+
+        let query = TaskQuery {
+            tags: TagFilter {
+                any: vec![1, 2],
+                ..Default::default()
+            },
+            limit: Some(500),
+            ..Default::default()
+        };
+        let start = Instant::now();
+        let page = run_query(&conn, &query).unwrap();
+        let query_time = start.elapsed();
+        check_tags(&page.tasks);
+        assert_eq!(page.tasks.len(), 500);
+
+        println!("load_tasks, 20000 tasks: {:?}", load_time);
+        println!("run_query, first 500 of {} tagged 1 or 2: {:?}", page.total, query_time);
+    }
+}
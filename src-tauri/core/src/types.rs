@@ -0,0 +1,323 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TaskPriority {
+    Low,
+    Medium,
+    High,
+}
+
+impl TaskPriority {
+    pub fn as_i32(&self) -> i32 {
+        match self {
+            TaskPriority::Low => 0,
+            TaskPriority::Medium => 1,
+            TaskPriority::High => 2,
+        }
+    }
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(TaskPriority::Low),
+            1 => Some(TaskPriority::Medium),
+            2 => Some(TaskPriority::High),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum RecurrenceMode {
+    // next due date follows the rule from the previous due date
+    FixedSchedule,
+    // next due date follows the rule from the moment the task was completed
+    AfterCompletion,
+}
+
+impl RecurrenceMode {
+    pub fn as_i32(&self) -> i32 {
+        match self {
+            RecurrenceMode::FixedSchedule => 0,
+            RecurrenceMode::AfterCompletion => 1,
+        }
+    }
+    pub fn from_i32(value: i32) -> Option<Self> {
+        match value {
+            0 => Some(RecurrenceMode::FixedSchedule),
+            1 => Some(RecurrenceMode::AfterCompletion),
+            _ => None,
+        }
+    }
+}
+
+// rule is an RFC 5545 RRULE value, e.g. "FREQ=DAILY" or "FREQ=WEEKLY;BYDAY=MO,TH"
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Recurrence {
+    pub rule: String,
+    pub mode: RecurrenceMode,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubtaskProgress {
+    pub completed: i32,
+    pub total: i32,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Task {
+    pub id: i32,
+    pub name: String,
+    pub due_date: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub completed: bool,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub priority: Option<TaskPriority>,
+    pub tags: Option<Vec<Tag>>,
+    pub recurrence: Option<Recurrence>,
+    pub parent_id: Option<i32>,
+    #[serde(default)]
+    pub position: i32,
+    pub subtask_progress: Option<SubtaskProgress>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tag {
+    pub id: i64,
+    pub name: String,
+    pub color: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NewTag {
+    pub name: String,
+    pub color: String,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewTask {
+    pub name: String,
+    pub due_date: Option<DateTime<Utc>>,
+    pub priority: Option<TaskPriority>,
+    // created if missing, an existing tag gets the given color
+    pub tags: Option<Vec<NewTag>>,
+    pub recurrence: Option<Recurrence>,
+}
+
+// Tells a field that is absent (leave as is) from one that is null (clear it)
+fn nullable<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+// An edit to a task, only the fields that are Some are changed
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskChanges {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub due_date: Option<Option<DateTime<Utc>>>,
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub priority: Option<Option<TaskPriority>>,
+    #[serde(default, deserialize_with = "nullable", skip_serializing_if = "Option::is_none")]
+    pub recurrence: Option<Option<Recurrence>>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SearchQuery {
+    pub text: String,
+    // matches tasks carrying any of these tag ids
+    pub tag_ids: Vec<i64>,
+    // None in this list matches tasks without a priority
+    pub priorities: Vec<Option<TaskPriority>>,
+    pub completed: Option<bool>,
+    pub limit: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResult {
+    pub task: Task,
+    // task name with matched terms wrapped in <mark></mark>, the rest is not escaped
+    pub snippet: String,
+    pub rank: f64,
+}
+
+// start is inclusive, end is exclusive, either side may be open
+#[derive(Serialize, Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DateRange {
+    pub start: Option<DateTime<Utc>>,
+    pub end: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TagFilter {
+    // task has at least one of these tag ids
+    pub any: Vec<i64>,
+    // task has every one of these tag ids
+    pub all: Vec<i64>,
+    // task has none of these tag ids
+    pub none: Vec<i64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub enum TaskSortKey {
+    Id,
+    Name,
+    DueDate,
+    CreatedAt,
+    CompletedAt,
+    Priority,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskSort {
+    pub key: TaskSortKey,
+    #[serde(default)]
+    pub descending: bool,
+}
+
+// Every field is optional, an empty query returns all top-level tasks by id
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TaskQuery {
+    pub tags: TagFilter,
+    // None in this list matches tasks without a priority
+    pub priorities: Vec<Option<TaskPriority>>,
+    pub due: Option<DateRange>,
+    pub has_due_date: Option<bool>,
+    // due before now and not completed
+    pub overdue: Option<bool>,
+    pub completed: Option<bool>,
+    pub created: Option<DateRange>,
+    pub completed_between: Option<DateRange>,
+    pub include_subtasks: bool,
+    pub sort: Vec<TaskSort>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskPage {
+    pub tasks: Vec<Task>,
+    // number of matching tasks ignoring limit and offset
+    pub total: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum BackupKind {
+    // taken from the settings page
+    Manual,
+    // taken automatically, only the newest few are kept
+    Daily,
+    PreReset,
+    PreRestore,
+    PreMigration,
+    PreImport,
+}
+
+impl BackupKind {
+    // file name prefix, backups are named "<prefix>-<local timestamp>.db"
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            BackupKind::Manual => "manual",
+            BackupKind::Daily => "daily",
+            BackupKind::PreReset => "pre-reset",
+            BackupKind::PreRestore => "pre-restore",
+            BackupKind::PreMigration => "pre-migration",
+            BackupKind::PreImport => "pre-import",
+        }
+    }
+
+    pub fn from_file_name(file_name: &str) -> Option<Self> {
+        [
+            BackupKind::Manual,
+            BackupKind::Daily,
+            BackupKind::PreReset,
+            BackupKind::PreRestore,
+            BackupKind::PreMigration,
+            BackupKind::PreImport,
+        ]
+        .into_iter()
+        .find(|kind| file_name.starts_with(&format!("{}-", kind.prefix())))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    pub file_name: String,
+    pub kind: BackupKind,
+    pub created_at: DateTime<Utc>,
+    pub size_bytes: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskTagLink {
+    pub task_id: i32,
+    pub tag_id: i64,
+}
+
+// The whole workspace as written by export_workspace. Ids are only meaningful
+// within the document, import assigns fresh ones.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkspaceExport {
+    // always "nowork-workspace"
+    pub format: String,
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub tags: Vec<Tag>,
+    // tags and subtaskProgress are left empty, links live in task_tags
+    pub tasks: Vec<Task>,
+    pub task_tags: Vec<TaskTagLink>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum ImportMode {
+    // add to the existing data, tags with the same name are reused
+    Merge,
+    // delete all tasks and tags first
+    Replace,
+}
+
+// A record that was skipped during import, index is its position in its section
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportRecordError {
+    pub section: String,
+    pub index: usize,
+    pub id: Option<i64>,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    pub tags_created: usize,
+    // tags matched by name to one that already existed
+    pub tags_merged: usize,
+    pub tasks_imported: usize,
+    pub links_imported: usize,
+    pub errors: Vec<ImportRecordError>,
+}
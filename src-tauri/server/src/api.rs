@@ -0,0 +1,563 @@
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put},
+    Router,
+};
+use nowork_core::{
+    database::Database,
+    error::NoWorkError,
+    query::run_query,
+    search, subtasks,
+    sync::{self, SyncRequest, SyncResponse},
+    tags,
+    tasks::{self, load_subtasks, load_task, TaskView, UidInsert},
+    types::{NewTag, NewTask, SearchQuery, SearchResult, Tag, Task, TaskChanges, TaskPage, TaskQuery},
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::{
+    auth::require_token,
+    error::ApiError,
+    extract::{Json, Path, Query},
+};
+
+#[derive(Clone)]
+pub struct AppState {
+    pub db: Database,
+    pub token: Arc<str>,
+}
+
+type ApiResult<T> = Result<T, ApiError>;
+
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+// Every route but the health check needs the bearer token. Bodies and
+// responses use the same camelCase JSON as the app's commands.
+pub fn router(state: AppState) -> Router {
+    let api = Router::new()
+        .route("/tasks", get(list_tasks).post(create_task))
+        .route("/tasks/query", post(query_tasks))
+        .route("/tasks/search", post(search_tasks))
+        .route("/tasks/{task_id}", get(get_task).patch(update_task).delete(delete_task))
+        .route("/tasks/{task_id}/complete", post(complete_task))
+        .route("/tasks/{task_id}/tags/{tag_id}", put(add_tag_to_task).delete(remove_tag_from_task))
+        .route(
+            "/tasks/{task_id}/subtasks",
+            get(list_subtasks).post(create_subtask).put(reorder_subtasks),
+        )
+        .route("/subtasks/{subtask_id}/completed", put(set_subtask_completed))
+        .route("/tags", get(list_tags).post(create_tag))
+        .route("/tags/{tag_name}", delete(remove_tag))
+        .route("/tags/{tag_name}/color", put(update_tag_color))
+        .route("/sync", post(sync_changes))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_token));
+
+    Router::new()
+        .route("/api/health", get(health))
+        .nest("/api", api)
+        .with_state(state)
+}
+
+async fn health() -> &'static str {
+    "ok"
+}
+
+#[derive(Deserialize)]
+struct ListParams {
+    #[serde(default)]
+    view: TaskView,
+}
+
+// GET /api/tasks?view=all|incomplete|dueToday|dueThisWeek
+async fn list_tasks(State(state): State<AppState>, Query(params): Query<ListParams>) -> ApiResult<Json<Vec<Task>>> {
+    let tasks = state
+        .db
+        .run(move |conn| Ok(run_query(conn, &params.view.query())?.tasks))
+        .await?;
+    Ok(Json(tasks))
+}
+
+async fn query_tasks(State(state): State<AppState>, Json(query): Json<TaskQuery>) -> ApiResult<Json<TaskPage>> {
+    let page = state.db.run(move |conn| run_query(conn, &query)).await?;
+    Ok(Json(page))
+}
+
+async fn search_tasks(State(state): State<AppState>, Json(query): Json<SearchQuery>) -> ApiResult<Json<Vec<SearchResult>>> {
+    let results = state.db.run(move |conn| search::search_tasks(conn, &query)).await?;
+    Ok(Json(results))
+}
+
+// With an Idempotency-Key header, the uid the new task gets, sending the
+// task again is safe: the task made the first time is answered with 200, or
+// 204 if it has been deleted since.
+async fn create_task(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(new_task): Json<NewTask>,
+) -> ApiResult<Response> {
+    let key = headers
+        .get(IDEMPOTENCY_KEY)
+        .map(|value| value.to_str().map(str::to_string))
+        .transpose()
+        .map_err(|_| NoWorkError::validation("The Idempotency-Key header is not a task uid"))?;
+
+    let Some(uid) = key else {
+        let task = state
+            .db
+            .run(move |conn| {
+                let task_id = tasks::add_task(conn, new_task)?;
+                load_task(conn, task_id)
+            })
+            .await?;
+        return Ok((StatusCode::CREATED, Json(task)).into_response());
+    };
+
+    let response = state
+        .db
+        .run(move |conn| {
+            Ok(match tasks::add_task_with_uid(conn, new_task, &uid)? {
+                UidInsert::Created(task_id) => (StatusCode::CREATED, Json(load_task(conn, task_id)?)).into_response(),
+                UidInsert::Existing(task_id) => (StatusCode::OK, Json(load_task(conn, task_id)?)).into_response(),
+                UidInsert::Deleted => StatusCode::NO_CONTENT.into_response(),
+            })
+        })
+        .await?;
+    Ok(response)
+}
+
+async fn get_task(State(state): State<AppState>, Path(task_id): Path<i32>) -> ApiResult<Json<Task>> {
+    let task = state.db.run(move |conn| load_task(conn, task_id)).await?;
+    Ok(Json(task))
+}
+
+// Only the fields present in the body are changed, null clears one
+async fn update_task(
+    State(state): State<AppState>,
+    Path(task_id): Path<i32>,
+    Json(changes): Json<TaskChanges>,
+) -> ApiResult<Json<Task>> {
+    let task = state
+        .db
+        .run(move |conn| tasks::update_task(conn, task_id, changes))
+        .await?;
+    Ok(Json(task))
+}
+
+async fn delete_task(State(state): State<AppState>, Path(task_id): Path<i32>) -> ApiResult<StatusCode> {
+    state.db.run(move |conn| tasks::delete_task(conn, task_id)).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CompleteParams {
+    #[serde(default)]
+    complete_subtasks: bool,
+}
+
+// POST /api/tasks/{task_id}/complete?completeSubtasks=true
+async fn complete_task(
+    State(state): State<AppState>,
+    Path(task_id): Path<i32>,
+    Query(params): Query<CompleteParams>,
+) -> ApiResult<Json<Task>> {
+    let task = state
+        .db
+        .run(move |conn| {
+            tasks::complete_task(conn, task_id, params.complete_subtasks)?;
+            load_task(conn, task_id)
+        })
+        .await?;
+    Ok(Json(task))
+}
+
+async fn add_tag_to_task(State(state): State<AppState>, Path((task_id, tag_id)): Path<(i32, i32)>) -> ApiResult<StatusCode> {
+    state.db.run(move |conn| tags::add_tag_to_task(conn, task_id, tag_id)).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn remove_tag_from_task(
+    State(state): State<AppState>,
+    Path((task_id, tag_id)): Path<(i32, i32)>,
+) -> ApiResult<StatusCode> {
+    state.db.run(move |conn| tags::remove_tag_from_task(conn, task_id, tag_id)).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_subtasks(State(state): State<AppState>, Path(task_id): Path<i32>) -> ApiResult<Json<Vec<Task>>> {
+    let subtasks = state.db.run(move |conn| load_subtasks(conn, task_id)).await?;
+    Ok(Json(subtasks))
+}
+
+#[derive(Deserialize)]
+struct NewSubtask {
+    name: String,
+}
+
+async fn create_subtask(
+    State(state): State<AppState>,
+    Path(task_id): Path<i32>,
+    Json(subtask): Json<NewSubtask>,
+) -> ApiResult<(StatusCode, Json<Task>)> {
+    let subtask = state
+        .db
+        .run(move |conn| subtasks::add_subtask(conn, task_id, &subtask.name))
+        .await?;
+    Ok((StatusCode::CREATED, Json(subtask)))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SubtaskOrder {
+    subtask_ids: Vec<i32>,
+}
+
+// The body lists every subtask of the task exactly once, in the new order
+async fn reorder_subtasks(
+    State(state): State<AppState>,
+    Path(task_id): Path<i32>,
+    Json(order): Json<SubtaskOrder>,
+) -> ApiResult<Json<Vec<Task>>> {
+    let subtasks = state
+        .db
+        .run(move |conn| subtasks::reorder_subtasks(conn, task_id, &order.subtask_ids))
+        .await?;
+    Ok(Json(subtasks))
+}
+
+#[derive(Deserialize)]
+struct Completion {
+    completed: bool,
+}
+
+async fn set_subtask_completed(
+    State(state): State<AppState>,
+    Path(subtask_id): Path<i32>,
+    Json(completion): Json<Completion>,
+) -> ApiResult<Json<Task>> {
+    let subtask = state
+        .db
+        .run(move |conn| subtasks::set_subtask_completed(conn, subtask_id, completion.completed))
+        .await?;
+    Ok(Json(subtask))
+}
+
+async fn list_tags(State(state): State<AppState>) -> ApiResult<Json<Vec<Tag>>> {
+    let tags = state.db.run(|conn| tags::list_tags(conn)).await?;
+    Ok(Json(tags))
+}
+
+async fn create_tag(State(state): State<AppState>, Json(new_tag): Json<NewTag>) -> ApiResult<(StatusCode, Json<Tag>)> {
+    let tag = state.db.run(move |conn| tags::add_tag(conn, new_tag)).await?;
+    Ok((StatusCode::CREATED, Json(tag)))
+}
+
+async fn remove_tag(State(state): State<AppState>, Path(tag_name): Path<String>) -> ApiResult<StatusCode> {
+    state.db.run(move |conn| tags::remove_tag(conn, &tag_name)).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Deserialize)]
+struct ColorChange {
+    color: String,
+}
+
+async fn update_tag_color(
+    State(state): State<AppState>,
+    Path(tag_name): Path<String>,
+    Json(change): Json<ColorChange>,
+) -> ApiResult<Json<Tag>> {
+    let tag = state
+        .db
+        .run(move |conn| tags::update_tag_color_by_name(conn, &tag_name, &change.color))
+        .await?;
+    Ok(Json(tag))
+}
+
+// Devices keeping their own copy push the changes they made and pull the
+// ones they have not seen, see nowork_core::sync
+async fn sync_changes(State(state): State<AppState>, Json(request): Json<SyncRequest>) -> ApiResult<Json<SyncResponse>> {
+    let response = state.db.run(move |conn| sync::serve(conn, request)).await?;
+    Ok(Json(response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{header, Method, Request},
+    };
+    use http_body_util::BodyExt;
+    use serde_json::{json, Value};
+    use tempfile::TempDir;
+    use tower::ServiceExt;
+
+    const TOKEN: &str = "secret";
+
+    // the router on a database in a temporary folder, removed with the TempDir
+    fn app() -> (Router, TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let db = nowork_core::database::open(dir.path().join("tasks.db"), &dir.path().join("backups")).unwrap();
+        let state = AppState {
+            db,
+            token: Arc::from(TOKEN),
+        };
+        (router(state), dir)
+    }
+
+    async fn send(app: &Router, method: Method, uri: &str, token: Option<&str>, body: Option<Value>) -> (StatusCode, Value) {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let request = match body {
+            Some(body) => request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(Body::from(body.to_string())),
+            None => request.body(Body::empty()),
+        }
+        .unwrap();
+
+        let response = app.clone().oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        let body = if bytes.is_empty() {
+            Value::Null
+        } else {
+            serde_json::from_slice(&bytes).unwrap_or_else(|_| Value::String(String::from_utf8_lossy(&bytes).into()))
+        };
+        (status, body)
+    }
+
+    async fn call(app: &Router, method: Method, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        send(app, method, uri, Some(TOKEN), body).await
+    }
+
+    fn assert_error(body: &Value, code: &str, category: &str) {
+        assert_eq!(body["code"], code);
+        assert_eq!(body["category"], category);
+        assert!(body["message"].as_str().is_some_and(|message| !message.is_empty()));
+    }
+
+    #[tokio::test]
+    async fn health_needs_no_token() {
+        let (app, _dir) = app();
+        let (status, body) = send(&app, Method::GET, "/api/health", None, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, "ok");
+    }
+
+    #[tokio::test]
+    async fn missing_or_wrong_token_is_rejected() {
+        let (app, _dir) = app();
+        for token in [None, Some("wrong"), Some("secret2"), Some("")] {
+            let (status, body) = send(&app, Method::GET, "/api/tasks", token, None).await;
+            assert_eq!(status, StatusCode::UNAUTHORIZED, "token {:?}", token);
+            assert_error(&body, "invalid_token", "auth");
+        }
+
+        // nothing is written without the token either
+        let new_task = json!({ "name": "sneaky" });
+        let (status, _) = send(&app, Method::POST, "/api/tasks", None, Some(new_task)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        let (_, tasks) = call(&app, Method::GET, "/api/tasks?view=all", None).await;
+        assert_eq!(tasks, json!([]));
+    }
+
+    #[tokio::test]
+    async fn tasks_round_trip() {
+        let (app, _dir) = app();
+
+        let new_task = json!({
+            "name": "Write report",
+            "dueDate": "2030-03-14T09:00:00Z",
+            "priority": "high",
+            "tags": [{ "name": "work", "color": "blue" }],
+        });
+        let (status, created) = call(&app, Method::POST, "/api/tasks", Some(new_task)).await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(created["name"], "Write report");
+        assert_eq!(created["priority"], "high");
+        assert_eq!(created["tags"][0]["name"], "work");
+        let id = created["id"].as_i64().unwrap();
+
+        let (status, tasks) = call(&app, Method::GET, "/api/tasks", None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(tasks.as_array().unwrap().len(), 1);
+        assert_eq!(tasks[0]["id"], id);
+
+        let (status, updated) = call(&app, Method::PATCH, &format!("/api/tasks/{}", id), Some(json!({ "name": "Send report" }))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(updated["name"], "Send report");
+
+        let (status, completed) = call(&app, Method::POST, &format!("/api/tasks/{}/complete", id), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(completed["completed"], true);
+        let (_, incomplete) = call(&app, Method::GET, "/api/tasks?view=incomplete", None).await;
+        assert_eq!(incomplete, json!([]));
+
+        let (status, _) = call(&app, Method::DELETE, &format!("/api/tasks/{}", id), None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (status, body) = call(&app, Method::GET, &format!("/api/tasks/{}", id), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_error(&body, "task_not_found", "notFound");
+    }
+
+    #[tokio::test]
+    async fn tags_round_trip() {
+        let (app, _dir) = app();
+
+        let (status, tag) = call(&app, Method::POST, "/api/tags", Some(json!({ "name": "home", "color": "default" }))).await;
+        assert_eq!(status, StatusCode::CREATED);
+        let (_, task) = call(&app, Method::POST, "/api/tasks", Some(json!({ "name": "Fix sink" }))).await;
+
+        let link = format!("/api/tasks/{}/tags/{}", task["id"], tag["id"]);
+        let (status, _) = call(&app, Method::PUT, &link, None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (_, task) = call(&app, Method::GET, &format!("/api/tasks/{}", task["id"]), None).await;
+        assert_eq!(task["tags"][0]["name"], "home");
+
+        let (status, recolored) = call(&app, Method::PUT, "/api/tags/home/color", Some(json!({ "color": "blue" }))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(recolored["color"], "blue");
+
+        let (status, _) = call(&app, Method::DELETE, "/api/tags/home", None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        let (_, tags) = call(&app, Method::GET, "/api/tags", None).await;
+        assert_eq!(tags, json!([]));
+    }
+
+    #[tokio::test]
+    async fn subtasks_round_trip() {
+        let (app, _dir) = app();
+        let (_, parent) = call(&app, Method::POST, "/api/tasks", Some(json!({ "name": "Pack" }))).await;
+        let subtasks = format!("/api/tasks/{}/subtasks", parent["id"]);
+
+        let mut ids = Vec::new();
+        for name in ["tent", "stove"] {
+            let (status, subtask) = call(&app, Method::POST, &subtasks, Some(json!({ "name": name }))).await;
+            assert_eq!(status, StatusCode::CREATED);
+            assert_eq!(subtask["parentId"], parent["id"]);
+            ids.push(subtask["id"].clone());
+        }
+
+        let (status, reordered) = call(&app, Method::PUT, &subtasks, Some(json!({ "subtaskIds": [ids[1], ids[0]] }))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(reordered[0]["name"], "stove");
+
+        let tick = format!("/api/subtasks/{}/completed", ids[0]);
+        let (status, ticked) = call(&app, Method::PUT, &tick, Some(json!({ "completed": true }))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(ticked["completed"], true);
+
+        let (_, listed) = call(&app, Method::GET, &subtasks, None).await;
+        let names: Vec<&str> = listed.as_array().unwrap().iter().map(|t| t["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["stove", "tent"]);
+
+        let (status, body) = call(&app, Method::PUT, &format!("/api/subtasks/{}/completed", parent["id"]), Some(json!({ "completed": true }))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_error(&body, "subtask_not_found", "notFound");
+        let (status, body) = call(&app, Method::PUT, &subtasks, Some(json!({ "subtaskIds": [ids[0]] }))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_error(&body, "invalid_input", "validation");
+    }
+
+    #[tokio::test]
+    async fn idempotency_key_adds_a_task_once() {
+        let (app, _dir) = app();
+        let create = |key: &str| {
+            Request::builder()
+                .method(Method::POST)
+                .uri("/api/tasks")
+                .header(header::AUTHORIZATION, format!("Bearer {}", TOKEN))
+                .header(header::CONTENT_TYPE, "application/json")
+                .header("Idempotency-Key", key)
+                .body(Body::from(json!({ "name": "Renew passport" }).to_string()))
+                .unwrap()
+        };
+        let key = "00112233445566778899aabbccddeeff";
+
+        let first = app.clone().oneshot(create(key)).await.unwrap();
+        assert_eq!(first.status(), StatusCode::CREATED);
+        let task: Value = serde_json::from_slice(&first.into_body().collect().await.unwrap().to_bytes()).unwrap();
+
+        let again = app.clone().oneshot(create(key)).await.unwrap();
+        assert_eq!(again.status(), StatusCode::OK);
+        let same: Value = serde_json::from_slice(&again.into_body().collect().await.unwrap().to_bytes()).unwrap();
+        assert_eq!(same["id"], task["id"]);
+        let (_, tasks) = call(&app, Method::GET, "/api/tasks?view=all", None).await;
+        assert_eq!(tasks.as_array().unwrap().len(), 1);
+
+        call(&app, Method::DELETE, &format!("/api/tasks/{}", task["id"]), None).await;
+        let deleted = app.clone().oneshot(create(key)).await.unwrap();
+        assert_eq!(deleted.status(), StatusCode::NO_CONTENT);
+
+        let invalid = app.clone().oneshot(create("not-a-uid")).await.unwrap();
+        assert_eq!(invalid.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn search_highlights_matches() {
+        let (app, _dir) = app();
+        call(&app, Method::POST, "/api/tasks", Some(json!({ "name": "Call <the> plumber" }))).await;
+        call(&app, Method::POST, "/api/tasks", Some(json!({ "name": "Water plants" }))).await;
+
+        let (status, results) = call(&app, Method::POST, "/api/tasks/search", Some(json!({ "text": "plumb" }))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(results.as_array().unwrap().len(), 1);
+        assert_eq!(results[0]["task"]["name"], "Call <the> plumber");
+        assert_eq!(results[0]["snippet"], "Call &lt;the&gt; <mark>plumber</mark>");
+    }
+
+    #[tokio::test]
+    async fn errors_have_code_category_and_message() {
+        let (app, _dir) = app();
+
+        let (status, body) = call(&app, Method::POST, "/api/tasks/999/complete", None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_error(&body, "task_not_found", "notFound");
+
+        let bad_rule = json!({ "name": "x", "recurrence": { "rule": "FREQ=SOMETIMES", "mode": "fixedSchedule" } });
+        let (status, body) = call(&app, Method::POST, "/api/tasks", Some(bad_rule)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_error(&body, "invalid_recurrence", "validation");
+
+        let (status, body) = call(&app, Method::PUT, "/api/tags/missing/color", Some(json!({ "color": "blue" }))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_error(&body, "tag_not_found", "notFound");
+    }
+
+    #[tokio::test]
+    async fn unreadable_requests_get_the_error_body() {
+        let (app, _dir) = app();
+
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/api/tasks")
+            .header(header::AUTHORIZATION, format!("Bearer {}", TOKEN))
+            .header(header::CONTENT_TYPE, "application/json")
+            .body(Body::from("{\"name\": "))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        assert_error(&serde_json::from_slice(&bytes).unwrap(), "invalid_input", "validation");
+
+        let (status, body) = call(&app, Method::POST, "/api/tasks", Some(json!({ "name": 5 }))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_error(&body, "invalid_input", "validation");
+
+        let (status, body) = call(&app, Method::GET, "/api/tasks/abc", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_error(&body, "invalid_input", "validation");
+
+        let (status, body) = call(&app, Method::GET, "/api/tasks?view=someday", None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_error(&body, "invalid_input", "validation");
+    }
+}
@@ -0,0 +1,28 @@
+use axum::{
+    extract::{Request, State},
+    http::header::AUTHORIZATION,
+    middleware::Next,
+    response::Response,
+};
+use nowork_core::error::NoWorkError;
+
+use crate::{api::AppState, error::ApiError};
+
+// Rejects any request without "Authorization: Bearer <NOWORK_TOKEN>"
+pub async fn require_token(State(state): State<AppState>, request: Request, next: Next) -> Result<Response, ApiError> {
+    let given = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match given {
+        Some(token) if tokens_match(token.as_bytes(), state.token.as_bytes()) => Ok(next.run(request).await),
+        _ => Err(NoWorkError::InvalidToken.into()),
+    }
+}
+
+// Compares every byte so the time taken does not reveal how much matched
+fn tokens_match(given: &[u8], expected: &[u8]) -> bool {
+    given.len() == expected.len() && given.iter().zip(expected).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
@@ -0,0 +1,49 @@
+use nowork_core::error::NoWorkError;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+const DEFAULT_ADDR: &str = "127.0.0.1:8787";
+const DEFAULT_DB: &str = "tasks.db";
+
+// Read from the environment:
+//   NOWORK_TOKEN       bearer token every request must carry, required
+//   NOWORK_ADDR        address to listen on, 127.0.0.1:8787 by default
+//   NOWORK_DB          database file, tasks.db in the working directory by default
+//   NOWORK_BACKUP_DIR  where backups go, "backups" next to the database by default
+pub struct Config {
+    pub addr: SocketAddr,
+    pub db_path: PathBuf,
+    pub backup_dir: PathBuf,
+    pub token: String,
+}
+
+impl Config {
+    pub fn from_env() -> Result<Self, NoWorkError> {
+        let token = std::env::var("NOWORK_TOKEN")
+            .ok()
+            .map(|token| token.trim().to_string())
+            .filter(|token| !token.is_empty())
+            .ok_or_else(|| NoWorkError::validation("Set NOWORK_TOKEN to the token clients must send"))?;
+
+        let addr = std::env::var("NOWORK_ADDR").unwrap_or_else(|_| DEFAULT_ADDR.to_string());
+        let addr = addr
+            .parse()
+            .map_err(|_| NoWorkError::validation(format!("NOWORK_ADDR is not a valid address: {}", addr)))?;
+
+        let db_path = PathBuf::from(std::env::var("NOWORK_DB").unwrap_or_else(|_| DEFAULT_DB.to_string()));
+        let backup_dir = match std::env::var("NOWORK_BACKUP_DIR") {
+            Ok(dir) => PathBuf::from(dir),
+            Err(_) => db_path
+                .parent()
+                .map(|dir| dir.join("backups"))
+                .unwrap_or_else(|| PathBuf::from("backups")),
+        };
+
+        Ok(Config {
+            addr,
+            db_path,
+            backup_dir,
+            token,
+        })
+    }
+}
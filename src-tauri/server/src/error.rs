@@ -0,0 +1,31 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use nowork_core::error::{ErrorCategory, NoWorkError};
+
+// NoWorkError as an HTTP response, the body is the same { code, category,
+// message } the app's commands reject with
+pub struct ApiError(NoWorkError);
+
+impl From<NoWorkError> for ApiError {
+    fn from(e: NoWorkError) -> Self {
+        ApiError(e)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let status = match (&self.0, self.0.category()) {
+            (NoWorkError::DatabaseLocked, _) => StatusCode::SERVICE_UNAVAILABLE,
+            (NoWorkError::EditConflict, _) => StatusCode::CONFLICT,
+            (_, ErrorCategory::NotFound) => StatusCode::NOT_FOUND,
+            (_, ErrorCategory::Validation) => StatusCode::BAD_REQUEST,
+            (_, ErrorCategory::Storage) => StatusCode::INTERNAL_SERVER_ERROR,
+            (_, ErrorCategory::Network) => StatusCode::BAD_GATEWAY,
+            (_, ErrorCategory::Auth) => StatusCode::UNAUTHORIZED,
+        };
+        (status, Json(self.0)).into_response()
+    }
+}
@@ -0,0 +1,40 @@
+// Headless NoWork: serves the task database over an authenticated JSON API
+// so it can be reached from other devices, e.g. over Tailscale.
+mod api;
+mod auth;
+mod config;
+mod error;
+mod extract;
+
+use nowork_core::{backup::run_daily_backups, database, error::NoWorkError, sync::run_daily_pruning};
+use std::sync::Arc;
+
+use crate::{api::AppState, config::Config};
+
+#[tokio::main]
+async fn main() {
+    // what the server does is logged, RUST_LOG can change how much is shown
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    if let Err(e) = serve().await {
+        eprintln!("nowork-server: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn serve() -> Result<(), NoWorkError> {
+    let config = Config::from_env()?;
+
+    let db = database::open(&config.db_path, &config.backup_dir)?;
+    tokio::spawn(run_daily_backups(db.clone(), config.backup_dir.clone()));
+    tokio::spawn(run_daily_pruning(db.clone()));
+
+    let state = AppState {
+        db,
+        token: Arc::from(config.token),
+    };
+
+    let listener = tokio::net::TcpListener::bind(config.addr).await?;
+    log::info!("Listening on http://{}", listener.local_addr()?);
+    axum::serve(listener, api::router(state)).await?;
+    Ok(())
+}
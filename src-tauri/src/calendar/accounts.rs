@@ -0,0 +1,218 @@
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+use crate::calendar::{
+    cache::sync_account,
+    caldav::{CalDavClient, CalendarInfo, ICLOUD_URL},
+    cal_credentials::{delete_password, load_password, save_password},
+};
+use crate::commands::database::Database;
+use crate::error::NoWorkError;
+
+const FASTMAIL_URL: &str = "https://caldav.fastmail.com";
+
+// Who hosts an account. iCloud and Fastmail have a fixed server, any other
+// CalDAV server (Nextcloud, Radicale, ...) is reached through the URL the
+// user enters. Local is the built-in account of subscriptions and imports.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum CalendarProvider {
+    Icloud,
+    Fastmail,
+    Caldav,
+    Local,
+}
+
+impl CalendarProvider {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CalendarProvider::Icloud => "icloud",
+            CalendarProvider::Fastmail => "fastmail",
+            CalendarProvider::Caldav => "caldav",
+            CalendarProvider::Local => "local",
+        }
+    }
+
+    fn from_str(value: &str) -> Self {
+        match value {
+            "icloud" => CalendarProvider::Icloud,
+            "fastmail" => CalendarProvider::Fastmail,
+            "local" => CalendarProvider::Local,
+            _ => CalendarProvider::Caldav,
+        }
+    }
+
+    fn server_url(&self) -> Option<&'static str> {
+        match self {
+            CalendarProvider::Icloud => Some(ICLOUD_URL),
+            CalendarProvider::Fastmail => Some(FASTMAIL_URL),
+            CalendarProvider::Caldav | CalendarProvider::Local => None,
+        }
+    }
+}
+
+// A saved account, known everywhere by its id. The same username may be
+// added once per server, its password is in the system keyring under both.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CalendarAccount {
+    pub id: i64,
+    pub username: String,
+    pub provider: CalendarProvider,
+    pub server_url: String,
+    // the calendar tasks are synced with, if any
+    pub task_calendar_url: Option<String>,
+}
+
+const ACCOUNT_COLUMNS: &str = "id, username, provider, server_url, task_calendar_url";
+
+fn account_from_row(row: &rusqlite::Row) -> rusqlite::Result<CalendarAccount> {
+    let provider = CalendarProvider::from_str(&row.get::<_, String>(2)?);
+    Ok(CalendarAccount {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        provider,
+        // only the local account has no server
+        server_url: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+        task_calendar_url: row.get(4)?,
+    })
+}
+
+pub fn load_account(conn: &Connection, account: i64) -> Result<CalendarAccount, NoWorkError> {
+    conn.query_row(
+        &format!("SELECT {} FROM calendar_accounts WHERE id = ?1", ACCOUNT_COLUMNS),
+        params![account],
+        account_from_row,
+    )
+    .optional()?
+    .ok_or(NoWorkError::AccountNotFound)
+}
+
+pub fn list_accounts(conn: &Connection) -> Result<Vec<CalendarAccount>, NoWorkError> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM calendar_accounts WHERE provider != 'local' ORDER BY username COLLATE NOCASE, server_url",
+        ACCOUNT_COLUMNS
+    ))?;
+    let accounts = stmt
+        .query_map([], account_from_row)?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(accounts)
+}
+
+// Client for a saved account
+pub async fn connect(db: &Database, account: i64) -> Result<CalDavClient, NoWorkError> {
+    let account = db.run(move |conn| load_account(conn, account)).await?;
+    if account.provider == CalendarProvider::Local {
+        return Err(NoWorkError::validation("Subscribed and imported calendars are read-only"));
+    }
+    let password = load_password(&account.server_url, &account.username)?;
+    CalDavClient::new(&account.server_url, &account.username, &password)
+}
+
+// Checks the password by finding the account's calendars, then saves the
+// account and syncs it for the first time
+#[tauri::command]
+pub async fn add_calendar_account(
+    db: State<'_, Database>,
+    provider: CalendarProvider,
+    server_url: Option<String>,
+    username: String,
+    password: String,
+) -> Result<Vec<CalendarInfo>, NoWorkError> {
+    let username = username.trim().to_string();
+    if provider == CalendarProvider::Local {
+        return Err(NoWorkError::validation("Subscribe to feeds and import files from the subscriptions list"));
+    }
+    if username.is_empty() || password.is_empty() {
+        return Err(NoWorkError::validation("Enter the username and password of the account"));
+    }
+    let server_url = match provider.server_url() {
+        Some(url) => url.to_string(),
+        None => server_url
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty())
+            .ok_or_else(|| NoWorkError::validation("Enter the address of the CalDAV server"))?,
+    };
+
+    let (account, server) = (username.clone(), server_url.clone());
+    let exists = db
+        .run(move |conn| {
+            Ok(conn.query_row(
+                "SELECT EXISTS(SELECT 1 FROM calendar_accounts WHERE server_url = ?1 AND username = ?2)",
+                params![server, account],
+                |row| row.get::<_, bool>(0),
+            )?)
+        })
+        .await?;
+    if exists {
+        return Err(NoWorkError::validation("This account has already been added"));
+    }
+
+    let client = CalDavClient::new(&server_url, &username, &password)?;
+    let principal = client.principal().await?;
+    let home = client.calendar_home(&principal).await?.to_string();
+    save_password(&server_url, &username, &password)?;
+
+    let account = db
+        .run(move |conn| {
+            conn.execute(
+                "INSERT INTO calendar_accounts (username, provider, server_url, home_url) VALUES (?1, ?2, ?3, ?4)",
+                params![username, provider.as_str(), server_url, home],
+            )?;
+            Ok(conn.last_insert_rowid())
+        })
+        .await?;
+
+    sync_account(&db, account).await
+}
+
+// Runs discovery only, without touching the cache
+#[tauri::command]
+pub async fn discover_calendars(db: State<'_, Database>, account: i64) -> Result<Vec<CalendarInfo>, NoWorkError> {
+    let client = connect(&db, account).await?;
+    client.discover().await
+}
+
+#[tauri::command]
+pub async fn list_calendar_accounts(db: State<'_, Database>) -> Result<Vec<CalendarAccount>, NoWorkError> {
+    db.run(|conn| list_accounts(conn)).await
+}
+
+// Signs an account out, its password and everything synced from it go
+#[tauri::command]
+pub async fn remove_calendar_account(db: State<'_, Database>, account: i64) -> Result<(), NoWorkError> {
+    let account = db
+        .run(move |conn| {
+            let account = load_account(conn, account)?;
+            if account.provider == CalendarProvider::Local {
+                return Err(NoWorkError::validation("Remove subscriptions and imports one by one instead"));
+            }
+            conn.execute("DELETE FROM calendar_accounts WHERE id = ?1", params![account.id])?;
+            Ok(account)
+        })
+        .await?;
+    delete_password(&account.server_url, &account.username)
+}
+
+// Hides a calendar's events, or shows them again. Disabled calendars keep
+// their cached events but are not synced until they are enabled.
+#[tauri::command]
+pub async fn set_calendar_enabled(
+    db: State<'_, Database>,
+    account: i64,
+    calendar_url: String,
+    enabled: bool,
+) -> Result<(), NoWorkError> {
+    db.run(move |conn| {
+        let updated = conn.execute(
+            "UPDATE calendars SET enabled = ?1 WHERE account = ?2 AND url = ?3",
+            params![enabled, account, calendar_url],
+        )?;
+        if updated == 0 {
+            return Err(NoWorkError::validation("Sync the account before choosing its calendars"));
+        }
+        Ok(())
+    })
+    .await
+}
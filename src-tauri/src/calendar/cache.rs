@@ -0,0 +1,425 @@
+use chrono::{DateTime, Utc};
+use futures::future::join_all;
+use reqwest::Url;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::{HashMap, HashSet};
+use tauri::State;
+
+use crate::calendar::{
+    ics::{parse_events, CalendarEvent},
+    accounts::{connect, list_accounts},
+    caldav::{CalDavClient, CalendarInfo, CalendarObject},
+    subscriptions::{refresh_subscriptions, LOCAL_ACCOUNT},
+};
+use crate::commands::database::Database;
+use crate::error::NoWorkError;
+
+// sync-collection requests per calendar when the server keeps truncating
+const MAX_SYNC_ROUNDS: usize = 10;
+
+// What a cached calendar was last synced at
+struct CachedCalendar {
+    id: i64,
+    ctag: Option<String>,
+    sync_token: Option<String>,
+    enabled: bool,
+}
+
+// Resources to write into and drop from one cached calendar
+#[derive(Default)]
+pub struct CalendarUpdate {
+    pub upserted: Vec<CalendarObject>,
+    pub removed: Vec<String>,
+}
+
+fn cached_home(conn: &Connection, account: i64) -> Result<Option<String>, NoWorkError> {
+    let home = conn
+        .query_row(
+            "SELECT home_url FROM calendar_accounts WHERE id = ?1",
+            params![account],
+            |row| row.get::<_, Option<String>>(0),
+        )
+        .optional()?;
+    Ok(home.flatten())
+}
+
+fn save_home(conn: &Connection, account: i64, home_url: &str) -> Result<(), NoWorkError> {
+    conn.execute(
+        "UPDATE calendar_accounts SET home_url = ?1 WHERE id = ?2",
+        params![home_url, account],
+    )?;
+    Ok(())
+}
+
+fn cached_calendars(conn: &Connection, account: i64) -> Result<HashMap<String, CachedCalendar>, NoWorkError> {
+    let mut stmt = conn.prepare("SELECT id, url, ctag, sync_token, enabled FROM calendars WHERE account = ?1")?;
+    let calendars = stmt
+        .query_map(params![account], |row| {
+            Ok((
+                row.get::<_, String>(1)?,
+                CachedCalendar {
+                    id: row.get(0)?,
+                    ctag: row.get(2)?,
+                    sync_token: row.get(3)?,
+                    enabled: row.get(4)?,
+                },
+            ))
+        })?
+        .collect::<rusqlite::Result<HashMap<_, _>>>()?;
+    Ok(calendars)
+}
+
+fn cached_etags(conn: &Connection, calendar_id: i64) -> Result<HashMap<String, Option<String>>, NoWorkError> {
+    let mut stmt = conn.prepare("SELECT href, etag FROM calendar_objects WHERE calendar_id = ?1")?;
+    let etags = stmt
+        .query_map(params![calendar_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<HashMap<_, _>>>()?;
+    Ok(etags)
+}
+
+// The calendars of an account as of the last sync
+pub fn list_cached_calendars(conn: &Connection, account: i64) -> Result<Vec<CalendarInfo>, NoWorkError> {
+    let mut stmt = conn.prepare(
+        "SELECT url, display_name, color, ctag, sync_token, components, enabled FROM calendars
+         WHERE account = ?1 ORDER BY display_name COLLATE NOCASE, url",
+    )?;
+    let calendars = stmt
+        .query_map(params![account], |row| {
+            let components: String = row.get(5)?;
+            Ok(CalendarInfo {
+                url: row.get(0)?,
+                display_name: row.get(1)?,
+                color: row.get(2)?,
+                ctag: row.get(3)?,
+                sync_token: row.get(4)?,
+                components: components.split(',').filter(|c| !c.is_empty()).map(String::from).collect(),
+                enabled: row.get(6)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(calendars)
+}
+
+// Writes one calendar and its changed resources in a single transaction, so
+// a failed sync never leaves a ctag or sync token pointing at a half updated cache
+pub fn store_calendar(conn: &mut Connection, account: i64, info: &CalendarInfo, update: CalendarUpdate) -> Result<(), NoWorkError> {
+    let tx = conn.transaction()?;
+
+    tx.execute(
+        "INSERT INTO calendars (account, url, display_name, color, ctag, sync_token, components, synced_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+         ON CONFLICT(account, url) DO UPDATE SET
+            display_name = excluded.display_name,
+            color = excluded.color,
+            ctag = excluded.ctag,
+            sync_token = excluded.sync_token,
+            components = excluded.components,
+            synced_at = excluded.synced_at",
+        params![
+            account,
+            info.url,
+            info.display_name,
+            info.color,
+            info.ctag,
+            info.sync_token,
+            info.components.join(","),
+            Utc::now().to_rfc3339()
+        ],
+    )?;
+    let calendar_id: i64 = tx.query_row(
+        "SELECT id FROM calendars WHERE account = ?1 AND url = ?2",
+        params![account, info.url],
+        |row| row.get(0),
+    )?;
+
+    for object in update.upserted {
+        tx.execute(
+            "INSERT INTO calendar_objects (calendar_id, href, etag, data) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(calendar_id, href) DO UPDATE SET etag = excluded.etag, data = excluded.data",
+            params![calendar_id, object.href, object.etag, object.data],
+        )?;
+    }
+    for href in update.removed {
+        tx.execute(
+            "DELETE FROM calendar_objects WHERE calendar_id = ?1 AND href = ?2",
+            params![calendar_id, href],
+        )?;
+    }
+
+    tx.commit()?;
+    Ok(())
+}
+
+// Drops calendars that are gone from the server, their resources go with them
+fn remove_missing_calendars(conn: &Connection, account: i64, remote_urls: &HashSet<String>) -> Result<(), NoWorkError> {
+    for (url, cached) in cached_calendars(conn, account)? {
+        if !remote_urls.contains(&url) {
+            conn.execute("DELETE FROM calendars WHERE id = ?1", params![cached.id])?;
+        }
+    }
+    Ok(())
+}
+
+// Downloads the changed resources. Anything the server no longer returns was
+// deleted after it was listed.
+async fn download(
+    client: &CalDavClient,
+    calendar_url: &str,
+    changed: Vec<String>,
+    mut removed: Vec<String>,
+) -> Result<CalendarUpdate, NoWorkError> {
+    let upserted = client.multiget(calendar_url, &changed).await?;
+    let returned: HashSet<&str> = upserted.iter().map(|o| o.href.as_str()).collect();
+    removed.extend(changed.iter().filter(|href| !returned.contains(href.as_str())).cloned());
+
+    Ok(CalendarUpdate { upserted, removed })
+}
+
+// Asks only for what changed since the stored sync token
+async fn incremental_sync(
+    client: &CalDavClient,
+    calendar_url: &str,
+    sync_token: &str,
+) -> Result<(CalendarUpdate, String), NoWorkError> {
+    let mut token = sync_token.to_string();
+    // href -> still exists, later rounds win
+    let mut seen: HashMap<String, bool> = HashMap::new();
+
+    for _ in 0..MAX_SYNC_ROUNDS {
+        let changes = client.sync_collection(calendar_url, &token).await?;
+        for href in changes.changed {
+            seen.insert(href, true);
+        }
+        for href in changes.removed {
+            seen.insert(href, false);
+        }
+        token = changes
+            .sync_token
+            .ok_or_else(|| NoWorkError::InvalidResponse("sync-collection returned no sync token".to_string()))?;
+        if !changes.truncated {
+            break;
+        }
+    }
+
+    let (changed, removed): (Vec<_>, Vec<_>) = seen.into_iter().partition(|(_, exists)| *exists);
+    let update = download(
+        client,
+        calendar_url,
+        changed.into_iter().map(|(href, _)| href).collect(),
+        removed.into_iter().map(|(href, _)| href).collect(),
+    )
+    .await?;
+    Ok((update, token))
+}
+
+// Lists every resource and compares ETags with the cache
+async fn full_sync(
+    client: &CalDavClient,
+    db: &Database,
+    calendar_url: &str,
+    cached_id: Option<i64>,
+) -> Result<CalendarUpdate, NoWorkError> {
+    let known = match cached_id {
+        Some(id) => db.run(move |conn| cached_etags(conn, id)).await?,
+        None => HashMap::new(),
+    };
+    let listing = client.etags(calendar_url).await?;
+
+    let listed: HashSet<&str> = listing.iter().map(|(href, _)| href.as_str()).collect();
+    let removed = known.keys().filter(|href| !listed.contains(href.as_str())).cloned().collect();
+    let changed = listing
+        .iter()
+        .filter(|(href, etag)| etag.is_none() || known.get(href) != Some(etag))
+        .map(|(href, _)| href.clone())
+        .collect();
+
+    download(client, calendar_url, changed, removed).await
+}
+
+async fn sync_calendar(
+    client: &CalDavClient,
+    db: &Database,
+    account: i64,
+    remote: &CalendarInfo,
+    cached: Option<&CachedCalendar>,
+) -> Result<(), NoWorkError> {
+    let mut info = remote.clone();
+    let mut update = None;
+
+    if let Some(cached) = cached {
+        if !cached.enabled {
+            // hidden calendars are not downloaded, they pick up where they
+            // left off once enabled again
+            info.ctag = cached.ctag.clone();
+            info.sync_token = cached.sync_token.clone();
+            update = Some(CalendarUpdate::default());
+        } else if remote.ctag.is_some() && remote.ctag == cached.ctag {
+            // nothing inside changed, only the name or color might have
+            info.sync_token = cached.sync_token.clone();
+            update = Some(CalendarUpdate::default());
+        } else if let Some(token) = &cached.sync_token {
+            match incremental_sync(client, &remote.url, token).await {
+                Ok((changes, token)) => {
+                    info.sync_token = Some(token);
+                    update = Some(changes);
+                }
+                Err(NoWorkError::AuthFailed) => return Err(NoWorkError::AuthFailed),
+                // usually an expired token, fall back to comparing ETags
+                Err(e) => log::info!("Incremental sync of {} failed, listing it instead: {}", remote.url, e),
+            }
+        }
+    }
+
+    let update = match update {
+        Some(update) => update,
+        None => full_sync(client, db, &remote.url, cached.map(|c| c.id)).await?,
+    };
+
+    db.run(move |conn| store_calendar(conn, account, &info, update)).await
+}
+
+async fn discover_home(client: &CalDavClient, db: &Database, account: i64) -> Result<Url, NoWorkError> {
+    let principal = client.principal().await?;
+    let home = client.calendar_home(&principal).await?;
+
+    let home_url = home.to_string();
+    db.run(move |conn| save_home(conn, account, &home_url)).await?;
+    Ok(home)
+}
+
+// Brings the cache of one account up to date with the server. Calendars that
+// fail to sync keep their previous contents and are retried next time.
+pub async fn sync_account(db: &Database, account: i64) -> Result<Vec<CalendarInfo>, NoWorkError> {
+    let client = connect(db, account).await?;
+
+    // discovery costs two requests, the home is remembered between syncs
+    let home = db.run(move |conn| cached_home(conn, account)).await?;
+    let remote = match home.and_then(|home| Url::parse(&home).ok()) {
+        Some(home) => match client.calendars(&home).await {
+            Ok(calendars) => calendars,
+            Err(NoWorkError::AuthFailed) => return Err(NoWorkError::AuthFailed),
+            // the home may have moved, look it up again
+            Err(_) => {
+                let home = discover_home(&client, db, account).await?;
+                client.calendars(&home).await?
+            }
+        },
+        None => {
+            let home = discover_home(&client, db, account).await?;
+            client.calendars(&home).await?
+        }
+    };
+
+    let cached = db.run(move |conn| cached_calendars(conn, account)).await?;
+
+    let results = join_all(
+        remote
+            .iter()
+            .map(|calendar| sync_calendar(&client, db, account, calendar, cached.get(&calendar.url))),
+    )
+    .await;
+
+    for (calendar, result) in remote.iter().zip(results) {
+        match result {
+            Ok(()) => {}
+            Err(NoWorkError::AuthFailed) => return Err(NoWorkError::AuthFailed),
+            // one broken calendar should not hide the others
+            Err(e) => log::warn!("Failed to sync calendar {}: {}", calendar.url, e),
+        }
+    }
+
+    let remote_urls: HashSet<String> = remote.into_iter().map(|c| c.url).collect();
+    db.run(move |conn| {
+        remove_missing_calendars(conn, account, &remote_urls)?;
+        list_cached_calendars(conn, account)
+    })
+    .await
+}
+
+// Events of the enabled cached calendars overlapping [start, end), of one
+// account or of all of them
+pub fn cached_events(
+    conn: &Connection,
+    account: Option<i64>,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<CalendarEvent>, NoWorkError> {
+    let mut stmt = conn.prepare(
+        "SELECT calendar_objects.href, calendar_objects.data, calendars.color FROM calendar_objects
+         JOIN calendars ON calendars.id = calendar_objects.calendar_id
+         WHERE calendars.enabled AND (?1 IS NULL OR calendars.account = ?1)",
+    )?;
+    let mut rows = stmt.query(params![account])?;
+
+    let mut events = Vec::new();
+    while let Some(row) = rows.next()? {
+        let href: String = row.get(0)?;
+        let data: String = row.get(1)?;
+        let color: Option<String> = row.get(2)?;
+        events.extend(parse_events(&data, start, end).into_iter().map(|event| CalendarEvent {
+            href: href.clone(),
+            color: color.clone(),
+            ..event
+        }));
+    }
+    Ok(events)
+}
+
+// Refreshes the cache from the server, for one account or for all of them.
+// With several accounts one being unreachable is only logged, it fails when
+// none could be synced. Subscriptions are refreshed with every account when
+// due, and all at once when the local account is asked for.
+#[tauri::command]
+pub async fn sync_calendars(db: State<'_, Database>, account: Option<i64>) -> Result<Vec<CalendarInfo>, NoWorkError> {
+    match account {
+        Some(LOCAL_ACCOUNT) => {
+            refresh_subscriptions(&db, true).await?;
+            return db.run(|conn| list_cached_calendars(conn, LOCAL_ACCOUNT)).await;
+        }
+        Some(account) => return sync_account(&db, account).await,
+        None => refresh_subscriptions(&db, false).await?,
+    }
+
+    let accounts = db.run(|conn| list_accounts(conn)).await?;
+    let mut calendars = Vec::new();
+    let mut synced_any = accounts.is_empty();
+    let mut last_error = None;
+    for account in &accounts {
+        match sync_account(&db, account.id).await {
+            Ok(synced) => {
+                calendars.extend(synced);
+                synced_any = true;
+            }
+            Err(e) => {
+                log::warn!("Failed to sync calendar account {}: {}", account.username, e);
+                last_error = Some(e);
+            }
+        }
+    }
+
+    match last_error {
+        Some(e) if !synced_any => Err(e),
+        _ => Ok(calendars),
+    }
+}
+
+// Reads from the cache only, so it answers instantly and offline.
+// Recurring events are expanded across the whole range.
+#[tauri::command]
+pub async fn fetch_events(
+    db: State<'_, Database>,
+    account: Option<i64>,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<Vec<CalendarEvent>, NoWorkError> {
+    if end <= start {
+        return Err(NoWorkError::validation("The end of the range must be after its start"));
+    }
+
+    db.run(move |conn| cached_events(conn, account, start, end)).await
+}
+
+#[tauri::command]
+pub async fn list_calendars(db: State<'_, Database>, account: i64) -> Result<Vec<CalendarInfo>, NoWorkError> {
+    db.run(move |conn| list_cached_calendars(conn, account)).await
+}
@@ -1,29 +1,47 @@
-use keyring_core::{Entry};
+use keyring_core::{Entry, Error};
+
+use crate::error::NoWorkError;
+
+// Passwords used to be kept under this service name keyed by username alone.
+// They move to the account's own entry the first time they are read.
+const LEGACY_SERVICE: &str = "my-app";
 
 pub fn init_keyring() {
     keyring::use_native_store(false).expect("Failed to initialize keyring");
 }
 
-#[tauri::command]
-pub fn save_credentials(email: &str, password: &str) -> Result<(), String> {
-    let entry = Entry::new("my-app", email)
-        .map_err(|e| e.to_string())?;
-    entry.set_password(password)
-        .map_err(|e| e.to_string())
+// One entry per account, the same username on two servers has two passwords
+fn entry(server_url: &str, username: &str) -> Result<Entry, NoWorkError> {
+    Ok(Entry::new(&format!("NoWork CalDAV {}", server_url), username)?)
 }
 
-#[tauri::command]
-pub fn load_credentials(email: &str) -> Result<String, String> {
-    let entry = Entry::new("my-app", email)
-        .map_err(|e| e.to_string())?;
-    entry.get_password()
-        .map_err(|e| e.to_string())
+pub fn save_password(server_url: &str, username: &str, password: &str) -> Result<(), NoWorkError> {
+    entry(server_url, username)?.set_password(password)?;
+    Ok(())
 }
 
-#[tauri::command]
-pub fn delete_credentials(email: &str) -> Result<(), String> {
-    let entry = Entry::new("my-app", email)
-        .map_err(|e| e.to_string())?;
-    entry.delete_credential()
-        .map_err(|e| e.to_string())
-}
\ No newline at end of file
+pub fn load_password(server_url: &str, username: &str) -> Result<String, NoWorkError> {
+    let entry = entry(server_url, username)?;
+    match entry.get_password() {
+        Ok(password) => Ok(password),
+        Err(Error::NoEntry) => {
+            let legacy = Entry::new(LEGACY_SERVICE, username)?;
+            let password = legacy.get_password()?;
+            entry.set_password(&password)?;
+            legacy.delete_credential()?;
+            Ok(password)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+// A password that is already gone counts as deleted
+pub fn delete_password(server_url: &str, username: &str) -> Result<(), NoWorkError> {
+    for entry in [entry(server_url, username)?, Entry::new(LEGACY_SERVICE, username)?] {
+        match entry.delete_credential() {
+            Ok(()) | Err(Error::NoEntry) => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,847 @@
+use reqwest::{header, redirect, Client, Method, StatusCode, Url};
+use roxmltree::{Document, Node};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+use crate::error::NoWorkError;
+
+pub const ICLOUD_URL: &str = "https://caldav.icloud.com";
+
+const DAV: &str = "DAV:";
+const CALDAV: &str = "urn:ietf:params:xml:ns:caldav";
+const CALENDARSERVER: &str = "http://calendarserver.org/ns/";
+const APPLE_ICAL: &str = "http://apple.com/ns/ical/";
+
+const MAX_REDIRECTS: usize = 5;
+// resources requested per calendar-multiget REPORT
+const MULTIGET_BATCH: usize = 100;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+// A calendar collection found under the user's calendar home
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct CalendarInfo {
+    // absolute URL of the collection
+    pub url: String,
+    pub display_name: Option<String>,
+    pub color: Option<String>,
+    // changes whenever anything in the collection changes, if the server supports it
+    pub ctag: Option<String>,
+    // RFC 6578 token for asking what changed since the last sync, if supported
+    pub sync_token: Option<String>,
+    // component types the calendar accepts, e.g. ["VEVENT", "VTODO"]; empty means unrestricted
+    pub components: Vec<String>,
+    // whether the user shows and syncs the calendar, always true for discovered ones
+    pub enabled: bool,
+}
+
+// One iCalendar resource inside a calendar collection
+#[derive(Debug, Clone)]
+pub struct CalendarObject {
+    // absolute URL of the resource
+    pub href: String,
+    pub etag: Option<String>,
+    pub data: String,
+}
+
+// Result of a sync-collection REPORT
+#[derive(Debug, Default)]
+pub struct SyncChanges {
+    pub sync_token: Option<String>,
+    // absolute URLs of resources that were added or modified
+    pub changed: Vec<String>,
+    // absolute URLs of resources that were deleted
+    pub removed: Vec<String>,
+    // the server returned only part of the changes, ask again with the new token
+    pub truncated: bool,
+}
+
+// Outcome of a conditional PUT or DELETE
+#[derive(Debug)]
+pub enum WriteOutcome {
+    // carries the new ETag after a PUT, if the server sent one
+    Done(Option<String>),
+    // the resource changed or appeared on the server since we last saw it
+    Conflict,
+}
+
+// RFC 4791 client for a single account. Works against any server reachable
+// over http(s), discovery starts from the URL the user entered.
+#[derive(Clone)]
+pub struct CalDavClient {
+    http: Client,
+    server: Url,
+    username: String,
+    password: String,
+}
+
+impl CalDavClient {
+    pub fn new(server_url: &str, username: &str, password: &str) -> Result<Self, NoWorkError> {
+        let server_url = server_url.trim();
+        // people usually type "cloud.example.com" rather than a full URL
+        let server_url = if server_url.contains("://") {
+            server_url.to_string()
+        } else {
+            format!("https://{}", server_url)
+        };
+
+        let server = Url::parse(&server_url)
+            .map_err(|e| NoWorkError::validation(format!("Invalid server URL: {}", e)))?;
+        if server.scheme() != "https" && server.scheme() != "http" {
+            return Err(NoWorkError::validation("Server URL must start with https://"));
+        }
+
+        // redirects are followed by hand so PROPFIND/REPORT keep their method and body
+        let http = Client::builder()
+            .redirect(redirect::Policy::none())
+            .timeout(REQUEST_TIMEOUT)
+            .build()?;
+
+        Ok(CalDavClient {
+            http,
+            server,
+            username: username.to_string(),
+            password: password.to_string(),
+        })
+    }
+
+    // The password only goes to the server the account was added with, so
+    // every URL from a response or the cache is checked before it is used.
+    // iCloud keeps each account's calendars on one of its numbered hosts,
+    // e.g. p42-caldav.icloud.com, which are trusted along with caldav.icloud.com.
+    fn on_server(&self, url: Url) -> Result<Url, NoWorkError> {
+        let icloud = self.server.as_str().trim_end_matches('/') == ICLOUD_URL
+            && url.scheme() == "https"
+            && url.port().is_none()
+            && url.host_str().is_some_and(|host| host.ends_with(".icloud.com"));
+        if url.origin() != self.server.origin() && !icloud {
+            return Err(NoWorkError::InvalidResponse(format!(
+                "{} is not on {}, the password is only sent to the account's server",
+                url,
+                self.server.origin().ascii_serialization()
+            )));
+        }
+        Ok(url)
+    }
+
+    fn calendar_url(&self, calendar_url: &str) -> Result<Url, NoWorkError> {
+        let url = Url::parse(calendar_url).map_err(|e| NoWorkError::validation(format!("Invalid calendar URL: {}", e)))?;
+        self.on_server(url)
+    }
+
+    // Hrefs may be absolute URLs, absolute paths or relative paths
+    fn resolve(&self, base: &Url, href: &str) -> Result<Url, NoWorkError> {
+        let url = base
+            .join(href.trim())
+            .map_err(|e| NoWorkError::InvalidResponse(format!("bad href {:?}: {}", href, e)))?;
+        self.on_server(url)
+    }
+
+    // Sends a WebDAV request, following redirects. Returns the URL that finally
+    // answered, which relative hrefs in the body are resolved against.
+    async fn send(&self, method: &str, url: &Url, depth: &str, body: &str) -> Result<(Url, String), NoWorkError> {
+        let method = Method::from_bytes(method.as_bytes()).map_err(|e| NoWorkError::Network(e.to_string()))?;
+        let origin = url.origin();
+        let mut url = self.on_server(url.clone())?;
+
+        for _ in 0..=MAX_REDIRECTS {
+            let response = self
+                .http
+                .request(method.clone(), url.clone())
+                .basic_auth(&self.username, Some(&self.password))
+                .header("Depth", depth)
+                .header(header::CONTENT_TYPE, "application/xml; charset=utf-8")
+                .body(body.to_string())
+                .send()
+                .await?;
+
+            let status = response.status();
+            if status.is_redirection() {
+                let location = response
+                    .headers()
+                    .get(header::LOCATION)
+                    .and_then(|l| l.to_str().ok())
+                    .ok_or_else(|| NoWorkError::InvalidResponse(format!("{} redirect without a location", status)))?;
+                let next = url
+                    .join(location)
+                    .map_err(|e| NoWorkError::InvalidResponse(format!("bad redirect location: {}", e)))?;
+                // the password only goes where the request was first sent, a
+                // redirect elsewhere (another host, port or plain http) must
+                // be entered as the server by the user instead
+                if next.origin() != origin {
+                    return Err(NoWorkError::InvalidResponse(format!(
+                        "server redirected to {}, enter that address as the server if you trust it",
+                        next.origin().ascii_serialization()
+                    )));
+                }
+                url = next;
+                continue;
+            }
+
+            if status == StatusCode::UNAUTHORIZED {
+                return Err(NoWorkError::AuthFailed);
+            }
+            if !status.is_success() {
+                // a failed precondition (e.g. an expired sync token) comes back with a DAV:error body
+                let text = response.text().await.unwrap_or_default();
+                if let Some(condition) = precondition(&text) {
+                    return Err(NoWorkError::InvalidResponse(format!(
+                        "{} {} failed precondition {}",
+                        method, url, condition
+                    )));
+                }
+                if status == StatusCode::FORBIDDEN {
+                    return Err(NoWorkError::AuthFailed);
+                }
+                return Err(NoWorkError::Network(format!("{} {} returned {}", method, url, status)));
+            }
+
+            return Ok((url, response.text().await?));
+        }
+
+        Err(NoWorkError::Network("too many redirects".to_string()))
+    }
+
+    // Finds the principal URL, trying the RFC 6764 well-known URL first and
+    // the URL the user entered after that
+    pub async fn principal(&self) -> Result<Url, NoWorkError> {
+        let body = propfind_body(r#"<d:current-user-principal/>"#);
+        let mut candidates = vec![self.server.join("/.well-known/caldav").map_err(|e| NoWorkError::validation(e.to_string()))?];
+        if self.server.path() == "/" {
+            candidates.push(self.server.clone());
+        } else {
+            // a full DAV URL (e.g. Nextcloud's /remote.php/dav) is more specific, ask it first
+            candidates.insert(0, self.server.clone());
+        }
+
+        let mut last_error = None;
+        for candidate in candidates {
+            match self.send("PROPFIND", &candidate, "0", &body).await {
+                Ok((base, text)) => {
+                    let doc = Document::parse(&text)?;
+                    let href = responses(&doc)
+                        .filter_map(|r| find_prop(r, DAV, "current-user-principal"))
+                        .find_map(href_text);
+                    match href {
+                        Some(href) => return self.resolve(&base, &href),
+                        None => last_error = Some(NoWorkError::InvalidResponse("server did not report a principal".to_string())),
+                    }
+                }
+                Err(NoWorkError::AuthFailed) => return Err(NoWorkError::AuthFailed),
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| NoWorkError::InvalidResponse("no CalDAV service found".to_string())))
+    }
+
+    pub async fn calendar_home(&self, principal: &Url) -> Result<Url, NoWorkError> {
+        let body = propfind_body(r#"<c:calendar-home-set/>"#);
+        let (base, text) = self.send("PROPFIND", principal, "0", &body).await?;
+        let doc = Document::parse(&text)?;
+
+        let href = responses(&doc)
+            .filter_map(|r| find_prop(r, CALDAV, "calendar-home-set"))
+            .find_map(href_text)
+            .ok_or_else(|| NoWorkError::InvalidResponse("server did not report a calendar home".to_string()))?;
+
+        self.resolve(&base, &href)
+    }
+
+    // Lists the calendar collections directly under the calendar home
+    pub async fn calendars(&self, home: &Url) -> Result<Vec<CalendarInfo>, NoWorkError> {
+        let body = propfind_body(
+            r#"<d:resourcetype/>
+               <d:displayname/>
+               <cs:getctag/>
+               <d:sync-token/>
+               <a:calendar-color/>
+               <c:supported-calendar-component-set/>"#,
+        );
+        let (base, text) = self.send("PROPFIND", home, "1", &body).await?;
+        let doc = Document::parse(&text)?;
+
+        let mut calendars = Vec::new();
+        for response in responses(&doc) {
+            let is_calendar = find_prop(response, DAV, "resourcetype")
+                .is_some_and(|rt| rt.children().any(|c| c.has_tag_name((CALDAV, "calendar"))));
+            if !is_calendar {
+                continue;
+            }
+            let Some(href) = href_text(response) else { continue };
+
+            let components = find_prop(response, CALDAV, "supported-calendar-component-set")
+                .map(|set| {
+                    set.children()
+                        .filter(|c| c.has_tag_name((CALDAV, "comp")))
+                        .filter_map(|c| c.attribute("name"))
+                        .map(|name| name.to_uppercase())
+                        .collect()
+                })
+                .unwrap_or_default();
+
+            calendars.push(CalendarInfo {
+                url: self.resolve(&base, &href)?.to_string(),
+                display_name: prop_text(response, DAV, "displayname"),
+                color: prop_text(response, APPLE_ICAL, "calendar-color").and_then(|c| css_color(&c)),
+                ctag: prop_text(response, CALENDARSERVER, "getctag"),
+                sync_token: prop_text(response, DAV, "sync-token"),
+                components,
+                enabled: true,
+            });
+        }
+
+        Ok(calendars)
+    }
+
+    // principal -> calendar-home-set -> calendars
+    pub async fn discover(&self) -> Result<Vec<CalendarInfo>, NoWorkError> {
+        let principal = self.principal().await?;
+        let home = self.calendar_home(&principal).await?;
+        self.calendars(&home).await
+    }
+
+    // Lists every resource in the calendar with its ETag, used when the
+    // server has no sync token or has forgotten ours
+    pub async fn etags(&self, calendar_url: &str) -> Result<Vec<(String, Option<String>)>, NoWorkError> {
+        let url = self.calendar_url(calendar_url)?;
+        let body = propfind_body(r#"<d:resourcetype/><d:getetag/>"#);
+        let (base, text) = self.send("PROPFIND", &url, "1", &body).await?;
+        let doc = Document::parse(&text)?;
+
+        let mut etags = Vec::new();
+        for response in responses(&doc) {
+            // the collection itself and anything nested in it
+            let is_collection = find_prop(response, DAV, "resourcetype")
+                .is_some_and(|rt| rt.children().any(|c| c.has_tag_name((DAV, "collection"))));
+            if is_collection {
+                continue;
+            }
+            let Some(href) = href_text(response) else { continue };
+            let href = self.resolve(&base, &href)?;
+            if href == url {
+                continue;
+            }
+            etags.push((href.to_string(), prop_text(response, DAV, "getetag")));
+        }
+
+        Ok(etags)
+    }
+
+    // RFC 6578 sync-collection REPORT, returns what changed since sync_token
+    pub async fn sync_collection(&self, calendar_url: &str, sync_token: &str) -> Result<SyncChanges, NoWorkError> {
+        let url = self.calendar_url(calendar_url)?;
+        let body = format!(
+            r#"<?xml version="1.0" encoding="utf-8" ?>
+            <d:sync-collection xmlns:d="DAV:">
+                <d:sync-token>{}</d:sync-token>
+                <d:sync-level>1</d:sync-level>
+                <d:prop>
+                    <d:getetag/>
+                </d:prop>
+            </d:sync-collection>"#,
+            escape(sync_token)
+        );
+
+        let (base, text) = self.send("REPORT", &url, "0", &body).await?;
+        let doc = Document::parse(&text)?;
+
+        let mut changes = SyncChanges {
+            sync_token: doc
+                .root_element()
+                .children()
+                .find(|n| n.has_tag_name((DAV, "sync-token")))
+                .and_then(|n| n.text())
+                .map(|t| t.trim().to_string())
+                .filter(|t| !t.is_empty()),
+            ..SyncChanges::default()
+        };
+
+        for response in responses(&doc) {
+            let Some(href) = href_text(response) else { continue };
+            let href = self.resolve(&base, &href)?;
+            match response_status(response) {
+                Some(404) => changes.removed.push(href.to_string()),
+                // 507 on the collection itself marks a truncated result
+                Some(507) => changes.truncated = true,
+                _ if href == url => {}
+                _ => changes.changed.push(href.to_string()),
+            }
+        }
+
+        Ok(changes)
+    }
+
+    // calendar-multiget REPORT, downloads the given resources of one calendar.
+    // Resources that no longer exist are left out of the result.
+    pub async fn multiget(&self, calendar_url: &str, hrefs: &[String]) -> Result<Vec<CalendarObject>, NoWorkError> {
+        let url = self.calendar_url(calendar_url)?;
+        let mut objects = Vec::new();
+
+        for chunk in hrefs.chunks(MULTIGET_BATCH) {
+            let href_elements = chunk
+                .iter()
+                .map(|href| Ok(format!("<d:href>{}</d:href>", escape(self.resolve(&url, href)?.path()))))
+                .collect::<Result<String, NoWorkError>>()?;
+            let body = format!(
+                r#"<?xml version="1.0" encoding="utf-8" ?>
+                <c:calendar-multiget xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">
+                    <d:prop>
+                        <d:getetag/>
+                        <c:calendar-data/>
+                    </d:prop>
+                    {}
+                </c:calendar-multiget>"#,
+                href_elements
+            );
+
+            let (base, text) = self.send("REPORT", &url, "1", &body).await?;
+            if text.trim().is_empty() {
+                continue;
+            }
+            let doc = Document::parse(&text)?;
+
+            for response in responses(&doc) {
+                let Some(href) = href_text(response) else { continue };
+                let Some(data) = prop_text(response, CALDAV, "calendar-data") else { continue };
+                objects.push(CalendarObject {
+                    href: self.resolve(&base, &href)?.to_string(),
+                    etag: prop_text(response, DAV, "getetag"),
+                    data,
+                });
+            }
+        }
+
+        Ok(objects)
+    }
+
+    // Uploads one iCalendar resource. With an ETag the server only takes it if
+    // the resource is unchanged since, without one only if it does not exist yet.
+    pub async fn put(&self, href: &str, data: &str, etag: Option<&str>) -> Result<WriteOutcome, NoWorkError> {
+        let url = self.calendar_url(href)?;
+        let request = self
+            .http
+            .put(url.clone())
+            .basic_auth(&self.username, Some(&self.password))
+            .header(header::CONTENT_TYPE, "text/calendar; charset=utf-8")
+            .body(data.to_string());
+        let request = match etag {
+            Some(etag) => request.header(header::IF_MATCH, etag),
+            None => request.header(header::IF_NONE_MATCH, "*"),
+        };
+
+        let response = request.send().await?;
+        let etag = response
+            .headers()
+            .get(header::ETAG)
+            .and_then(|e| e.to_str().ok())
+            .map(String::from);
+        write_outcome("PUT", &url, response.status(), etag)
+    }
+
+    // Deletes one resource if it is unchanged since etag. One that is already
+    // gone counts as deleted.
+    pub async fn delete(&self, href: &str, etag: Option<&str>) -> Result<WriteOutcome, NoWorkError> {
+        let url = self.calendar_url(href)?;
+        let mut request = self
+            .http
+            .delete(url.clone())
+            .basic_auth(&self.username, Some(&self.password));
+        if let Some(etag) = etag {
+            request = request.header(header::IF_MATCH, etag);
+        }
+
+        let status = request.send().await?.status();
+        if status == StatusCode::NOT_FOUND {
+            return Ok(WriteOutcome::Done(None));
+        }
+        write_outcome("DELETE", &url, status, None)
+    }
+}
+
+fn write_outcome(method: &str, url: &Url, status: StatusCode, etag: Option<String>) -> Result<WriteOutcome, NoWorkError> {
+    match status {
+        StatusCode::PRECONDITION_FAILED => Ok(WriteOutcome::Conflict),
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Err(NoWorkError::AuthFailed),
+        status if status.is_success() => Ok(WriteOutcome::Done(etag)),
+        status => Err(NoWorkError::Network(format!("{} {} returned {}", method, url, status))),
+    }
+}
+
+// calendar-color is #RRGGBB or, from Apple, #RRGGBBAA. The alpha is dropped
+// so the frontend can mix the color with its own opacity.
+pub fn css_color(color: &str) -> Option<String> {
+    let hex = color.trim().strip_prefix('#')?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    match hex.len() {
+        6 | 8 => Some(format!("#{}", hex[..6].to_ascii_uppercase())),
+        _ => None,
+    }
+}
+
+fn propfind_body(props: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="utf-8" ?>
+        <d:propfind xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav"
+                    xmlns:cs="http://calendarserver.org/ns/" xmlns:a="http://apple.com/ns/ical/">
+            <d:prop>{}</d:prop>
+        </d:propfind>"#,
+        props
+    )
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Name of the failed condition in a DAV:error body, e.g. "valid-sync-token"
+fn precondition(text: &str) -> Option<String> {
+    let doc = Document::parse(text).ok()?;
+    let root = doc.root_element();
+    if !root.has_tag_name((DAV, "error")) {
+        return None;
+    }
+    root.children()
+        .find(|n| n.is_element())
+        .map(|n| n.tag_name().name().to_string())
+}
+
+fn responses<'a, 'input>(doc: &'a Document<'input>) -> impl Iterator<Item = Node<'a, 'input>> {
+    doc.root_element()
+        .children()
+        .filter(|n| n.has_tag_name((DAV, "response")))
+}
+
+// Status of a whole response, used for members without properties such as
+// deleted resources in a sync-collection result
+fn response_status(response: Node) -> Option<u16> {
+    response
+        .children()
+        .find(|n| n.has_tag_name((DAV, "status")))
+        .and_then(|n| n.text())
+        .and_then(|status| status.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+}
+
+// The response's own href, or the href nested inside a property
+fn href_text(node: Node) -> Option<String> {
+    node.children()
+        .find(|n| n.has_tag_name((DAV, "href")))
+        .and_then(|n| n.text())
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+}
+
+// Looks a property up in the propstats that came back with a 2xx status
+fn find_prop<'a, 'input>(response: Node<'a, 'input>, namespace: &str, name: &str) -> Option<Node<'a, 'input>> {
+    response
+        .children()
+        .filter(|n| n.has_tag_name((DAV, "propstat")))
+        .filter(|propstat| {
+            propstat
+                .children()
+                .find(|n| n.has_tag_name((DAV, "status")))
+                .and_then(|n| n.text())
+                .is_none_or(|status| status.split_whitespace().nth(1).is_some_and(|code| code.starts_with('2')))
+        })
+        .flat_map(|propstat| propstat.children().filter(|n| n.has_tag_name((DAV, "prop"))))
+        .flat_map(|prop| prop.children())
+        .find(|n| n.has_tag_name((namespace, name)))
+}
+
+fn prop_text(response: Node, namespace: &str, name: &str) -> Option<String> {
+    find_prop(response, namespace, name)
+        .and_then(|n| n.text())
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{any, body_string_contains, header, header_exists, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn multistatus(responses: &str) -> ResponseTemplate {
+        ResponseTemplate::new(207).set_body_string(format!(
+            r#"<?xml version="1.0" encoding="utf-8"?>
+            <d:multistatus xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav"
+                           xmlns:cs="http://calendarserver.org/ns/" xmlns:a="http://apple.com/ns/ical/">
+                {}
+            </d:multistatus>"#,
+            responses
+        ))
+    }
+
+    fn client(server: &MockServer) -> CalDavClient {
+        CalDavClient::new(&server.uri(), "alice", "hunter2").unwrap()
+    }
+
+    #[tokio::test]
+    async fn discovers_calendars_from_the_well_known_url() {
+        let server = MockServer::start().await;
+        Mock::given(method("PROPFIND"))
+            .and(path("/.well-known/caldav"))
+            .and(header("Depth", "0"))
+            .and(header_exists("authorization"))
+            .and(body_string_contains("current-user-principal"))
+            .respond_with(multistatus(
+                r#"<d:response><d:href>/</d:href><d:propstat>
+                    <d:prop><d:current-user-principal><d:href>/principals/alice/</d:href></d:current-user-principal></d:prop>
+                    <d:status>HTTP/1.1 200 OK</d:status>
+                </d:propstat></d:response>"#,
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PROPFIND"))
+            .and(path("/principals/alice/"))
+            .and(body_string_contains("calendar-home-set"))
+            .respond_with(multistatus(
+                r#"<d:response><d:href>/principals/alice/</d:href><d:propstat>
+                    <d:prop><c:calendar-home-set><d:href>/calendars/alice/</d:href></c:calendar-home-set></d:prop>
+                    <d:status>HTTP/1.1 200 OK</d:status>
+                </d:propstat></d:response>"#,
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("PROPFIND"))
+            .and(path("/calendars/alice/"))
+            .and(header("Depth", "1"))
+            .respond_with(multistatus(
+                r##"<d:response><d:href>/calendars/alice/</d:href><d:propstat>
+                    <d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop>
+                    <d:status>HTTP/1.1 200 OK</d:status>
+                </d:propstat></d:response>
+                <d:response><d:href>work/</d:href>
+                    <d:propstat>
+                        <d:prop>
+                            <d:resourcetype><d:collection/><c:calendar/></d:resourcetype>
+                            <d:displayname>Work</d:displayname>
+                            <cs:getctag>ctag-1</cs:getctag>
+                            <d:sync-token>token-1</d:sync-token>
+                            <a:calendar-color>#ff8800cc</a:calendar-color>
+                            <c:supported-calendar-component-set><c:comp name="VEVENT"/><c:comp name="vtodo"/></c:supported-calendar-component-set>
+                        </d:prop>
+                        <d:status>HTTP/1.1 200 OK</d:status>
+                    </d:propstat>
+                </d:response>
+                <d:response><d:href>/calendars/alice/inbox/</d:href><d:propstat>
+                    <d:prop><d:resourcetype><d:collection/><c:schedule-inbox/></d:resourcetype></d:prop>
+                    <d:status>HTTP/1.1 200 OK</d:status>
+                </d:propstat></d:response>
+                <d:response><d:href>/calendars/alice/home/</d:href>
+                    <d:propstat>
+                        <d:prop><d:resourcetype><d:collection/><c:calendar/></d:resourcetype></d:prop>
+                        <d:status>HTTP/1.1 200 OK</d:status>
+                    </d:propstat>
+                    <d:propstat>
+                        <d:prop><d:displayname/><cs:getctag/></d:prop>
+                        <d:status>HTTP/1.1 404 Not Found</d:status>
+                    </d:propstat>
+                </d:response>"##,
+            ))
+            .mount(&server)
+            .await;
+
+        let calendars = client(&server).discover().await.unwrap();
+        assert_eq!(calendars.len(), 2);
+
+        let work = &calendars[0];
+        assert_eq!(work.url, format!("{}/calendars/alice/work/", server.uri()));
+        assert_eq!(work.display_name.as_deref(), Some("Work"));
+        assert_eq!(work.ctag.as_deref(), Some("ctag-1"));
+        assert_eq!(work.sync_token.as_deref(), Some("token-1"));
+        assert_eq!(work.color.as_deref(), Some("#FF8800"));
+        assert_eq!(work.components, ["VEVENT", "VTODO"]);
+
+        let home = &calendars[1];
+        assert_eq!(home.url, format!("{}/calendars/alice/home/", server.uri()));
+        assert_eq!(home.display_name, None);
+        assert!(home.components.is_empty());
+    }
+
+    #[tokio::test]
+    async fn reads_sync_collection_reports() {
+        let server = MockServer::start().await;
+        Mock::given(method("REPORT"))
+            .and(path("/calendars/alice/work/"))
+            .and(body_string_contains("<d:sync-token>old &amp; token</d:sync-token>"))
+            .respond_with(ResponseTemplate::new(207).set_body_string(
+                r#"<?xml version="1.0" encoding="utf-8"?>
+                <d:multistatus xmlns:d="DAV:">
+                    <d:response><d:href>/calendars/alice/work/a.ics</d:href><d:propstat>
+                        <d:prop><d:getetag>"2"</d:getetag></d:prop><d:status>HTTP/1.1 200 OK</d:status>
+                    </d:propstat></d:response>
+                    <d:response><d:href>/calendars/alice/work/b.ics</d:href><d:status>HTTP/1.1 404 Not Found</d:status></d:response>
+                    <d:response><d:href>/calendars/alice/work/</d:href><d:status>HTTP/1.1 507 Insufficient Storage</d:status></d:response>
+                    <d:sync-token> new-token </d:sync-token>
+                </d:multistatus>"#,
+            ))
+            .mount(&server)
+            .await;
+
+        let url = format!("{}/calendars/alice/work/", server.uri());
+        let changes = client(&server).sync_collection(&url, "old & token").await.unwrap();
+        assert_eq!(changes.sync_token.as_deref(), Some("new-token"));
+        assert_eq!(changes.changed, [format!("{}a.ics", url)]);
+        assert_eq!(changes.removed, [format!("{}b.ics", url)]);
+        assert!(changes.truncated);
+    }
+
+    #[tokio::test]
+    async fn reads_multiget_reports() {
+        let server = MockServer::start().await;
+        Mock::given(method("REPORT"))
+            .and(path("/calendars/alice/work/"))
+            .and(body_string_contains("<d:href>/calendars/alice/work/a.ics</d:href>"))
+            .respond_with(multistatus(
+                r#"<d:response><d:href>/calendars/alice/work/a.ics</d:href><d:propstat>
+                    <d:prop>
+                        <d:getetag>"7"</d:getetag>
+                        <c:calendar-data>BEGIN:VCALENDAR&#13;
+END:VCALENDAR</c:calendar-data>
+                    </d:prop>
+                    <d:status>HTTP/1.1 200 OK</d:status>
+                </d:propstat></d:response>
+                <d:response><d:href>/calendars/alice/work/gone.ics</d:href><d:status>HTTP/1.1 404 Not Found</d:status></d:response>"#,
+            ))
+            .mount(&server)
+            .await;
+
+        let url = format!("{}/calendars/alice/work/", server.uri());
+        let hrefs = [format!("{}a.ics", url), format!("{}gone.ics", url)];
+        let objects = client(&server).multiget(&url, &hrefs).await.unwrap();
+        assert_eq!(objects.len(), 1);
+        assert_eq!(objects[0].href, hrefs[0]);
+        assert_eq!(objects[0].etag.as_deref(), Some("\"7\""));
+        assert!(objects[0].data.starts_with("BEGIN:VCALENDAR"));
+    }
+
+    #[tokio::test]
+    async fn follows_redirects_on_the_same_server() {
+        let server = MockServer::start().await;
+        Mock::given(method("PROPFIND"))
+            .and(path("/.well-known/caldav"))
+            .respond_with(ResponseTemplate::new(301).insert_header("Location", "/dav/"))
+            .mount(&server)
+            .await;
+        Mock::given(method("PROPFIND"))
+            .and(path("/dav/"))
+            .and(header_exists("authorization"))
+            .and(body_string_contains("current-user-principal"))
+            .respond_with(multistatus(
+                r#"<d:response><d:href>/dav/</d:href><d:propstat>
+                    <d:prop><d:current-user-principal><d:href>principals/alice/</d:href></d:current-user-principal></d:prop>
+                    <d:status>HTTP/1.1 200 OK</d:status>
+                </d:propstat></d:response>"#,
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let principal = client(&server).principal().await.unwrap();
+        // relative hrefs are resolved against the URL that answered
+        assert_eq!(principal.as_str(), format!("{}/dav/principals/alice/", server.uri()));
+    }
+
+    #[tokio::test]
+    async fn never_sends_the_password_to_another_server() {
+        let server = MockServer::start().await;
+        let elsewhere = MockServer::start().await;
+        Mock::given(method("PROPFIND"))
+            .and(path("/calendars/alice/"))
+            .respond_with(ResponseTemplate::new(302).insert_header("Location", format!("{}/steal", elsewhere.uri())))
+            .mount(&server)
+            .await;
+        Mock::given(method("PROPFIND"))
+            .respond_with(multistatus(""))
+            .expect(0)
+            .mount(&elsewhere)
+            .await;
+
+        let home = Url::parse(&format!("{}/calendars/alice/", server.uri())).unwrap();
+        let error = client(&server).calendars(&home).await.unwrap_err();
+        assert!(matches!(&error, NoWorkError::InvalidResponse(message) if message.contains(&elsewhere.uri())));
+    }
+
+    #[tokio::test]
+    async fn never_follows_hrefs_to_another_server() {
+        let server = MockServer::start().await;
+        let elsewhere = MockServer::start().await;
+        let stolen = format!("{}/steal/a.ics", elsewhere.uri());
+        Mock::given(method("PROPFIND"))
+            .and(path("/calendars/alice/work/"))
+            .respond_with(multistatus(&format!(
+                r#"<d:response>
+                    <d:href>{}</d:href>
+                    <d:propstat>
+                        <d:prop><d:resourcetype/><d:getetag>"1"</d:getetag></d:prop>
+                        <d:status>HTTP/1.1 200 OK</d:status>
+                    </d:propstat>
+                </d:response>"#,
+                stolen
+            )))
+            .mount(&server)
+            .await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&elsewhere)
+            .await;
+
+        let client = client(&server);
+        let calendar = format!("{}/calendars/alice/work/", server.uri());
+        let refused = |result: Result<_, NoWorkError>| {
+            matches!(result, Err(NoWorkError::InvalidResponse(message)) if message.contains(&stolen))
+        };
+        assert!(refused(client.etags(&calendar).await.map(|_| ())));
+        assert!(refused(client.put(&stolen, "BEGIN:VCALENDAR", Some("\"1\"")).await.map(|_| ())));
+        assert!(refused(client.delete(&stolen, Some("\"1\"")).await.map(|_| ())));
+        assert!(refused(client.multiget(&calendar, std::slice::from_ref(&stolen)).await.map(|_| ())));
+        assert!(elsewhere.received_requests().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn rejected_credentials_are_auth_failures() {
+        let server = MockServer::start().await;
+        Mock::given(method("PROPFIND"))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&server)
+            .await;
+
+        let client = client(&server);
+        assert!(matches!(client.discover().await, Err(NoWorkError::AuthFailed)));
+        let href = format!("{}/calendars/alice/work/a.ics", server.uri());
+        assert!(matches!(client.put(&href, "BEGIN:VCALENDAR", None).await, Err(NoWorkError::AuthFailed)));
+    }
+
+    #[tokio::test]
+    async fn conditional_writes_report_conflicts() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(header("If-Match", "\"1\""))
+            .respond_with(ResponseTemplate::new(412))
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(header("If-None-Match", "*"))
+            .respond_with(ResponseTemplate::new(201).insert_header("ETag", "\"2\""))
+            .mount(&server)
+            .await;
+
+        let client = client(&server);
+        let href = format!("{}/calendars/alice/work/a.ics", server.uri());
+        assert!(matches!(client.put(&href, "data", Some("\"1\"")).await, Ok(WriteOutcome::Conflict)));
+        assert!(matches!(client.put(&href, "data", None).await, Ok(WriteOutcome::Done(Some(etag))) if etag == "\"2\""));
+    }
+}
@@ -0,0 +1,610 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, Utc};
+use ical::IcalParser;
+use icalendar::{Alarm, Calendar, CalendarComponent, CalendarDateTime, Component, Event, EventLike, Property, Trigger};
+use rrule::{RRule, Tz, Unvalidated};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::io::BufReader;
+use std::str::FromStr;
+use tauri::State;
+use uuid::Uuid;
+
+use crate::calendar::{
+    accounts::connect,
+    caldav::WriteOutcome,
+    ical_time::{find_prop, iana, param, parse_duration, parse_time, vtimezone, Zone, Zones},
+    todo_sync::{resource_url, unescape_text, PRODID},
+};
+use crate::commands::database::Database;
+use crate::error::NoWorkError;
+
+// An event as the editor sees it. start and end are written like those of
+// CalendarEvent: RFC 3339 date-times, or plain dates for all-day events
+// whose end is the day after the last one.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EventDraft {
+    pub summary: String,
+    pub start: String,
+    pub end: String,
+    #[serde(default)]
+    pub all_day: bool,
+    // IANA zone a timed event is kept in, so a weekly 9:00 meeting stays at
+    // 9:00 across DST changes. Without one the times are written in UTC.
+    pub time_zone: Option<String>,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    // RRULE value, e.g. "FREQ=WEEKLY;BYDAY=MO,WE"
+    pub recurrence: Option<String>,
+    // reminders, in minutes before the start
+    #[serde(default)]
+    pub alarms: Vec<u32>,
+}
+
+// When an event happens, read from a draft
+#[derive(Debug, PartialEq)]
+enum Times {
+    // first day and the day after the last
+    Dates(NaiveDate, NaiveDate),
+    Instants(DateTime<Utc>, DateTime<Utc>, Option<chrono_tz::Tz>),
+}
+
+// The cached copy of the resource an event is stored in
+struct StoredEvent {
+    calendar_id: i64,
+    etag: Option<String>,
+    data: String,
+}
+
+fn times(draft: &EventDraft) -> Result<Times, NoWorkError> {
+    let times = if draft.all_day {
+        let date = |value: &str| {
+            NaiveDate::parse_from_str(value.trim(), "%Y-%m-%d")
+                .map_err(|_| NoWorkError::validation(format!("Invalid date: {}", value)))
+        };
+        Times::Dates(date(&draft.start)?, date(&draft.end)?)
+    } else {
+        let instant = |value: &str| {
+            DateTime::parse_from_rfc3339(value.trim())
+                .map(|dt| dt.with_timezone(&Utc))
+                .map_err(|_| NoWorkError::validation(format!("Invalid date and time: {}", value)))
+        };
+        let time_zone = match draft.time_zone.as_deref() {
+            Some(name) => Some(
+                name.parse::<chrono_tz::Tz>()
+                    .map_err(|_| NoWorkError::validation(format!("Unknown time zone: {}", name)))?,
+            ),
+            None => None,
+        };
+        Times::Instants(instant(&draft.start)?, instant(&draft.end)?, time_zone)
+    };
+
+    let ordered = match &times {
+        Times::Dates(start, end) => end > start,
+        Times::Instants(start, end, _) => end > start,
+    };
+    if !ordered {
+        return Err(NoWorkError::validation("An event has to end after it starts"));
+    }
+    Ok(times)
+}
+
+// Trims the draft and checks it can be written
+fn normalize(mut draft: EventDraft) -> Result<(EventDraft, Times), NoWorkError> {
+    draft.summary = draft.summary.trim().to_string();
+    if draft.summary.is_empty() {
+        return Err(NoWorkError::validation("An event needs a title"));
+    }
+    let optional = |text: Option<String>| text.map(|t| t.trim().to_string()).filter(|t| !t.is_empty());
+    draft.description = optional(draft.description);
+    draft.location = optional(draft.location);
+    draft.alarms.sort_unstable();
+    draft.alarms.dedup();
+    let times = times(&draft)?;
+
+    // checked against the event's own start, an UNTIL before it is an error
+    if let Some(rule) = optional(draft.recurrence.take()) {
+        let rule = rule.to_uppercase();
+        let rule = rule.strip_prefix("RRULE:").unwrap_or(&rule).to_string();
+        let start = match times {
+            Times::Dates(start, _) => start.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc(),
+            Times::Instants(start, _, _) => start,
+        };
+        rule.parse::<RRule<Unvalidated>>()?.build(start.with_timezone(&Tz::UTC))?;
+        draft.recurrence = Some(rule);
+    }
+
+    Ok((draft, times))
+}
+
+// Minutes before the start an alarm goes off at. None for alarms the editor
+// does not show, which are kept as they are: absolute times and ones
+// relative to the end or after the start.
+fn alarm_minutes(trigger: &str, related: Option<&str>, value: Option<&str>) -> Option<u32> {
+    if related.is_some_and(|r| r.eq_ignore_ascii_case("END")) || value.is_some_and(|v| v.eq_ignore_ascii_case("DATE-TIME")) {
+        return None;
+    }
+    let offset = parse_duration(trigger)?;
+    (offset <= Duration::zero()).then(|| (-offset).num_minutes() as u32)
+}
+
+// Reads the master VEVENT of a resource into a draft
+fn read_event(data: &str) -> Option<EventDraft> {
+    let calendar = IcalParser::new(BufReader::new(data.as_bytes())).flatten().next()?;
+    let zones = Zones::new(&calendar.timezones);
+    let event = calendar
+        .events
+        .iter()
+        .find(|event| find_prop(&event.properties, "RECURRENCE-ID").is_none())?;
+    let props = &event.properties;
+
+    let value = |name| find_prop(props, name).and_then(|p| p.value.as_deref());
+    let text = |name| value(name).map(|v| unescape_text(v, false).concat()).filter(|v| !v.is_empty());
+
+    let start = find_prop(props, "DTSTART").and_then(parse_time)?;
+    let end = find_prop(props, "DTEND").and_then(parse_time);
+    let length = value("DURATION").and_then(parse_duration);
+
+    let (start_text, end_text) = if start.is_date {
+        let end = match (end, length) {
+            (Some(end), _) => end.wall,
+            (None, Some(length)) => start.wall + length,
+            (None, None) => start.wall + Duration::days(1),
+        };
+        (start.wall.date().to_string(), end.date().to_string())
+    } else {
+        let end = match (end, length) {
+            (Some(end), _) => zones.resolve(end.wall, &end.zone),
+            (None, length) => zones.resolve(start.wall + length.unwrap_or_else(Duration::zero), &start.zone),
+        };
+        (zones.resolve(start.wall, &start.zone).to_rfc3339(), end.to_rfc3339())
+    };
+
+    let time_zone = match &start.zone {
+        Zone::Named(tzid) if !start.is_date => iana(tzid).map(|tz| tz.name().to_string()),
+        _ => None,
+    };
+
+    let mut alarms: Vec<u32> = event
+        .alarms
+        .iter()
+        .filter_map(|alarm| {
+            let trigger = find_prop(&alarm.properties, "TRIGGER")?;
+            alarm_minutes(trigger.value.as_deref()?, param(trigger, "RELATED"), param(trigger, "VALUE"))
+        })
+        .collect();
+    alarms.sort_unstable();
+    alarms.dedup();
+
+    Some(EventDraft {
+        summary: text("SUMMARY").unwrap_or_default(),
+        start: start_text,
+        end: end_text,
+        all_day: start.is_date,
+        time_zone,
+        description: text("DESCRIPTION"),
+        location: text("LOCATION"),
+        recurrence: value("RRULE").map(String::from),
+        alarms,
+    })
+}
+
+fn write_times(event: &mut Event, times: &Times) {
+    event.remove_starts().remove_ends().remove_property("DURATION");
+    match *times {
+        Times::Dates(start, end) => event.starts(start).ends(end),
+        Times::Instants(start, end, Some(tz)) => {
+            let wall = |instant: DateTime<Utc>| CalendarDateTime::WithTimezone {
+                date_time: instant.with_timezone(&tz).naive_local(),
+                tzid: tz.name().to_string(),
+            };
+            event.starts(wall(start)).ends(wall(end))
+        }
+        Times::Instants(start, end, None) => event.starts(start).ends(end),
+    };
+}
+
+fn write_alarms(event: &mut Event, draft: &EventDraft) {
+    for minutes in &draft.alarms {
+        let mut alarm = Alarm::display(&draft.summary, Trigger::before_start(Duration::minutes(i64::from(*minutes))));
+        // icalendar would write the offset in seconds
+        alarm.append_property(
+            Property::new("TRIGGER", format!("-PT{}M", minutes))
+                .add_parameter("RELATED", "START")
+                .done(),
+        );
+        event.alarm(alarm);
+    }
+}
+
+// Writes a draft into a VEVENT, skipping the parts that match previous
+fn write_fields(event: &mut Event, draft: &EventDraft, times: &Times, previous: Option<(&EventDraft, &Times)>) {
+    let now = Utc::now();
+    event.timestamp(now).last_modified(now);
+
+    if previous.is_none_or(|(p, _)| p.summary != draft.summary) {
+        event.summary(&draft.summary);
+    }
+    if previous.is_none_or(|(p, _)| p.description != draft.description) {
+        match &draft.description {
+            Some(description) => event.description(description),
+            None => event.remove_description(),
+        };
+    }
+    if previous.is_none_or(|(p, _)| p.location != draft.location) {
+        match &draft.location {
+            Some(location) => event.location(location),
+            None => event.remove_location(),
+        };
+    }
+    if previous.is_none_or(|(_, t)| t != times) {
+        write_times(event, times);
+    }
+    if previous.is_none_or(|(p, _)| p.recurrence != draft.recurrence) {
+        event.remove_property("RRULE");
+        if let Some(rule) = &draft.recurrence {
+            event.append_property(("RRULE", rule.as_str()));
+        }
+    }
+}
+
+// Serializes the calendar, adding a VTIMEZONE for the zone the times are
+// written in unless it has one already. Spliced into the text because
+// icalendar gives every component it writes a UID and DTSTAMP.
+fn finish(calendar: &Calendar, times: &Times) -> String {
+    let text = calendar.to_string();
+    let Times::Instants(start, _, Some(tz)) = times else { return text };
+
+    let defined = calendar.components.iter().any(|component| match component {
+        CalendarComponent::Other(other) => {
+            other.component_kind() == "VTIMEZONE" && other.property_value("TZID") == Some(tz.name())
+        }
+        _ => false,
+    });
+    if defined {
+        return text;
+    }
+    let definition = vtimezone(*tz, start.with_timezone(tz).year());
+    text.replacen("BEGIN:VEVENT\r\n", &format!("{}BEGIN:VEVENT\r\n", definition), 1)
+}
+
+fn new_resource(uid: &str, draft: &EventDraft, times: &Times) -> String {
+    let mut event = Event::with_uid(uid);
+    event.created(Utc::now());
+    write_fields(&mut event, draft, times, None);
+    write_alarms(&mut event, draft);
+
+    let mut calendar = Calendar::empty();
+    calendar
+        .append_property(("VERSION", "2.0"))
+        .append_property(("PRODID", PRODID))
+        .push(event);
+    finish(&calendar, times)
+}
+
+// Applies the edits on top of the stored resource, so attendees, other
+// alarms and anything else NoWork does not edit survive. A changed start or
+// rule drops the exceptions and moved instances, which would no longer line
+// up with the new occurrences.
+fn updated_resource(data: &str, draft: &EventDraft, times: &Times, previous: &EventDraft) -> Result<String, NoWorkError> {
+    let unreadable = || NoWorkError::InvalidResponse("the stored event could not be read".to_string());
+    let previous_times = self::times(previous)?;
+    let mut calendar = Calendar::from_str(data).map_err(|_| unreadable())?;
+
+    let rescheduled = previous_times != *times || previous.recurrence != draft.recurrence;
+    if rescheduled {
+        calendar.components.retain(|component| match component {
+            CalendarComponent::Event(event) => event.get_recurrence_id().is_none(),
+            _ => true,
+        });
+    }
+
+    let master = calendar
+        .components
+        .iter_mut()
+        .find_map(|component| match component {
+            CalendarComponent::Event(event) if event.get_recurrence_id().is_none() => Some(event),
+            _ => None,
+        })
+        .ok_or_else(unreadable)?;
+
+    // rebuilt because icalendar cannot remove a child component
+    let alarms_changed = previous.alarms != draft.alarms;
+    let mut event = Event::new();
+    for property in master.properties().values() {
+        event.append_property(property.clone());
+    }
+    for property in master.multi_properties().values().flatten() {
+        event.append_multi_property(property.clone());
+    }
+    for child in master.components() {
+        let is_editor_alarm = child.component_kind() == "VALARM"
+            && child.properties().get("TRIGGER").is_some_and(|trigger| {
+                let param = |name: &str| trigger.params().get(name).map(|p| p.value());
+                alarm_minutes(trigger.value(), param("RELATED"), param("VALUE")).is_some()
+            });
+        if !(alarms_changed && is_editor_alarm) {
+            event.append_component(child.clone());
+        }
+    }
+
+    write_fields(&mut event, draft, times, Some((previous, &previous_times)));
+    if alarms_changed {
+        write_alarms(&mut event, draft);
+    }
+    if rescheduled {
+        event.remove_multi_property("EXDATE").remove_multi_property("RDATE");
+    }
+    event.sequence(master.get_sequence().unwrap_or(0) + 1);
+
+    *master = event;
+    Ok(finish(&calendar, times))
+}
+
+// The calendar's id in the cache, if it can hold events
+fn event_calendar(conn: &Connection, account: i64, calendar_url: &str) -> Result<i64, NoWorkError> {
+    let calendar = conn
+        .query_row(
+            "SELECT id, components FROM calendars WHERE account = ?1 AND url = ?2",
+            params![account, calendar_url],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+        )
+        .optional()?;
+    let Some((id, components)) = calendar else {
+        return Err(NoWorkError::validation("Sync the account before adding events to it"));
+    };
+    // an empty set means the calendar takes any component
+    if !components.is_empty() && !components.split(',').any(|c| c == "VEVENT") {
+        return Err(NoWorkError::validation("This calendar cannot hold events"));
+    }
+    Ok(id)
+}
+
+fn stored_event(conn: &Connection, account: i64, href: &str) -> Result<StoredEvent, NoWorkError> {
+    conn.query_row(
+        "SELECT calendar_objects.calendar_id, calendar_objects.etag, calendar_objects.data FROM calendar_objects
+         JOIN calendars ON calendars.id = calendar_objects.calendar_id
+         WHERE calendars.account = ?1 AND calendar_objects.href = ?2",
+        params![account, href],
+        |row| {
+            Ok(StoredEvent {
+                calendar_id: row.get(0)?,
+                etag: row.get(1)?,
+                data: row.get(2)?,
+            })
+        },
+    )
+    .optional()?
+    .ok_or(NoWorkError::EventNotFound)
+}
+
+// Writes what was uploaded into the cache, so the change shows up before the next sync
+fn store_event(conn: &Connection, calendar_id: i64, href: &str, etag: Option<String>, data: &str) -> Result<(), NoWorkError> {
+    conn.execute(
+        "INSERT INTO calendar_objects (calendar_id, href, etag, data) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(calendar_id, href) DO UPDATE SET etag = excluded.etag, data = excluded.data",
+        params![calendar_id, href, etag, data],
+    )?;
+    Ok(())
+}
+
+// Adds an event to one of the account's calendars and returns its href
+#[tauri::command]
+pub async fn create_event(
+    db: State<'_, Database>,
+    account: i64,
+    calendar_url: String,
+    event: EventDraft,
+) -> Result<String, NoWorkError> {
+    let (draft, times) = normalize(event)?;
+    let url = calendar_url.clone();
+    let calendar_id = db.run(move |conn| event_calendar(conn, account, &url)).await?;
+
+    let uid = Uuid::new_v4().to_string();
+    let href = resource_url(&calendar_url, &uid);
+    let data = new_resource(&uid, &draft, &times);
+
+    let client = connect(&db, account).await?;
+    match client.put(&href, &data, None).await? {
+        WriteOutcome::Done(etag) => {
+            let stored = href.clone();
+            db.run(move |conn| store_event(conn, calendar_id, &stored, etag, &data)).await?;
+            Ok(href)
+        }
+        WriteOutcome::Conflict => Err(NoWorkError::EditConflict),
+    }
+}
+
+// The event stored at href as of the last sync, ready for the editor
+#[tauri::command]
+pub async fn get_event(db: State<'_, Database>, account: i64, href: String) -> Result<EventDraft, NoWorkError> {
+    let stored = db.run(move |conn| stored_event(conn, account, &href)).await?;
+    read_event(&stored.data).ok_or(NoWorkError::EventNotFound)
+}
+
+// Replaces the event at href. Fails with an edit conflict if it changed on
+// the server since the last sync. Recurring events are edited as a whole.
+#[tauri::command]
+pub async fn update_event(
+    db: State<'_, Database>,
+    account: i64,
+    href: String,
+    event: EventDraft,
+) -> Result<(), NoWorkError> {
+    let (draft, times) = normalize(event)?;
+    let target = href.clone();
+    let stored = db.run(move |conn| stored_event(conn, account, &target)).await?;
+    let previous = read_event(&stored.data).ok_or(NoWorkError::EventNotFound)?;
+    let data = updated_resource(&stored.data, &draft, &times, &previous)?;
+
+    let client = connect(&db, account).await?;
+    match client.put(&href, &data, stored.etag.as_deref()).await? {
+        WriteOutcome::Done(etag) => {
+            db.run(move |conn| store_event(conn, stored.calendar_id, &href, etag, &data)).await
+        }
+        WriteOutcome::Conflict => Err(NoWorkError::EditConflict),
+    }
+}
+
+// Deletes the event at href with all of its occurrences, unless it changed
+// on the server since the last sync
+#[tauri::command]
+pub async fn delete_event(
+    db: State<'_, Database>,
+    account: i64,
+    href: String,
+) -> Result<(), NoWorkError> {
+    let target = href.clone();
+    let stored = db.run(move |conn| stored_event(conn, account, &target)).await?;
+
+    let client = connect(&db, account).await?;
+    match client.delete(&href, stored.etag.as_deref()).await? {
+        WriteOutcome::Done(_) => {
+            db.run(move |conn| {
+                conn.execute(
+                    "DELETE FROM calendar_objects WHERE calendar_id = ?1 AND href = ?2",
+                    params![stored.calendar_id, href],
+                )?;
+                Ok(())
+            })
+            .await
+        }
+        WriteOutcome::Conflict => Err(NoWorkError::EditConflict),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a weekly event with an attendee, a reminder the editor shows, one it
+    // does not (at a fixed time), a skipped week and a moved occurrence
+    const STANDUP: &str = "BEGIN:VCALENDAR\r\n\
+        VERSION:2.0\r\n\
+        PRODID:-//Test//EN\r\n\
+        BEGIN:VEVENT\r\n\
+        UID:standup\r\n\
+        DTSTAMP:20260101T000000Z\r\n\
+        DTSTART:20260105T090000Z\r\n\
+        DTEND:20260105T093000Z\r\n\
+        SUMMARY:Standup\r\n\
+        RRULE:FREQ=WEEKLY\r\n\
+        EXDATE:20260112T090000Z\r\n\
+        ATTENDEE;CN=Bob:mailto:bob@example.com\r\n\
+        BEGIN:VALARM\r\n\
+        ACTION:DISPLAY\r\n\
+        DESCRIPTION:Standup\r\n\
+        TRIGGER;RELATED=START:-PT15M\r\n\
+        END:VALARM\r\n\
+        BEGIN:VALARM\r\n\
+        ACTION:AUDIO\r\n\
+        TRIGGER;VALUE=DATE-TIME:20260105T080000Z\r\n\
+        END:VALARM\r\n\
+        END:VEVENT\r\n\
+        BEGIN:VEVENT\r\n\
+        UID:standup\r\n\
+        DTSTAMP:20260101T000000Z\r\n\
+        RECURRENCE-ID:20260119T090000Z\r\n\
+        DTSTART:20260119T100000Z\r\n\
+        DTEND:20260119T103000Z\r\n\
+        SUMMARY:Moved standup\r\n\
+        END:VEVENT\r\n\
+        END:VCALENDAR\r\n";
+
+    fn edit(change: impl FnOnce(&mut EventDraft)) -> String {
+        let previous = read_event(STANDUP).unwrap();
+        let mut draft = previous.clone();
+        change(&mut draft);
+        let (draft, times) = normalize(draft).unwrap();
+        updated_resource(STANDUP, &draft, &times, &previous).unwrap()
+    }
+
+    #[test]
+    fn reads_the_master_event() {
+        let draft = read_event(STANDUP).unwrap();
+        assert_eq!(draft.summary, "Standup");
+        assert_eq!(draft.start, "2026-01-05T09:00:00+00:00");
+        assert_eq!(draft.recurrence.as_deref(), Some("FREQ=WEEKLY"));
+        // the alarm at a fixed time is not the editor's
+        assert_eq!(draft.alarms, [15]);
+    }
+
+    #[test]
+    fn editing_the_title_keeps_everything_else() {
+        let data = edit(|draft| draft.summary = "Daily sync".to_string());
+
+        assert!(data.contains("SUMMARY:Daily sync"));
+        assert!(data.contains("ATTENDEE;CN=Bob:mailto:bob@example.com"));
+        assert!(data.contains("TRIGGER;RELATED=START:-PT15M"));
+        assert!(data.contains("TRIGGER;VALUE=DATE-TIME:20260105T080000Z"));
+        assert!(data.contains("EXDATE:20260112T090000Z"));
+        assert!(data.contains("RECURRENCE-ID:20260119T090000Z"));
+        assert!(data.contains("SUMMARY:Moved standup"));
+        assert!(data.contains("SEQUENCE:1"));
+        assert_eq!(read_event(&data).unwrap().start, "2026-01-05T09:00:00+00:00");
+    }
+
+    #[test]
+    fn moving_the_start_drops_exceptions_and_moved_occurrences() {
+        let data = edit(|draft| {
+            draft.start = "2026-01-05T10:00:00Z".to_string();
+            draft.end = "2026-01-05T10:30:00Z".to_string();
+        });
+
+        assert!(data.contains("ATTENDEE;CN=Bob:mailto:bob@example.com"));
+        assert!(data.contains("TRIGGER;RELATED=START:-PT15M"));
+        assert!(data.contains("TRIGGER;VALUE=DATE-TIME:20260105T080000Z"));
+        assert!(data.contains("RRULE:FREQ=WEEKLY"));
+        assert!(!data.contains("EXDATE"));
+        assert!(!data.contains("RECURRENCE-ID"));
+        assert!(!data.contains("Moved standup"));
+        assert_eq!(read_event(&data).unwrap().start, "2026-01-05T10:00:00+00:00");
+    }
+
+    #[test]
+    fn changing_reminders_keeps_the_ones_the_editor_does_not_show() {
+        let data = edit(|draft| draft.alarms = vec![30, 5]);
+
+        assert!(!data.contains("-PT15M"));
+        assert!(data.contains("TRIGGER;RELATED=START:-PT5M"));
+        assert!(data.contains("TRIGGER;RELATED=START:-PT30M"));
+        assert!(data.contains("TRIGGER;VALUE=DATE-TIME:20260105T080000Z"));
+        assert!(data.contains("RECURRENCE-ID:20260119T090000Z"));
+        assert_eq!(read_event(&data).unwrap().alarms, [5, 30]);
+    }
+
+    #[test]
+    fn normalize_trims_and_checks_the_draft() {
+        let draft = EventDraft {
+            summary: "  Lunch ".to_string(),
+            start: "2026-01-05T12:00:00Z".to_string(),
+            end: "2026-01-05T13:00:00Z".to_string(),
+            all_day: false,
+            time_zone: None,
+            description: Some("   ".to_string()),
+            location: Some(" Canteen ".to_string()),
+            recurrence: Some("rrule:freq=daily".to_string()),
+            alarms: vec![10, 0, 10],
+        };
+        let (normalized, _) = normalize(draft.clone()).unwrap();
+        assert_eq!(normalized.summary, "Lunch");
+        assert_eq!(normalized.description, None);
+        assert_eq!(normalized.location.as_deref(), Some("Canteen"));
+        assert_eq!(normalized.recurrence.as_deref(), Some("FREQ=DAILY"));
+        assert_eq!(normalized.alarms, [0, 10]);
+
+        let invalid = |change: fn(&mut EventDraft)| {
+            let mut draft = draft.clone();
+            change(&mut draft);
+            matches!(normalize(draft), Err(NoWorkError::Validation(_)))
+        };
+        assert!(invalid(|d| d.summary = " ".to_string()));
+        assert!(invalid(|d| d.end = d.start.clone()));
+        assert!(invalid(|d| d.time_zone = Some("Mars/Olympus".to_string())));
+        let rule = Some("FREQ=SOMETIMES".to_string());
+        assert!(matches!(
+            normalize(EventDraft { recurrence: rule, ..draft }),
+            Err(NoWorkError::InvalidRecurrence(_))
+        ));
+    }
+}
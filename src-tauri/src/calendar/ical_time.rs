@@ -0,0 +1,408 @@
+use chrono::{DateTime, Datelike, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::{OffsetComponents, OffsetName};
+use ical::parser::ical::component::IcalTimeZone;
+use ical::property::Property;
+use rrule::{RRuleError, RRuleSet, Tz};
+use std::collections::HashMap;
+
+const DATE_TIME_FORMAT: &str = "%Y%m%dT%H%M%S";
+// VTIMEZONE rules are expanded up to this year
+const LAST_TRANSITION_YEAR: i32 = 2100;
+
+// How the wall-clock time of a value maps onto the timeline
+#[derive(Debug, Clone, PartialEq)]
+pub enum Zone {
+    Utc,
+    // no TZID and no trailing Z, the time wherever the user is
+    Floating,
+    // TZID, an IANA name or one defined by a VTIMEZONE in the same calendar
+    Named(String),
+}
+
+// A DTSTART, DTEND, RECURRENCE-ID, RDATE or EXDATE value
+#[derive(Debug, Clone, PartialEq)]
+pub struct IcalTime {
+    pub wall: NaiveDateTime,
+    pub zone: Zone,
+    // VALUE=DATE, wall is midnight of the day and the zone is floating
+    pub is_date: bool,
+}
+
+pub fn find_prop<'a>(props: &'a [Property], name: &str) -> Option<&'a Property> {
+    props.iter().find(|p| p.name == name)
+}
+
+pub fn param<'a>(prop: &'a Property, name: &str) -> Option<&'a str> {
+    prop.params
+        .as_ref()?
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .and_then(|(_, vs)| vs.first())
+        .map(|v| v.as_str())
+}
+
+pub fn parse_time_value(value: &str, tzid: Option<&str>) -> Option<IcalTime> {
+    let value = value.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y%m%d") {
+        return Some(IcalTime {
+            wall: date.and_hms_opt(0, 0, 0)?,
+            zone: Zone::Floating,
+            is_date: true,
+        });
+    }
+
+    let is_utc = value.ends_with(['Z', 'z']);
+    let wall = NaiveDateTime::parse_from_str(value.trim_end_matches(['Z', 'z']), DATE_TIME_FORMAT).ok()?;
+    let zone = match (is_utc, tzid) {
+        (true, _) => Zone::Utc,
+        (false, Some(tzid)) => Zone::Named(tzid.trim_matches('"').to_string()),
+        (false, None) => Zone::Floating,
+    };
+    Some(IcalTime { wall, zone, is_date: false })
+}
+
+// Every value of a property, RDATE and EXDATE may list several. Periods
+// ("start/end") count from their start.
+pub fn parse_times(prop: &Property) -> Vec<IcalTime> {
+    let Some(value) = prop.value.as_deref() else { return Vec::new() };
+    let tzid = param(prop, "TZID");
+    value
+        .split(',')
+        .filter_map(|v| v.split('/').next())
+        .filter_map(|v| parse_time_value(v, tzid))
+        .collect()
+}
+
+pub fn parse_time(prop: &Property) -> Option<IcalTime> {
+    parse_times(prop).into_iter().next()
+}
+
+// RFC 5545 DURATION, e.g. "PT1H30M", "P1D" or "-P1W"
+pub fn parse_duration(value: &str) -> Option<Duration> {
+    let value = value.trim();
+    let (sign, rest) = match value.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let rest = rest.strip_prefix('P')?;
+
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    let mut in_time = false;
+    let mut any = false;
+    for c in rest.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' if number.is_empty() && !in_time => in_time = true,
+            'W' | 'D' | 'H' | 'M' | 'S' => {
+                let n: i64 = number.parse().ok()?;
+                number.clear();
+                total += match (c, in_time) {
+                    ('W', false) => Duration::weeks(n),
+                    ('D', false) => Duration::days(n),
+                    ('H', true) => Duration::hours(n),
+                    ('M', true) => Duration::minutes(n),
+                    ('S', true) => Duration::seconds(n),
+                    _ => return None,
+                };
+                any = true;
+            }
+            _ => return None,
+        }
+    }
+
+    (any && number.is_empty()).then_some(total * sign)
+}
+
+// UTC offset as written in TZOFFSETTO, e.g. "+0100" or "-053000"
+fn parse_offset(value: &str) -> Option<FixedOffset> {
+    let value = value.trim();
+    let (sign, digits) = match value.as_bytes().first()? {
+        b'+' => (1, &value[1..]),
+        b'-' => (-1, &value[1..]),
+        _ => (1, value),
+    };
+    if digits.len() != 4 && digits.len() != 6 {
+        return None;
+    }
+    let field = |range: std::ops::Range<usize>| digits.get(range).and_then(|d| d.parse::<i32>().ok());
+    let seconds = field(0..2)? * 3600 + field(2..4)? * 60 + field(4..6).unwrap_or(0);
+    FixedOffset::east_opt(sign * seconds)
+}
+
+// The inverse of parse_offset
+fn format_offset(offset: FixedOffset) -> String {
+    let seconds = offset.local_minus_utc();
+    let sign = if seconds < 0 { '-' } else { '+' };
+    let seconds = seconds.abs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if seconds == 0 {
+        format!("{}{:02}{:02}", sign, hours, minutes)
+    } else {
+        format!("{}{:02}{:02}{:02}", sign, hours, minutes, seconds)
+    }
+}
+
+// VTIMEZONE for an IANA zone, sent along with events NoWork writes in it.
+// The transitions of the given year are restated as yearly rules ("last
+// Sunday of March"), the way Apple and Google describe zones.
+pub fn vtimezone(tz: chrono_tz::Tz, year: i32) -> String {
+    let offset_at = |instant: DateTime<Utc>| tz.offset_from_utc_datetime(&instant.naive_utc());
+    let mut lines = vec!["BEGIN:VTIMEZONE".to_string(), format!("TZID:{}", tz.name())];
+    let mut observance = |kind: &str, onset: NaiveDateTime, rule: Option<String>, from: FixedOffset, to: <chrono_tz::Tz as TimeZone>::Offset| {
+        lines.push(format!("BEGIN:{}", kind));
+        lines.push(format!("DTSTART:{}", onset.format(DATE_TIME_FORMAT)));
+        lines.extend(rule);
+        lines.push(format!("TZOFFSETFROM:{}", format_offset(from)));
+        lines.push(format!("TZOFFSETTO:{}", format_offset(to.fix())));
+        lines.push(format!("TZNAME:{}", to.abbreviation()));
+        lines.push(format!("END:{}", kind));
+    };
+
+    let Some(noon) = NaiveDate::from_ymd_opt(year, 1, 1).and_then(|d| d.and_hms_opt(12, 0, 0)) else {
+        return String::new();
+    };
+    let mut day = noon.and_utc();
+    let mut transitions = 0;
+    while day.year() == year {
+        let next = day + Duration::days(1);
+        let (before, after) = (offset_at(day), offset_at(next));
+        if before.fix() != after.fix() {
+            // narrow the day down to the second the clocks change at
+            let (mut old, mut new) = (day, next);
+            while new - old > Duration::seconds(1) {
+                let middle = old + (new - old) / 2;
+                if offset_at(middle).fix() == before.fix() {
+                    old = middle;
+                } else {
+                    new = middle;
+                }
+            }
+            let onset = new.naive_utc() + Duration::seconds(before.fix().local_minus_utc().into());
+            // -1 for the last such weekday of the month
+            let ordinal = if (onset.date() + Duration::days(7)).month() != onset.month() {
+                -1
+            } else {
+                (onset.day() as i32 - 1) / 7 + 1
+            };
+            let weekday = onset.weekday().to_string()[..2].to_uppercase();
+            let rule = format!("RRULE:FREQ=YEARLY;BYMONTH={};BYDAY={}{}", onset.month(), ordinal, weekday);
+            let kind = if after.dst_offset().is_zero() { "STANDARD" } else { "DAYLIGHT" };
+            observance(kind, onset, Some(rule), before.fix(), after);
+            transitions += 1;
+        }
+        day = next;
+    }
+
+    // a zone without daylight saving time
+    if transitions == 0 {
+        let offset = offset_at(noon.and_utc());
+        let epoch = NaiveDate::from_ymd_opt(1970, 1, 1).and_then(|d| d.and_hms_opt(0, 0, 0)).unwrap_or(noon);
+        observance("STANDARD", epoch, None, offset.fix(), offset);
+    }
+
+    lines.push("END:VTIMEZONE".to_string());
+    lines.join("\r\n") + "\r\n"
+}
+
+// IANA zone for a TZID, also found at the end of prefixed ids such as
+// "/mozilla.org/20050126_1/Europe/Berlin"
+pub fn iana(tzid: &str) -> Option<chrono_tz::Tz> {
+    let tzid = tzid.trim().trim_matches('"');
+    std::iter::once(tzid)
+        .chain(tzid.match_indices('/').map(|(i, _)| &tzid[i + 1..]))
+        .find_map(|id| id.parse().ok())
+}
+
+fn from_local<T: TimeZone>(tz: &T, wall: NaiveDateTime) -> DateTime<FixedOffset> {
+    tz.from_local_datetime(&wall)
+        .earliest()
+        // wall times skipped by a DST change are moved past the gap
+        .or_else(|| tz.from_local_datetime(&(wall + Duration::hours(1))).earliest())
+        .map(|dt| dt.fixed_offset())
+        .unwrap_or_else(|| wall.and_utc().fixed_offset())
+}
+
+fn as_utc(wall: NaiveDateTime) -> DateTime<Tz> {
+    Tz::UTC.from_utc_datetime(&wall)
+}
+
+// Time zones a calendar can refer to. IANA names are looked up in the tz
+// database, anything else (Outlook's "W. Europe Standard Time", custom
+// zones) comes from the VTIMEZONE definitions sent along with the events.
+pub struct Zones {
+    // transition onsets in local time, sorted, with the offset in effect from each
+    custom: HashMap<String, Vec<(NaiveDateTime, FixedOffset)>>,
+}
+
+impl Zones {
+    pub fn new(timezones: &[IcalTimeZone]) -> Self {
+        let mut custom = HashMap::new();
+
+        for timezone in timezones {
+            let Some(tzid) = find_prop(&timezone.properties, "TZID").and_then(|p| p.value.clone()) else {
+                continue;
+            };
+            if iana(&tzid).is_some() {
+                continue;
+            }
+
+            let mut onsets = Vec::new();
+            for transition in &timezone.transitions {
+                let props = &transition.properties;
+                let Some(offset) = find_prop(props, "TZOFFSETTO")
+                    .and_then(|p| p.value.as_deref())
+                    .and_then(parse_offset)
+                else {
+                    continue;
+                };
+                let Some(start) = find_prop(props, "DTSTART").and_then(parse_time) else { continue };
+
+                onsets.push((start.wall, offset));
+                for prop in props.iter().filter(|p| p.name == "RDATE") {
+                    onsets.extend(parse_times(prop).into_iter().map(|t| (t.wall, offset)));
+                }
+                for rule in props.iter().filter(|p| p.name == "RRULE").filter_map(|p| p.value.as_deref()) {
+                    onsets.extend(transition_onsets(start.wall, rule).into_iter().map(|wall| (wall, offset)));
+                }
+            }
+
+            onsets.sort_by_key(|(wall, _)| *wall);
+            custom.insert(tzid, onsets);
+        }
+
+        Zones { custom }
+    }
+
+    fn custom_offset(&self, tzid: &str, wall: NaiveDateTime) -> Option<FixedOffset> {
+        let onsets = self.custom.get(tzid)?;
+        // before the first transition the earliest known offset is the best guess
+        let index = onsets.partition_point(|(onset, _)| *onset <= wall).saturating_sub(1);
+        onsets.get(index).map(|(_, offset)| *offset)
+    }
+
+    // The instant a wall-clock time in zone refers to
+    pub fn resolve(&self, wall: NaiveDateTime, zone: &Zone) -> DateTime<FixedOffset> {
+        match zone {
+            Zone::Utc => wall.and_utc().fixed_offset(),
+            Zone::Floating => from_local(&Local, wall),
+            Zone::Named(tzid) => {
+                if let Some(tz) = iana(tzid) {
+                    from_local(&tz, wall)
+                } else if let Some(offset) = self.custom_offset(tzid, wall) {
+                    from_local(&offset, wall)
+                } else {
+                    // an undefined zone, the user's own is the best guess
+                    from_local(&Local, wall)
+                }
+            }
+        }
+    }
+
+    // The wall-clock time in zone at an instant, the inverse of resolve
+    pub fn wall_clock(&self, instant: DateTime<Utc>, zone: &Zone) -> NaiveDateTime {
+        match zone {
+            Zone::Utc => instant.naive_utc(),
+            Zone::Floating => instant.with_timezone(&Local).naive_local(),
+            Zone::Named(tzid) => {
+                if let Some(tz) = iana(tzid) {
+                    instant.with_timezone(&tz).naive_local()
+                } else if let Some(offset) = self.custom_offset(tzid, instant.naive_utc()) {
+                    instant.with_timezone(&offset).naive_local()
+                } else {
+                    instant.with_timezone(&Local).naive_local()
+                }
+            }
+        }
+    }
+
+    // time's wall clock as seen in another zone, dates stay the same day
+    fn wall_in(&self, time: &IcalTime, zone: &Zone) -> NaiveDateTime {
+        if time.is_date || time.zone == *zone {
+            return time.wall;
+        }
+        let instant = self.resolve(time.wall, &time.zone).with_timezone(&Utc);
+        self.wall_clock(instant, zone)
+    }
+}
+
+// Onsets of a yearly VTIMEZONE rule in local time
+fn transition_onsets(start: NaiveDateTime, rule: &str) -> Vec<NaiveDateTime> {
+    let Some(last) = NaiveDate::from_ymd_opt(LAST_TRANSITION_YEAR, 1, 1).and_then(|d| d.and_hms_opt(0, 0, 0)) else {
+        return Vec::new();
+    };
+    format!("DTSTART:{}Z\nRRULE:{}", start.format(DATE_TIME_FORMAT), rule)
+        .parse::<RRuleSet>()
+        .map(|set| set.limit().before(as_utc(last)).all_unchecked())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|dt| dt.naive_utc())
+        .collect()
+}
+
+// Restates UNTIL as a wall-clock time in the event's zone, the form the
+// expansion below works in
+fn rewrite_until(rule: &str, start: &IcalTime, zones: &Zones) -> String {
+    rule.split(';')
+        .map(|part| match part.split_once('=') {
+            Some((key, value)) if key.eq_ignore_ascii_case("UNTIL") => {
+                let Some(until) = parse_time_value(value, None) else { return part.to_string() };
+                let wall = if until.is_date && !start.is_date {
+                    // a date bound on timed events includes the whole day
+                    until.wall.date().and_hms_opt(23, 59, 59).unwrap_or(until.wall)
+                } else {
+                    zones.wall_in(&until, &start.zone)
+                };
+                format!("UNTIL={}Z", wall.format(DATE_TIME_FORMAT))
+            }
+            _ => part.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(";")
+}
+
+// Occurrences of a recurring event between from and until, as wall-clock
+// times in the zone of its DTSTART. The expansion runs on wall-clock times
+// held as UTC so the rrule crate does no time zone arithmetic of its own:
+// a 9:00 meeting stays at 9:00 across DST changes, and zones that only
+// exist as a VTIMEZONE work too. Callers map the results back with resolve.
+pub fn expand(
+    start: &IcalTime,
+    rules: &[&str],
+    rdates: &[IcalTime],
+    exdates: &[IcalTime],
+    zones: &Zones,
+    from: NaiveDateTime,
+    until: NaiveDateTime,
+) -> Result<Vec<NaiveDateTime>, RRuleError> {
+    let dtstart = as_utc(start.wall);
+
+    let mut set = if rules.is_empty() {
+        // DTSTART is always the first instance
+        RRuleSet::new(dtstart).rdate(dtstart)
+    } else {
+        let lines: Vec<String> = rules
+            .iter()
+            .map(|rule| format!("RRULE:{}", rewrite_until(rule, start, zones)))
+            .collect();
+        format!("DTSTART:{}Z\n{}", start.wall.format(DATE_TIME_FORMAT), lines.join("\n")).parse::<RRuleSet>()?
+    };
+
+    for rdate in rdates {
+        set = set.rdate(as_utc(zones.wall_in(rdate, &start.zone)));
+    }
+    for exdate in exdates {
+        set = set.exdate(as_utc(zones.wall_in(exdate, &start.zone)));
+    }
+
+    // bounded by the range instead of a count, limit() keeps the iteration
+    // guard for rules that never produce a date
+    Ok(set
+        .limit()
+        .after(as_utc(from))
+        .before(as_utc(until))
+        .all_unchecked()
+        .into_iter()
+        .map(|dt| dt.naive_utc())
+        .collect())
+}
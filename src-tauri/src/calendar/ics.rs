@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+use std::io::BufReader;
+
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use ical::{IcalParser, property::Property};
+use serde::{Deserialize, Serialize};
+
+use crate::calendar::ical_time::{expand, find_prop, parse_duration, parse_time, parse_times, IcalTime, Zones};
+use crate::error::NoWorkError;
+
+// start and end are RFC 3339 date-times, or plain dates ("2026-01-19") for
+// all-day events, whose end is the day after the last one
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct CalendarEvent {
+    pub summary: String,
+    pub start: String,
+    pub end: String,
+    pub uid: String,
+    pub description: Option<String>,
+    pub location: Option<String>,
+    #[serde(default)]
+    pub all_day: bool,
+    // the resource the event is stored in, what it is edited and deleted by
+    #[serde(default)]
+    pub href: String,
+    // color of the calendar the event is in
+    #[serde(default)]
+    pub color: Option<String>,
+}
+
+fn get_prop( props: &[Property], name: &str ) -> Option<String> { 
+    props.iter().find(|p| p.name == name).and_then(|p| p.value.clone()) 
+}
+
+// Events of every calendar in an iCalendar text overlapping the range, with
+// recurring ones expanded. Shared by CalDAV calendars, subscriptions and imports.
+pub fn parse_events(calendar_data: &str, range_start: DateTime<Utc>, range_end: DateTime<Utc>) -> Vec<CalendarEvent> {
+    let reader = BufReader::new(calendar_data.as_bytes());
+    let parser = IcalParser::new(reader);
+    let mut events = Vec::new();
+
+    for calendar in parser.flatten() {
+        let zones = Zones::new(&calendar.timezones);
+
+        // Instances of a recurring event that were moved or edited are sent as
+        // separate VEVENTs with the same UID and a RECURRENCE-ID naming the
+        // occurrence they replace
+        let mut overridden: HashMap<String, Vec<IcalTime>> = HashMap::new();
+        for event in &calendar.events {
+            let props = &event.properties;
+            if let (Some(uid), Some(recurrence_id)) = (
+                get_prop(props, "UID"),
+                find_prop(props, "RECURRENCE-ID").and_then(parse_time),
+            ) {
+                overridden.entry(uid).or_default().push(recurrence_id);
+            }
+        }
+
+        for event in calendar.events {
+            let props = &event.properties;
+
+            // a cancelled override removes its occurrence and shows nothing in its place
+            if get_prop(props, "STATUS").is_some_and(|s| s.eq_ignore_ascii_case("CANCELLED")) {
+                continue;
+            }
+
+            let summary     = get_prop(props, "SUMMARY").unwrap_or_default();
+            let uid         = get_prop(props, "UID").unwrap_or_default();
+            let description = get_prop(props, "DESCRIPTION");
+            let location    = get_prop(props, "LOCATION");
+
+            let Some(start) = find_prop(props, "DTSTART").and_then(parse_time) else { continue };
+
+            // DTEND, else DURATION, else a day for dates and no length at all
+            // for date-times (RFC 5545 3.6.1)
+            let length = match find_prop(props, "DTEND").and_then(parse_time) {
+                Some(end) if end.zone == start.zone || end.is_date => end.wall - start.wall,
+                // e.g. a flight that lands in another time zone
+                Some(end) => zones.resolve(end.wall, &end.zone) - zones.resolve(start.wall, &start.zone),
+                None => match get_prop(props, "DURATION").as_deref().and_then(parse_duration) {
+                    Some(duration) => duration,
+                    None if start.is_date => Duration::days(1),
+                    None => Duration::zero(),
+                },
+            };
+
+            // One occurrence starting at the given wall-clock time, if it
+            // overlaps the range. Lengths are added on the wall clock so a
+            // one day event stays one day long across DST changes.
+            let occurrence = |wall: NaiveDateTime| {
+                let starts_at = zones.resolve(wall, &start.zone);
+                let ends_at = zones.resolve(wall + length, &start.zone);
+                let in_range = starts_at < range_end && (ends_at > range_start || starts_at >= range_start);
+                in_range.then(|| {
+                    let (start_text, end_text) = if start.is_date {
+                        (wall.date().to_string(), (wall + length).date().to_string())
+                    } else {
+                        (starts_at.to_rfc3339(), ends_at.to_rfc3339())
+                    };
+                    CalendarEvent {
+                        summary:     summary.clone(),
+                        uid:         uid.clone(),
+                        description: description.clone(),
+                        location:    location.clone(),
+                        start:       start_text,
+                        end:         end_text,
+                        all_day:     start.is_date,
+                        href:        String::new(),
+                        color:       None,
+                    }
+                })
+            };
+
+            // an override is a single instance even if it repeats its master's RRULE
+            let is_override = find_prop(props, "RECURRENCE-ID").is_some();
+            let rules: Vec<&str> = props
+                .iter()
+                .filter(|p| p.name == "RRULE" && !is_override)
+                .filter_map(|p| p.value.as_deref())
+                .collect();
+            let dates_of = |name: &str| -> Vec<IcalTime> {
+                props
+                    .iter()
+                    .filter(|p| p.name == name && !is_override)
+                    .flat_map(parse_times)
+                    .collect()
+            };
+            let rdates = dates_of("RDATE");
+
+            if rules.is_empty() && rdates.is_empty() {
+                events.extend(occurrence(start.wall));
+                continue;
+            }
+
+            // ── Recurring event ──────────────────────────────────────────
+            // overridden occurrences are shown from their own VEVENT instead
+            let mut exdates = dates_of("EXDATE");
+            exdates.extend(overridden.get(&uid).into_iter().flatten().cloned());
+
+            // a day of slack on both sides covers any offset between the
+            // event's zone and UTC, occurrence() does the exact check
+            let from = range_start.naive_utc() - length - Duration::days(1);
+            let until = range_end.naive_utc() + Duration::days(1);
+
+            match expand(&start, &rules, &rdates, &exdates, &zones, from, until) {
+                Ok(walls) => events.extend(walls.into_iter().filter_map(occurrence)),
+                // Fallback: treat as a single event if the rule cannot be read
+                Err(e) => {
+                    log::warn!("Showing {} once, its recurrence could not be expanded: {}", uid, NoWorkError::from(e));
+                    events.extend(occurrence(start.wall));
+                }
+            }
+        }
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ICLOUD: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/icloud_weekly_exceptions.ics"));
+    const GOOGLE: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/google_daily_rdate.ics"));
+    const OUTLOOK: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/outlook_custom_timezone.ics"));
+    const ALL_DAY: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/all_day_and_duration.ics"));
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    // (start in UTC, summary) of the timed events, sorted by start
+    fn expand(data: &str, start: &str, end: &str) -> Vec<(DateTime<Utc>, String)> {
+        let mut events: Vec<_> = parse_events(data, utc(start), utc(end))
+            .into_iter()
+            .filter(|e| !e.all_day)
+            .map(|e| (utc(&e.start), e.summary))
+            .collect();
+        events.sort();
+        events
+    }
+
+    #[test]
+    fn icloud_exdate_and_moved_instance() {
+        let events = expand(ICLOUD, "2026-03-01T00:00:00Z", "2026-04-01T00:00:00Z");
+        assert_eq!(
+            events,
+            vec![
+                (utc("2026-03-03T09:00:00Z"), "Team sync".to_string()),
+                (utc("2026-03-10T09:00:00Z"), "Team sync".to_string()),
+                // the 17th is excluded, the 24th moved to the 25th
+                (utc("2026-03-25T13:00:00Z"), "Team sync (moved)".to_string()),
+                // summer time started on the 29th
+                (utc("2026-03-31T08:00:00Z"), "Team sync".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn icloud_until_ends_the_series() {
+        let events = expand(ICLOUD, "2026-04-01T00:00:00Z", "2026-06-01T00:00:00Z");
+        assert_eq!(events.len(), 4);
+        assert_eq!(events.last().unwrap().0, utc("2026-04-28T08:00:00Z"));
+    }
+
+    #[test]
+    fn google_exdate_rdate_and_overrides() {
+        let events = expand(GOOGLE, "2026-03-01T00:00:00Z", "2026-03-09T00:00:00Z");
+        assert_eq!(
+            events,
+            vec![
+                (utc("2026-03-02T14:00:00Z"), "Standup".to_string()),
+                (utc("2026-03-03T14:00:00Z"), "Standup".to_string()),
+                (utc("2026-03-03T20:00:00Z"), "Dentist".to_string()),
+                // the 4th is an EXDATE and the 5th a cancelled override
+                (utc("2026-03-06T15:00:00Z"), "Standup (late)".to_string()),
+                // extra RDATE instance keeps the series' duration
+                (utc("2026-03-07T17:00:00Z"), "Standup".to_string()),
+            ]
+        );
+
+        let rdate = parse_events(GOOGLE, utc("2026-03-07T00:00:00Z"), utc("2026-03-08T00:00:00Z"));
+        assert_eq!(rdate.len(), 1);
+        assert_eq!(utc(&rdate[0].end) - utc(&rdate[0].start), Duration::minutes(30));
+    }
+
+    #[test]
+    fn override_outside_the_range_is_not_shown() {
+        let events = expand(GOOGLE, "2026-03-06T16:00:00Z", "2026-03-07T00:00:00Z");
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn vtimezone_defines_unknown_tzid() {
+        let events = expand(OUTLOOK, "2026-03-20T00:00:00Z", "2026-04-10T00:00:00Z");
+        let planning: Vec<_> = events.iter().filter(|(_, summary)| summary == "Planning").map(|(start, _)| *start).collect();
+        assert_eq!(
+            planning,
+            vec![
+                utc("2026-03-23T08:00:00Z"),
+                // the VTIMEZONE switches to summer time on the 29th
+                utc("2026-03-30T07:00:00Z"),
+                utc("2026-04-06T07:00:00Z"),
+            ]
+        );
+    }
+
+    // all-day events carry plain dates, so only their text is compared
+    fn by_summary<'a>(events: &'a [CalendarEvent], summary: &str) -> Vec<&'a CalendarEvent> {
+        events.iter().filter(|e| e.summary == summary).collect()
+    }
+
+    #[test]
+    fn all_day_events() {
+        let outlook = parse_events(OUTLOOK, utc("2026-03-20T00:00:00Z"), utc("2026-04-10T00:00:00Z"));
+        let offsite = by_summary(&outlook, "Offsite");
+        assert_eq!(offsite.len(), 1);
+        assert!(offsite[0].all_day);
+        assert_eq!((offsite[0].start.as_str(), offsite[0].end.as_str()), ("2026-04-01", "2026-04-03"));
+
+        let events = parse_events(ALL_DAY, utc("2026-03-01T00:00:00Z"), utc("2026-04-01T00:00:00Z"));
+
+        // no DTEND or DURATION on a date means one day
+        let birthday = by_summary(&events, "Sam's birthday");
+        assert_eq!(birthday.len(), 1);
+        assert_eq!((birthday[0].start.as_str(), birthday[0].end.as_str()), ("2026-03-15", "2026-03-16"));
+
+        let trip = by_summary(&events, "Trip");
+        assert_eq!((trip[0].start.as_str(), trip[0].end.as_str()), ("2026-03-20", "2026-03-23"));
+
+        let mut bins: Vec<_> = by_summary(&events, "Bins out").iter().map(|e| e.start.clone()).collect();
+        bins.sort();
+        assert_eq!(bins, vec!["2026-03-02", "2026-03-16", "2026-03-23"]);
+        assert!(by_summary(&events, "Bins out").iter().all(|e| e.all_day));
+    }
+
+    #[test]
+    fn duration_and_floating_times() {
+        let events = parse_events(ALL_DAY, utc("2026-03-01T00:00:00Z"), utc("2026-04-01T00:00:00Z"));
+
+        let review = by_summary(&events, "Code review");
+        assert_eq!(review.len(), 1);
+        assert!(!review[0].all_day);
+        assert_eq!(utc(&review[0].end), utc("2026-03-10T13:45:00Z"));
+
+        // floating times are read in the user's own zone, whatever that is here
+        let gym = by_summary(&events, "Gym");
+        assert_eq!(gym.len(), 1);
+        assert_eq!(utc(&gym[0].end) - utc(&gym[0].start), Duration::hours(1));
+    }
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("PT1H30M"), Some(Duration::minutes(90)));
+        assert_eq!(parse_duration("P1W"), Some(Duration::weeks(1)));
+        assert_eq!(parse_duration("P1DT12H"), Some(Duration::hours(36)));
+        assert_eq!(parse_duration("-PT15M"), Some(Duration::minutes(-15)));
+        assert_eq!(parse_duration("P"), None);
+        assert_eq!(parse_duration("PT5"), None);
+        assert_eq!(parse_duration("1H"), None);
+    }
+}
@@ -1,2 +1,10 @@
-pub mod authtest;
-pub mod cal_credentials;
\ No newline at end of file
+pub mod accounts;
+pub mod cache;
+pub mod cal_credentials;
+pub mod caldav;
+pub mod events;
+pub mod ical_time;
+pub mod ics;
+pub mod subscriptions;
+pub mod task_export;
+pub mod todo_sync;
@@ -0,0 +1,286 @@
+use std::io::BufReader;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use ical::IcalParser;
+use reqwest::{header, Client, StatusCode, Url};
+use rusqlite::{params, Connection, OptionalExtension};
+use tauri::State;
+use uuid::Uuid;
+
+use crate::calendar::{
+    cache::{list_cached_calendars, store_calendar, CalendarUpdate},
+    caldav::{css_color, CalendarInfo, CalendarObject},
+};
+use crate::commands::database::Database;
+use crate::error::NoWorkError;
+
+// id of the account subscribed feeds and imported files belong to
+pub const LOCAL_ACCOUNT: i64 = 0;
+
+const DEFAULT_REFRESH_MINUTES: u32 = 6 * 60;
+const MIN_REFRESH_MINUTES: u32 = 15;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+// A subscribed feed as of its last fetch
+struct Subscription {
+    url: String,
+    display_name: Option<String>,
+    color: Option<String>,
+    etag: Option<String>,
+    refresh_minutes: u32,
+    synced_at: Option<DateTime<Utc>>,
+}
+
+impl Subscription {
+    fn is_due(&self, now: DateTime<Utc>) -> bool {
+        match self.synced_at {
+            Some(synced_at) => now - synced_at >= chrono::Duration::minutes(self.refresh_minutes as i64),
+            None => true,
+        }
+    }
+}
+
+// webcal:// is what most sites link feeds as, it is plain HTTPS underneath
+fn feed_url(url: &str) -> Result<Url, NoWorkError> {
+    let url = url.trim();
+    let url = match url.strip_prefix("webcal://") {
+        Some(rest) => format!("https://{}", rest),
+        None => url.to_string(),
+    };
+    let url = Url::parse(&url).map_err(|e| NoWorkError::validation(format!("Invalid feed URL: {}", e)))?;
+    if url.scheme() != "https" && url.scheme() != "http" {
+        return Err(NoWorkError::validation("Feed URL must start with https:// or webcal://"));
+    }
+    Ok(url)
+}
+
+// Name and color the feed gives itself, also checks it is iCalendar at all
+fn feed_details(data: &str) -> Result<(Option<String>, Option<String>), NoWorkError> {
+    let calendar = IcalParser::new(BufReader::new(data.as_bytes()))
+        .next()
+        .ok_or_else(|| NoWorkError::InvalidResponse("not an iCalendar file".to_string()))?
+        .map_err(|e| NoWorkError::InvalidResponse(format!("not an iCalendar file: {}", e)))?;
+    let prop = |name: &str| {
+        calendar
+            .properties
+            .iter()
+            .find(|p| p.name == name)
+            .and_then(|p| p.value.clone())
+            .filter(|v| !v.trim().is_empty())
+    };
+    Ok((prop("X-WR-CALNAME"), prop("X-APPLE-CALENDAR-COLOR").and_then(|c| css_color(&c))))
+}
+
+// Downloads a feed, None when it has not changed since etag
+async fn download_feed(url: &Url, etag: Option<&str>) -> Result<Option<(String, Option<String>)>, NoWorkError> {
+    let http = Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+    let mut request = http.get(url.clone());
+    if let Some(etag) = etag {
+        request = request.header(header::IF_NONE_MATCH, etag);
+    }
+    let response = request.send().await?;
+
+    let status = response.status();
+    if status == StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+    if !status.is_success() {
+        return Err(NoWorkError::Network(format!("GET {} returned {}", url, status)));
+    }
+    let etag = response
+        .headers()
+        .get(header::ETAG)
+        .and_then(|e| e.to_str().ok())
+        .map(String::from);
+    Ok(Some((response.text().await?, etag)))
+}
+
+// The whole feed or file is kept as a single resource, parse_events reads
+// every VEVENT in it
+fn save_local_calendar(conn: &mut Connection, info: &CalendarInfo, data: String, refresh_minutes: Option<u32>) -> Result<(), NoWorkError> {
+    let object = CalendarObject {
+        href: info.url.clone(),
+        etag: info.ctag.clone(),
+        data,
+    };
+    store_calendar(conn, LOCAL_ACCOUNT, info, CalendarUpdate { upserted: vec![object], removed: Vec::new() })?;
+    conn.execute(
+        "UPDATE calendars SET refresh_minutes = ?1 WHERE account = ?2 AND url = ?3",
+        params![refresh_minutes, LOCAL_ACCOUNT, info.url],
+    )?;
+    Ok(())
+}
+
+fn local_calendar(conn: &Connection, url: &str) -> Result<CalendarInfo, NoWorkError> {
+    list_cached_calendars(conn, LOCAL_ACCOUNT)?
+        .into_iter()
+        .find(|c| c.url == url)
+        .ok_or_else(|| NoWorkError::Storage("the calendar was not saved".to_string()))
+}
+
+fn subscriptions(conn: &Connection) -> Result<Vec<Subscription>, NoWorkError> {
+    let mut stmt = conn.prepare(
+        "SELECT url, display_name, color, ctag, refresh_minutes, synced_at FROM calendars
+         WHERE account = ?1 AND refresh_minutes IS NOT NULL AND enabled",
+    )?;
+    let subscriptions = stmt
+        .query_map(params![LOCAL_ACCOUNT], |row| {
+            let synced_at: Option<String> = row.get(5)?;
+            Ok(Subscription {
+                url: row.get(0)?,
+                display_name: row.get(1)?,
+                color: row.get(2)?,
+                etag: row.get(3)?,
+                refresh_minutes: row.get(4)?,
+                synced_at: synced_at
+                    .and_then(|s| DateTime::parse_from_rfc3339(&s).ok())
+                    .map(|s| s.with_timezone(&Utc)),
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(subscriptions)
+}
+
+async fn refresh(db: &Database, subscription: Subscription) -> Result<(), NoWorkError> {
+    let url = feed_url(&subscription.url)?;
+    let info = CalendarInfo {
+        url: subscription.url,
+        display_name: subscription.display_name,
+        color: subscription.color,
+        ctag: subscription.etag,
+        sync_token: None,
+        components: vec!["VEVENT".to_string()],
+        enabled: true,
+    };
+    let refresh_minutes = Some(subscription.refresh_minutes);
+
+    match download_feed(&url, info.ctag.as_deref()).await? {
+        Some((data, etag)) => {
+            feed_details(&data)?;
+            let info = CalendarInfo { ctag: etag, ..info };
+            db.run(move |conn| save_local_calendar(conn, &info, data, refresh_minutes)).await
+        }
+        // only the time of the last fetch moves
+        None => {
+            db.run(move |conn| store_calendar(conn, LOCAL_ACCOUNT, &info, CalendarUpdate::default()))
+                .await
+        }
+    }
+}
+
+// Fetches the subscriptions whose refresh interval has passed, or all of
+// them with force. A feed that fails keeps its last contents.
+pub async fn refresh_subscriptions(db: &Database, force: bool) -> Result<(), NoWorkError> {
+    let now = Utc::now();
+    let due: Vec<_> = db
+        .run(|conn| subscriptions(conn))
+        .await?
+        .into_iter()
+        .filter(|s| force || s.is_due(now))
+        .collect();
+
+    for subscription in due {
+        let url = subscription.url.clone();
+        if let Err(e) = refresh(db, subscription).await {
+            log::warn!("Failed to refresh subscription {}: {}", url, e);
+        }
+    }
+    Ok(())
+}
+
+// Subscribes to a read-only ICS feed, refreshed every refresh_minutes
+// (6 hours if not given) whenever the calendars are synced
+#[tauri::command]
+pub async fn subscribe_calendar(
+    db: State<'_, Database>,
+    url: String,
+    refresh_minutes: Option<u32>,
+) -> Result<CalendarInfo, NoWorkError> {
+    let refresh_minutes = refresh_minutes.unwrap_or(DEFAULT_REFRESH_MINUTES);
+    if refresh_minutes < MIN_REFRESH_MINUTES {
+        return Err(NoWorkError::validation(format!(
+            "Feeds can be refreshed at most every {} minutes",
+            MIN_REFRESH_MINUTES
+        )));
+    }
+    let url = feed_url(&url)?;
+
+    let stored = url.to_string();
+    let exists = db
+        .run(move |conn| {
+            Ok(conn
+                .query_row(
+                    "SELECT 1 FROM calendars WHERE account = ?1 AND url = ?2",
+                    params![LOCAL_ACCOUNT, stored],
+                    |_| Ok(()),
+                )
+                .optional()?
+                .is_some())
+        })
+        .await?;
+    if exists {
+        return Err(NoWorkError::validation("You are already subscribed to this feed"));
+    }
+
+    let (data, etag) = download_feed(&url, None)
+        .await?
+        .ok_or_else(|| NoWorkError::InvalidResponse("feed returned 304 without being asked".to_string()))?;
+    let (name, color) = feed_details(&data)?;
+    let info = CalendarInfo {
+        url: url.to_string(),
+        display_name: name.or_else(|| url.host_str().map(String::from)),
+        color,
+        ctag: etag,
+        sync_token: None,
+        components: vec!["VEVENT".to_string()],
+        enabled: true,
+    };
+
+    db.run(move |conn| {
+        save_local_calendar(conn, &info, data, Some(refresh_minutes))?;
+        local_calendar(conn, &info.url)
+    })
+    .await
+}
+
+// Imports the contents of a local .ics file as a calendar of its own. name is
+// used when the file does not name itself, usually the file name.
+#[tauri::command]
+pub async fn import_calendar(db: State<'_, Database>, name: String, data: String) -> Result<CalendarInfo, NoWorkError> {
+    let (calendar_name, color) = feed_details(&data)
+        .map_err(|_| NoWorkError::validation("This file is not an iCalendar (.ics) file"))?;
+    let name = name.trim();
+    let info = CalendarInfo {
+        // imports have no location, the URL only has to be unique
+        url: format!("import:{}", Uuid::new_v4()),
+        display_name: calendar_name.or_else(|| (!name.is_empty()).then(|| name.to_string())),
+        color,
+        ctag: None,
+        sync_token: None,
+        components: vec!["VEVENT".to_string()],
+        enabled: true,
+    };
+
+    db.run(move |conn| {
+        save_local_calendar(conn, &info, data, None)?;
+        local_calendar(conn, &info.url)
+    })
+    .await
+}
+
+// Unsubscribes from a feed or drops an imported file
+#[tauri::command]
+pub async fn remove_local_calendar(db: State<'_, Database>, url: String) -> Result<(), NoWorkError> {
+    db.run(move |conn| {
+        let removed = conn.execute(
+            "DELETE FROM calendars WHERE account = ?1 AND url = ?2",
+            params![LOCAL_ACCOUNT, url],
+        )?;
+        if removed == 0 {
+            return Err(NoWorkError::validation("No subscription or import with this URL"));
+        }
+        Ok(())
+    })
+    .await
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+
+use icalendar::{Calendar, Component, Event, EventLike};
+use rusqlite::Connection;
+use tauri::State;
+
+use crate::calendar::todo_sync::{ical_priority, new_todo, TaskFields, PRODID};
+use crate::commands::{
+    database::Database,
+    query::run_query,
+    types::{Task, TaskQuery},
+};
+use crate::error::NoWorkError;
+
+// UIDs of tasks that are synced, so an export of a synced task is recognised
+// as the same item by apps that also see the task list
+fn synced_uids(conn: &Connection) -> Result<HashMap<i32, String>, NoWorkError> {
+    let mut stmt = conn.prepare("SELECT task_id, uid FROM task_sync_links WHERE task_id IS NOT NULL")?;
+    let uids = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<HashMap<_, _>>>()?;
+    Ok(uids)
+}
+
+fn task_uid(task: &Task, synced: &HashMap<i32, String>) -> String {
+    synced
+        .get(&task.id)
+        .cloned()
+        .unwrap_or_else(|| format!("nowork-task-{}", task.id))
+}
+
+// A task with a due date as a zero length event at the due time, for calendar
+// apps that ignore VTODO. Events have no completion, only the rest is kept.
+fn task_event(uid: &str, task: &Task) -> Option<Event> {
+    let due = task.due_date?;
+    let mut event = Event::with_uid(uid);
+    event.summary(&task.name).starts(due).timestamp(task.created_at);
+    if let Some(priority) = &task.priority {
+        event.priority(ical_priority(priority));
+    }
+    for tag in task.tags.iter().flatten() {
+        event.add_multi_property("CATEGORIES", &tag.name);
+    }
+    Some(event.done())
+}
+
+// The matching tasks as one iCalendar document, and how many went in
+pub fn export_calendar(conn: &Connection, query: &TaskQuery, as_events: bool) -> Result<(String, usize), NoWorkError> {
+    let tasks = run_query(conn, query)?.tasks;
+    let synced = synced_uids(conn)?;
+
+    let mut calendar = Calendar::empty();
+    calendar
+        .append_property(("VERSION", "2.0"))
+        .append_property(("PRODID", PRODID))
+        .append_property(("X-WR-CALNAME", "NoWork tasks"));
+
+    let mut count = 0;
+    for task in &tasks {
+        let uid = task_uid(task, &synced);
+        if as_events {
+            // without a due date there is nowhere to put the task
+            let Some(event) = task_event(&uid, task) else { continue };
+            calendar.push(event);
+        } else {
+            calendar.push(new_todo(&uid, task.created_at, &TaskFields::from_task(task)));
+        }
+        count += 1;
+    }
+
+    Ok((calendar.to_string(), count))
+}
+
+// Writes the tasks matching query to an .ics file at path, as VTODOs or, with
+// as_events, as VEVENTs at their due time. Returns how many were written.
+#[tauri::command]
+pub async fn export_tasks(
+    db: State<'_, Database>,
+    path: String,
+    query: Option<TaskQuery>,
+    as_events: bool,
+) -> Result<usize, NoWorkError> {
+    let query = query.unwrap_or_default();
+    db.run(move |conn| {
+        let (data, count) = export_calendar(conn, &query, as_events)?;
+        std::fs::write(&path, data)?;
+        Ok(count)
+    })
+    .await
+}
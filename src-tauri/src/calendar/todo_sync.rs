@@ -0,0 +1,993 @@
+use chrono::{DateTime, Utc};
+use ical::IcalParser;
+use icalendar::{Calendar, Component, Todo, TodoStatus};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::io::BufReader;
+use std::str::FromStr;
+use tauri::State;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::calendar::{
+    accounts::connect,
+    caldav::{CalDavClient, CalendarObject, WriteOutcome},
+    ical_time::{find_prop, param, parse_time, Zones},
+};
+use crate::commands::{
+    database::Database,
+    recurrence,
+    tasks::{load_task, load_tasks},
+    types::{Task, TaskPriority},
+};
+use crate::error::NoWorkError;
+
+pub const PRODID: &str = "-//NoWork//NoWork//EN";
+
+// two syncs running at once would both upload the same new tasks
+static SYNC_LOCK: Mutex<()> = Mutex::const_new(());
+
+// The parts of a task that are synced. Compared field by field, so an edit on
+// one side only rewrites what changed and leaves the rest of the VTODO alone.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TaskFields {
+    name: String,
+    due_date: Option<DateTime<Utc>>,
+    priority: Option<i32>,
+    completed: bool,
+    completed_at: Option<DateTime<Utc>>,
+    // sorted
+    tags: Vec<String>,
+}
+
+impl TaskFields {
+    pub fn from_task(task: &Task) -> Self {
+        let mut tags: Vec<String> = task.tags.iter().flatten().map(|tag| tag.name.clone()).collect();
+        tags.sort();
+        TaskFields {
+            name: task.name.clone(),
+            due_date: task.due_date,
+            priority: task.priority.as_ref().map(TaskPriority::as_i32),
+            completed: task.completed,
+            completed_at: task.completed_at,
+            tags,
+        }
+    }
+
+    fn fingerprint(&self) -> Result<String, NoWorkError> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+// The task a server resource holds
+struct RemoteTask {
+    uid: String,
+    created_at: Option<DateTime<Utc>>,
+    fields: TaskFields,
+}
+
+// One row of task_sync_links
+struct Link {
+    href: String,
+    uid: String,
+    task_id: Option<i32>,
+    etag: Option<String>,
+    fingerprint: Option<String>,
+    data: String,
+    ignored: bool,
+}
+
+// What one sync did. When a task was edited on both sides the server copy is
+// kept and the task is listed under conflicts, so the local edit can be redone.
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskSyncReport {
+    // tasks created or updated on the server
+    pub pushed: usize,
+    // tasks created or updated locally
+    pub pulled: usize,
+    // tasks deleted on one side because they were deleted on the other
+    pub deleted: usize,
+    pub conflicts: Vec<String>,
+}
+
+// What syncing one resource did, tallied into the report
+enum Outcome {
+    Unchanged,
+    Pushed,
+    Pulled,
+    Deleted,
+    // pulled over a local edit, carries the task name
+    Conflict(String),
+}
+
+// RFC 5545 PRIORITY runs from 1 (highest) to 9, 0 means undefined
+pub fn ical_priority(priority: &TaskPriority) -> u32 {
+    match priority {
+        TaskPriority::High => 1,
+        TaskPriority::Medium => 5,
+        TaskPriority::Low => 9,
+    }
+}
+
+fn task_priority(value: u32) -> Option<TaskPriority> {
+    match value {
+        1..=4 => Some(TaskPriority::High),
+        5 => Some(TaskPriority::Medium),
+        6..=9 => Some(TaskPriority::Low),
+        _ => None,
+    }
+}
+
+// Undoes RFC 5545 TEXT escaping. With split set, unescaped commas separate
+// the values of a list such as CATEGORIES.
+pub fn unescape_text(value: &str, split: bool) -> Vec<String> {
+    let mut values = vec![String::new()];
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        let current = values.last_mut().expect("values is never empty");
+        match c {
+            '\\' => match chars.next() {
+                Some('n' | 'N') => current.push('\n'),
+                Some(escaped) => current.push(escaped),
+                None => {}
+            },
+            ',' if split => values.push(String::new()),
+            c => current.push(c),
+        }
+    }
+    values
+}
+
+// Reads the task out of a resource. None for anything NoWork does not show
+// as a task: resources without a VTODO, and subtasks, which point at their
+// parent through RELATED-TO.
+fn parse_todo(data: &str) -> Option<RemoteTask> {
+    let calendar = IcalParser::new(BufReader::new(data.as_bytes())).flatten().next()?;
+    let zones = Zones::new(&calendar.timezones);
+    // edited instances of a recurring task carry a RECURRENCE-ID, the master does not
+    let todo = calendar
+        .todos
+        .iter()
+        .find(|todo| find_prop(&todo.properties, "RECURRENCE-ID").is_none())?;
+    let props = &todo.properties;
+
+    let is_subtask = props
+        .iter()
+        .filter(|p| p.name == "RELATED-TO")
+        .any(|p| param(p, "RELTYPE").is_none_or(|reltype| reltype.eq_ignore_ascii_case("PARENT")));
+    if is_subtask {
+        return None;
+    }
+
+    let value = |name| find_prop(props, name).and_then(|p| p.value.as_deref());
+    let instant = |name| {
+        find_prop(props, name)
+            .and_then(parse_time)
+            .map(|time| zones.resolve(time.wall, &time.zone).with_timezone(&Utc))
+    };
+
+    let completed_at = instant("COMPLETED");
+    let completed = match value("STATUS") {
+        Some(status) => status.eq_ignore_ascii_case("COMPLETED"),
+        None => completed_at.is_some(),
+    };
+
+    let mut tags: Vec<String> = props
+        .iter()
+        .filter(|p| p.name == "CATEGORIES")
+        .filter_map(|p| p.value.as_deref())
+        .flat_map(|list| unescape_text(list, true))
+        .map(|tag| tag.trim().to_string())
+        .filter(|tag| !tag.is_empty())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect();
+    tags.sort();
+
+    Some(RemoteTask {
+        uid: value("UID")?.to_string(),
+        created_at: instant("CREATED"),
+        fields: TaskFields {
+            name: value("SUMMARY").map(|s| unescape_text(s, false).concat()).unwrap_or_default(),
+            due_date: instant("DUE"),
+            priority: value("PRIORITY")
+                .and_then(|p| p.trim().parse().ok())
+                .and_then(task_priority)
+                .map(|p| p.as_i32()),
+            completed,
+            completed_at: completed_at.filter(|_| completed),
+            tags,
+        },
+    })
+}
+
+// Writes fields into a VTODO, skipping those that already match previous
+fn write_fields(todo: &mut Todo, fields: &TaskFields, previous: Option<&TaskFields>) {
+    let now = Utc::now();
+    todo.timestamp(now).last_modified(now);
+
+    if previous.is_none_or(|p| p.name != fields.name) {
+        todo.summary(&fields.name);
+    }
+    if previous.is_none_or(|p| p.due_date != fields.due_date) {
+        todo.remove_due();
+        if let Some(due) = fields.due_date {
+            todo.due(due);
+        }
+    }
+    if previous.is_none_or(|p| p.priority != fields.priority) {
+        match fields.priority.and_then(TaskPriority::from_i32) {
+            Some(priority) => todo.priority(ical_priority(&priority)),
+            None => todo.remove_priority(),
+        };
+    }
+    if previous.is_none_or(|p| p.completed != fields.completed) {
+        if fields.completed {
+            todo.status(TodoStatus::Completed)
+                .completed(fields.completed_at.unwrap_or(now))
+                .percent_complete(100);
+        } else {
+            todo.mark_uncompleted().status(TodoStatus::NeedsAction);
+        }
+    }
+    // always rewritten, icalendar would escape the commas of a list it read
+    // back as part of a single category
+    todo.remove_multi_property("CATEGORIES");
+    for tag in &fields.tags {
+        todo.add_multi_property("CATEGORIES", tag);
+    }
+}
+
+pub fn new_todo(uid: &str, created_at: DateTime<Utc>, fields: &TaskFields) -> Todo {
+    let mut todo = Todo::with_uid(uid);
+    todo.created(created_at);
+    write_fields(&mut todo, fields, None);
+    todo
+}
+
+fn new_resource(uid: &str, created_at: DateTime<Utc>, fields: &TaskFields) -> String {
+    let todo = new_todo(uid, created_at, fields);
+
+    let mut calendar = Calendar::empty();
+    calendar
+        .append_property(("VERSION", "2.0"))
+        .append_property(("PRODID", PRODID))
+        .push(todo);
+    calendar.to_string()
+}
+
+// Applies local edits on top of the server copy, so everything NoWork does
+// not know about (descriptions, alarms, time zones, ...) survives. Falls back
+// to a fresh resource if the server copy cannot be read back.
+fn updated_resource(link: &Link, task: &Task, fields: &TaskFields) -> String {
+    let previous = parse_todo(&link.data);
+    let calendar = Calendar::from_str(&link.data).ok().filter(|_| previous.is_some());
+
+    if let (Some(previous), Some(mut calendar)) = (previous, calendar) {
+        let master = calendar
+            .todos_mut()
+            .find(|todo| todo.get_recurrence_id().is_none());
+        if let Some(todo) = master {
+            write_fields(todo, fields, Some(&previous.fields));
+            let sequence = todo.get_sequence().unwrap_or(0);
+            todo.sequence(sequence + 1);
+            return calendar.to_string();
+        }
+    }
+
+    log::warn!("Could not read back {}, replacing it", link.href);
+    new_resource(&link.uid, task.created_at, fields)
+}
+
+// New resources are named after their UID inside the calendar
+pub fn resource_url(calendar_url: &str, uid: &str) -> String {
+    format!("{}/{}.ics", calendar_url.trim_end_matches('/'), uid)
+}
+
+fn load_links(conn: &Connection, account: i64) -> Result<Vec<Link>, NoWorkError> {
+    let mut stmt = conn.prepare(
+        "SELECT href, uid, task_id, etag, fingerprint, data, ignored FROM task_sync_links WHERE account = ?1",
+    )?;
+    let links = stmt
+        .query_map(params![account], |row| {
+            Ok(Link {
+                href: row.get(0)?,
+                uid: row.get(1)?,
+                task_id: row.get(2)?,
+                etag: row.get(3)?,
+                fingerprint: row.get(4)?,
+                data: row.get(5)?,
+                ignored: row.get(6)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(links)
+}
+
+fn save_link(conn: &Connection, account: i64, link: &Link) -> Result<(), NoWorkError> {
+    conn.execute(
+        "INSERT INTO task_sync_links (account, href, uid, task_id, etag, fingerprint, data, ignored)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+         ON CONFLICT(account, href) DO UPDATE SET
+            uid = excluded.uid,
+            task_id = excluded.task_id,
+            etag = excluded.etag,
+            fingerprint = excluded.fingerprint,
+            data = excluded.data,
+            ignored = excluded.ignored",
+        params![
+            account,
+            link.href,
+            link.uid,
+            link.task_id,
+            link.etag,
+            link.fingerprint,
+            link.data,
+            link.ignored
+        ],
+    )?;
+    Ok(())
+}
+
+fn drop_link(conn: &Connection, account: i64, href: &str) -> Result<(), NoWorkError> {
+    conn.execute(
+        "DELETE FROM task_sync_links WHERE account = ?1 AND href = ?2",
+        params![account, href],
+    )?;
+    Ok(())
+}
+
+pub fn task_calendar(conn: &Connection, account: i64) -> Result<Option<String>, NoWorkError> {
+    let url = conn
+        .query_row(
+            "SELECT task_calendar_url FROM calendar_accounts WHERE id = ?1",
+            params![account],
+            |row| row.get::<_, Option<String>>(0),
+        )
+        .optional()?;
+    Ok(url.flatten())
+}
+
+// Writes the server's version of a task, creating the task if task_id is None
+fn apply_remote(conn: &Connection, task_id: Option<i32>, remote: &RemoteTask) -> Result<i32, NoWorkError> {
+    let fields = &remote.fields;
+    let due_date = fields.due_date.map(|d| d.to_rfc3339());
+    let completed_at = fields.completed_at.map(|d| d.to_rfc3339());
+
+    let (task_id, was_completed) = match task_id {
+        Some(task_id) => {
+            let was_completed: bool =
+                conn.query_row("SELECT completed FROM tasks WHERE id = ?1", params![task_id], |row| row.get(0))?;
+            conn.execute(
+                "UPDATE tasks SET name = ?1, due_date = ?2, priority = ?3, completed = ?4,
+                    completed_at = CASE WHEN ?4 THEN COALESCE(?5, completed_at, strftime('%Y-%m-%dT%H:%M:%fZ', 'now')) END
+                 WHERE id = ?6",
+                params![fields.name, due_date, fields.priority, fields.completed, completed_at, task_id],
+            )?;
+            (task_id, was_completed)
+        }
+        None => {
+            conn.execute(
+                "INSERT INTO tasks (name, due_date, priority, completed, completed_at, created_at)
+                 VALUES (?1, ?2, ?3, ?4,
+                    CASE WHEN ?4 THEN COALESCE(?5, strftime('%Y-%m-%dT%H:%M:%fZ', 'now')) END,
+                    COALESCE(?6, strftime('%Y-%m-%dT%H:%M:%fZ', 'now')))",
+                params![
+                    fields.name,
+                    due_date,
+                    fields.priority,
+                    fields.completed,
+                    completed_at,
+                    remote.created_at.map(|d| d.to_rfc3339())
+                ],
+            )?;
+            (conn.last_insert_rowid() as i32, false)
+        }
+    };
+
+    // tags are matched by name, ones that do not exist yet get the default color
+    conn.execute("DELETE FROM task_tags WHERE task_id = ?1", params![task_id])?;
+    for tag in &fields.tags {
+        conn.execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", params![tag])?;
+        conn.execute(
+            "INSERT OR IGNORE INTO task_tags (task_id, tag_id) SELECT ?1, id FROM tags WHERE name = ?2",
+            params![task_id, tag],
+        )?;
+    }
+
+    // completing a recurring task elsewhere schedules its next occurrence, like complete_task
+    if fields.completed && !was_completed {
+        recurrence::spawn_next_occurrence(conn, task_id)?;
+    }
+
+    Ok(task_id)
+}
+
+// Stores a downloaded resource. Tasks are created or updated from it, other
+// resources are remembered as ignored so they are not downloaded again until
+// they change.
+async fn pull(
+    db: &Database,
+    account: i64,
+    object: &CalendarObject,
+    link: Option<&Link>,
+) -> Result<Outcome, NoWorkError> {
+    let task_id = link.and_then(|l| l.task_id);
+    let object = object.clone();
+
+    let Some(remote) = parse_todo(&object.data) else {
+        return db.run(move |conn| {
+            let tx = conn.transaction()?;
+            // a task that turned into something else is gone as far as NoWork is concerned
+            if let Some(task_id) = task_id {
+                tx.execute("DELETE FROM tasks WHERE id = ?1", params![task_id])?;
+            }
+            let ignored = Link {
+                href: object.href,
+                uid: String::new(),
+                task_id: None,
+                etag: object.etag,
+                fingerprint: None,
+                data: String::new(),
+                ignored: true,
+            };
+            save_link(&tx, account, &ignored)?;
+            tx.commit()?;
+            Ok(if task_id.is_some() { Outcome::Deleted } else { Outcome::Unchanged })
+        })
+        .await;
+    };
+
+    db.run(move |conn| {
+        let tx = conn.transaction()?;
+        let task_id = apply_remote(&tx, task_id, &remote)?;
+        let task = load_task(&tx, task_id)?;
+        let link = Link {
+            href: object.href,
+            uid: remote.uid,
+            task_id: Some(task_id),
+            etag: object.etag,
+            fingerprint: Some(TaskFields::from_task(&task).fingerprint()?),
+            data: object.data,
+            ignored: false,
+        };
+        save_link(&tx, account, &link)?;
+        tx.commit()?;
+        Ok(Outcome::Pulled)
+    })
+    .await
+}
+
+// Uploads a task over the resource at link.href
+async fn push(client: &CalDavClient, db: &Database, account: i64, link: &Link, task: &Task, exists: bool) -> Result<Outcome, NoWorkError> {
+    let fields = TaskFields::from_task(task);
+    let (data, etag) = if exists {
+        (updated_resource(link, task, &fields), link.etag.as_deref())
+    } else {
+        (new_resource(&link.uid, task.created_at, &fields), None)
+    };
+
+    match client.put(&link.href, &data, etag).await? {
+        WriteOutcome::Done(etag) => {
+            let link = Link {
+                href: link.href.clone(),
+                uid: link.uid.clone(),
+                task_id: Some(task.id),
+                etag,
+                fingerprint: Some(fields.fingerprint()?),
+                data,
+                ignored: false,
+            };
+            db.run(move |conn| save_link(conn, account, &link)).await?;
+            Ok(Outcome::Pushed)
+        }
+        // changed on the server in the meantime, the next sync pulls it
+        WriteOutcome::Conflict => {
+            log::info!("{} changed on the server while uploading, keeping the server copy", link.href);
+            Ok(Outcome::Unchanged)
+        }
+    }
+}
+
+async fn sync_link(
+    client: &CalDavClient,
+    db: &Database,
+    account: i64,
+    link: &Link,
+    task: Option<&Task>,
+    remote: Option<&CalendarObject>,
+    gone: bool,
+) -> Result<Outcome, NoWorkError> {
+    if link.ignored {
+        return match remote {
+            Some(object) => pull(db, account, object, Some(link)).await,
+            None if gone => {
+                let href = link.href.clone();
+                db.run(move |conn| drop_link(conn, account, &href)).await?;
+                Ok(Outcome::Unchanged)
+            }
+            None => Ok(Outcome::Unchanged),
+        };
+    }
+
+    let local_changed = match task {
+        Some(task) => Some(TaskFields::from_task(task).fingerprint()?) != link.fingerprint,
+        None => true,
+    };
+
+    match (task, remote) {
+        // deleted on both sides
+        (None, None) if gone => {
+            let href = link.href.clone();
+            db.run(move |conn| drop_link(conn, account, &href)).await?;
+            Ok(Outcome::Unchanged)
+        }
+        // deleted here, untouched there
+        (None, None) => match client.delete(&link.href, link.etag.as_deref()).await? {
+            WriteOutcome::Done(_) => {
+                let href = link.href.clone();
+                db.run(move |conn| drop_link(conn, account, &href)).await?;
+                Ok(Outcome::Deleted)
+            }
+            // edited there in the meantime, the next sync brings it back
+            WriteOutcome::Conflict => Ok(Outcome::Unchanged),
+        },
+        // edited there, an edit wins over a delete
+        (None, Some(object)) => {
+            pull(db, account, object, Some(link)).await?;
+            let name = parse_todo(&object.data).map(|r| r.fields.name).unwrap_or_default();
+            Ok(Outcome::Conflict(name))
+        }
+        // deleted there
+        (Some(task), None) if gone => {
+            if local_changed {
+                return push(client, db, account, link, task, false).await;
+            }
+            let (href, task_id) = (link.href.clone(), task.id);
+            db.run(move |conn| {
+                let tx = conn.transaction()?;
+                tx.execute("DELETE FROM tasks WHERE id = ?1", params![task_id])?;
+                drop_link(&tx, account, &href)?;
+                tx.commit()?;
+                Ok(())
+            })
+            .await?;
+            Ok(Outcome::Deleted)
+        }
+        (Some(task), None) if local_changed => push(client, db, account, link, task, true).await,
+        (Some(_), None) => Ok(Outcome::Unchanged),
+        (Some(task), Some(object)) => {
+            let outcome = pull(db, account, object, Some(link)).await?;
+            if local_changed && matches!(outcome, Outcome::Pulled) {
+                return Ok(Outcome::Conflict(task.name.clone()));
+            }
+            Ok(outcome)
+        }
+    }
+}
+
+// Two-way sync between the top-level tasks and the VTODOs of one calendar.
+// Changes are spotted with ETags on the server and with a fingerprint of the
+// synced fields locally. Completed tasks that never reached the server and
+// subtasks stay local.
+pub async fn sync_task_list(
+    db: &Database,
+    client: &CalDavClient,
+    account: i64,
+    calendar_url: &str,
+) -> Result<TaskSyncReport, NoWorkError> {
+    let _guard = SYNC_LOCK.lock().await;
+
+    let links = db.run(move |conn| load_links(conn, account)).await?;
+    let listing: HashMap<String, Option<String>> = client.etags(calendar_url).await?.into_iter().collect();
+
+    let known: HashMap<&str, &Link> = links.iter().map(|link| (link.href.as_str(), link)).collect();
+    let changed: Vec<String> = listing
+        .iter()
+        .filter(|(href, etag)| {
+            known
+                .get(href.as_str())
+                .is_none_or(|link| etag.is_none() || link.etag != **etag)
+        })
+        .map(|(href, _)| href.clone())
+        .collect();
+    let downloaded: HashMap<String, CalendarObject> = client
+        .multiget(calendar_url, &changed)
+        .await?
+        .into_iter()
+        .map(|object| (object.href.clone(), object))
+        .collect();
+    let changed: HashSet<String> = changed.into_iter().collect();
+
+    let ids: Vec<i32> = links.iter().filter_map(|link| link.task_id).collect();
+    let tasks: HashMap<i32, Task> = db
+        .run(move |conn| {
+            let ids_json = serde_json::to_string(&ids)?;
+            let tasks = load_tasks(conn, "WHERE tasks.id IN (SELECT value FROM json_each(?1))", params![ids_json])?;
+            Ok(tasks.into_iter().map(|task| (task.id, task)).collect())
+        })
+        .await?;
+
+    let mut outcomes = Vec::new();
+
+    for link in &links {
+        let remote = downloaded.get(&link.href);
+        // missing from the listing, or deleted between listing and download
+        let gone = remote.is_none() && (!listing.contains_key(&link.href) || changed.contains(&link.href));
+        let task = link.task_id.and_then(|id| tasks.get(&id));
+        outcomes.push((link.href.clone(), sync_link(client, db, account, link, task, remote, gone).await));
+    }
+
+    // new on the server
+    for (href, object) in &downloaded {
+        if !known.contains_key(href.as_str()) {
+            outcomes.push((href.clone(), pull(db, account, object, None).await));
+        }
+    }
+
+    // new here
+    let fresh = db
+        .run(|conn| {
+            load_tasks(
+                conn,
+                "WHERE tasks.parent_id IS NULL AND tasks.completed = 0
+                 AND NOT EXISTS (SELECT 1 FROM task_sync_links WHERE task_sync_links.task_id = tasks.id)",
+                [],
+            )
+        })
+        .await?;
+    for task in &fresh {
+        let uid = Uuid::new_v4().to_string();
+        let link = Link {
+            href: resource_url(calendar_url, &uid),
+            uid,
+            task_id: Some(task.id),
+            etag: None,
+            fingerprint: None,
+            data: String::new(),
+            ignored: false,
+        };
+        outcomes.push((link.href.clone(), push(client, db, account, &link, task, false).await));
+    }
+
+    let mut report = TaskSyncReport::default();
+    for (href, outcome) in outcomes {
+        match outcome {
+            Ok(Outcome::Unchanged) => {}
+            Ok(Outcome::Pushed) => report.pushed += 1,
+            Ok(Outcome::Pulled) => report.pulled += 1,
+            Ok(Outcome::Deleted) => report.deleted += 1,
+            Ok(Outcome::Conflict(name)) => {
+                report.pulled += 1;
+                report.conflicts.push(name);
+            }
+            Err(NoWorkError::AuthFailed) => return Err(NoWorkError::AuthFailed),
+            // one bad resource should not stop the others from syncing
+            Err(e) => log::warn!("Failed to sync task {}: {}", href, e),
+        }
+    }
+
+    Ok(report)
+}
+
+// Chooses the calendar tasks are synced with, None stops syncing. Links to
+// the previous one are dropped, so its tasks stay here as local tasks and
+// the open ones are uploaded to the new calendar on the next sync.
+#[tauri::command]
+pub async fn set_task_calendar(
+    db: State<'_, Database>,
+    account: i64,
+    calendar_url: Option<String>,
+) -> Result<(), NoWorkError> {
+    db.run(move |conn| {
+        if let Some(url) = &calendar_url {
+            let components: Option<String> = conn
+                .query_row(
+                    "SELECT components FROM calendars WHERE account = ?1 AND url = ?2",
+                    params![account, url],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            let Some(components) = components else {
+                return Err(NoWorkError::validation("Sync the account before choosing its task list"));
+            };
+            // an empty set means the calendar takes any component
+            if !components.is_empty() && !components.split(',').any(|c| c == "VTODO") {
+                return Err(NoWorkError::validation("This calendar cannot hold tasks"));
+            }
+        }
+
+        if task_calendar(conn, account)? == calendar_url {
+            return Ok(());
+        }
+
+        let tx = conn.transaction()?;
+        tx.execute(
+            "UPDATE calendar_accounts SET task_calendar_url = ?1 WHERE id = ?2",
+            params![calendar_url, account],
+        )?;
+        tx.execute("DELETE FROM task_sync_links WHERE account = ?1", params![account])?;
+        tx.commit()?;
+        Ok(())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn get_task_calendar(db: State<'_, Database>, account: i64) -> Result<Option<String>, NoWorkError> {
+    db.run(move |conn| task_calendar(conn, account)).await
+}
+
+// Syncs tasks with the account's chosen task list
+#[tauri::command]
+pub async fn sync_tasks(db: State<'_, Database>, account: i64) -> Result<TaskSyncReport, NoWorkError> {
+    let calendar_url = db
+        .run(move |conn| task_calendar(conn, account))
+        .await?
+        .ok_or_else(|| NoWorkError::validation("Choose a task list to sync with first"))?;
+
+    let client = connect(&db, account).await?;
+    sync_task_list(&db, &client, account, &calendar_url).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+    use wiremock::matchers::{header, method};
+    use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+    // The task list on the mock server: path, etag and data of each resource.
+    // PROPFIND lists them, a multiget REPORT returns the ones it asks for.
+    struct TaskList(Vec<(&'static str, &'static str, String)>);
+
+    impl Respond for TaskList {
+        fn respond(&self, request: &Request) -> ResponseTemplate {
+            let body = String::from_utf8_lossy(&request.body);
+            let responses: String = self
+                .0
+                .iter()
+                .filter(|(path, _, _)| request.method.as_str() == "PROPFIND" || body.contains(&format!("<d:href>{}</d:href>", path)))
+                .map(|(path, etag, data)| {
+                    let data = match request.method.as_str() {
+                        "REPORT" => format!("<c:calendar-data>{}</c:calendar-data>", data),
+                        _ => "<d:resourcetype/>".to_string(),
+                    };
+                    format!(
+                        r#"<d:response>
+                            <d:href>{}</d:href>
+                            <d:propstat>
+                                <d:prop><d:getetag>"{}"</d:getetag>{}</d:prop>
+                                <d:status>HTTP/1.1 200 OK</d:status>
+                            </d:propstat>
+                        </d:response>"#,
+                        path, etag, data
+                    )
+                })
+                .collect();
+            ResponseTemplate::new(207).set_body_string(format!(
+                r#"<?xml version="1.0" encoding="utf-8"?>
+                <d:multistatus xmlns:d="DAV:" xmlns:c="urn:ietf:params:xml:ns:caldav">{}</d:multistatus>"#,
+                responses
+            ))
+        }
+    }
+
+    fn vtodo(uid: &str, summary: &str) -> String {
+        format!(
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Test//EN\r\nBEGIN:VTODO\r\nUID:{}\r\n\
+             DTSTAMP:20260101T000000Z\r\nSUMMARY:{}\r\nEND:VTODO\r\nEND:VCALENDAR\r\n",
+            uid, summary
+        )
+    }
+
+    fn vevent(uid: &str) -> String {
+        format!(
+            "BEGIN:VCALENDAR\r\nVERSION:2.0\r\nPRODID:-//Test//EN\r\nBEGIN:VEVENT\r\nUID:{}\r\n\
+             DTSTAMP:20260101T000000Z\r\nDTSTART:20260101T090000Z\r\nSUMMARY:Standup\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n",
+            uid
+        )
+    }
+
+    struct Setup {
+        _dir: TempDir,
+        db: Database,
+        server: MockServer,
+        client: CalDavClient,
+        account: i64,
+        calendar: String,
+    }
+
+    impl Setup {
+        async fn serve(&self, list: TaskList) {
+            self.server.reset().await;
+            Mock::given(method("PROPFIND")).respond_with(list).mount(&self.server).await;
+        }
+
+        // a multiget needs the same list as the listing, see TaskList
+        async fn serve_both(&self, list: Vec<(&'static str, &'static str, String)>) {
+            self.server.reset().await;
+            Mock::given(method("PROPFIND"))
+                .respond_with(TaskList(list.clone()))
+                .mount(&self.server)
+                .await;
+            Mock::given(method("REPORT"))
+                .respond_with(TaskList(list))
+                .mount(&self.server)
+                .await;
+        }
+
+        async fn sync(&self) -> TaskSyncReport {
+            sync_task_list(&self.db, &self.client, self.account, &self.calendar).await.unwrap()
+        }
+
+        async fn names(&self) -> Vec<String> {
+            self.db
+                .run(|conn| {
+                    let mut stmt = conn.prepare("SELECT name FROM tasks ORDER BY name")?;
+                    let names = stmt.query_map([], |row| row.get(0))?.collect::<rusqlite::Result<Vec<_>>>()?;
+                    Ok(names)
+                })
+                .await
+                .unwrap()
+        }
+
+        async fn links(&self) -> usize {
+            let account = self.account;
+            self.db.run(move |conn| load_links(conn, account)).await.unwrap().len()
+        }
+
+        async fn execute(&self, sql: &'static str) {
+            self.db
+                .run(move |conn| {
+                    conn.execute(sql, [])?;
+                    Ok(())
+                })
+                .await
+                .unwrap();
+        }
+    }
+
+    // an account whose task list holds "Buy milk", synced once
+    async fn synced() -> Setup {
+        let dir = tempfile::tempdir().unwrap();
+        let db = nowork_core::database::open(dir.path().join("tasks.db"), &dir.path().join("backups")).unwrap();
+        let server = MockServer::start().await;
+        let uri = server.uri();
+        let account = db
+            .run(move |conn| {
+                conn.execute(
+                    "INSERT INTO calendar_accounts (username, provider, server_url) VALUES ('alice', 'caldav', ?1)",
+                    params![uri],
+                )?;
+                Ok(conn.last_insert_rowid())
+            })
+            .await
+            .unwrap();
+        let setup = Setup {
+            client: CalDavClient::new(&server.uri(), "alice", "hunter2").unwrap(),
+            calendar: format!("{}/cal/", server.uri()),
+            _dir: dir,
+            db,
+            server,
+            account,
+        };
+
+        setup.serve_both(vec![("/cal/milk.ics", "1", vtodo("milk", "Buy milk"))]).await;
+        assert_eq!(setup.sync().await.pulled, 1);
+        assert_eq!(setup.names().await, ["Buy milk"]);
+        setup
+    }
+
+    #[tokio::test]
+    async fn deleted_on_both_sides_drops_the_link() {
+        let setup = synced().await;
+        setup.execute("DELETE FROM tasks").await;
+        setup.serve(TaskList(vec![])).await;
+        Mock::given(method("DELETE")).respond_with(ResponseTemplate::new(204)).expect(0).mount(&setup.server).await;
+
+        let report = setup.sync().await;
+        assert_eq!(report.deleted, 0);
+        assert_eq!(setup.links().await, 0);
+    }
+
+    #[tokio::test]
+    async fn deleted_here_is_deleted_on_the_server() {
+        let setup = synced().await;
+        setup.execute("DELETE FROM tasks").await;
+        setup.serve(TaskList(vec![("/cal/milk.ics", "1", String::new())])).await;
+        Mock::given(method("DELETE"))
+            .and(header("If-Match", "\"1\""))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&setup.server)
+            .await;
+
+        assert_eq!(setup.sync().await.deleted, 1);
+        assert_eq!(setup.links().await, 0);
+    }
+
+    #[tokio::test]
+    async fn an_edit_on_the_server_wins_over_a_delete_here() {
+        let setup = synced().await;
+        setup.execute("DELETE FROM tasks").await;
+        setup.serve_both(vec![("/cal/milk.ics", "2", vtodo("milk", "Buy oat milk"))]).await;
+        Mock::given(method("DELETE")).respond_with(ResponseTemplate::new(204)).expect(0).mount(&setup.server).await;
+
+        let report = setup.sync().await;
+        assert_eq!(report.conflicts, ["Buy oat milk"]);
+        assert_eq!(setup.names().await, ["Buy oat milk"]);
+    }
+
+    #[tokio::test]
+    async fn deleted_on_the_server_is_deleted_here() {
+        let setup = synced().await;
+        setup.serve(TaskList(vec![])).await;
+
+        assert_eq!(setup.sync().await.deleted, 1);
+        assert!(setup.names().await.is_empty());
+        assert_eq!(setup.links().await, 0);
+    }
+
+    #[tokio::test]
+    async fn an_edit_here_is_uploaded_again_when_the_server_deleted_the_task() {
+        let setup = synced().await;
+        setup.execute("UPDATE tasks SET name = 'Buy oat milk'").await;
+        setup.serve(TaskList(vec![])).await;
+        Mock::given(method("PUT"))
+            .and(header("If-None-Match", "*"))
+            .respond_with(ResponseTemplate::new(201).insert_header("ETag", "\"3\""))
+            .expect(1)
+            .mount(&setup.server)
+            .await;
+
+        let report = setup.sync().await;
+        assert_eq!((report.pushed, report.deleted), (1, 0));
+        assert_eq!(setup.names().await, ["Buy oat milk"]);
+        assert_eq!(setup.links().await, 1);
+    }
+
+    #[tokio::test]
+    async fn edits_on_both_sides_keep_the_server_copy_and_report_a_conflict() {
+        let setup = synced().await;
+        setup.execute("UPDATE tasks SET name = 'Buy soy milk'").await;
+        setup.serve_both(vec![("/cal/milk.ics", "2", vtodo("milk", "Buy oat milk"))]).await;
+        Mock::given(method("PUT")).respond_with(ResponseTemplate::new(204)).expect(0).mount(&setup.server).await;
+
+        let report = setup.sync().await;
+        assert_eq!(report.conflicts, ["Buy soy milk"]);
+        assert_eq!(setup.names().await, ["Buy oat milk"]);
+    }
+
+    #[tokio::test]
+    async fn resources_without_a_task_are_ignored_until_they_change() {
+        let setup = synced().await;
+        let list = vec![
+            ("/cal/milk.ics", "1", vtodo("milk", "Buy milk")),
+            ("/cal/standup.ics", "1", vevent("standup")),
+        ];
+        setup.serve_both(list.clone()).await;
+        assert_eq!(setup.sync().await.pulled, 0);
+        assert_eq!(setup.names().await, ["Buy milk"]);
+        assert_eq!(setup.links().await, 2);
+
+        // nothing changed, so nothing is downloaded again
+        setup.serve(TaskList(list)).await;
+        let report = setup.sync().await;
+        assert_eq!((report.pulled, report.pushed, report.deleted), (0, 0, 0));
+        let requests = setup.server.received_requests().await.unwrap();
+        assert!(requests.iter().all(|r| r.method.as_str() != "REPORT"));
+
+        // until it changes
+        setup
+            .serve_both(vec![
+                ("/cal/milk.ics", "1", vtodo("milk", "Buy milk")),
+                ("/cal/standup.ics", "2", vtodo("standup", "Prepare standup")),
+            ])
+            .await;
+        assert_eq!(setup.sync().await.pulled, 1);
+        assert_eq!(setup.names().await, ["Buy milk", "Prepare standup"]);
+    }
+}
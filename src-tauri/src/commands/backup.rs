@@ -0,0 +1,52 @@
+use nowork_core::backup::{delete_backup_file, list_backups, prune, restore, run_daily_backups};
+use std::path::PathBuf;
+use tauri::{AppHandle, State};
+
+use crate::commands::{
+    database::{get_backup_dir, Database},
+    types::{BackupInfo, BackupKind},
+};
+use crate::error::NoWorkError;
+
+pub use nowork_core::backup::create_backup;
+
+// Runs the daily backup check for as long as the app is open
+pub fn schedule_daily_backups(db: Database, backup_dir: PathBuf) {
+    tauri::async_runtime::spawn(run_daily_backups(db, backup_dir));
+}
+
+#[tauri::command]
+pub async fn create_database_backup(app: AppHandle, db: State<'_, Database>) -> Result<BackupInfo, NoWorkError> {
+    let backup_dir = get_backup_dir(&app);
+    db.run(move |conn| create_backup(conn, &backup_dir, BackupKind::Manual)).await
+}
+
+#[tauri::command]
+pub async fn list_database_backups(app: AppHandle) -> Result<Vec<BackupInfo>, NoWorkError> {
+    let backup_dir = get_backup_dir(&app);
+    tokio::task::spawn_blocking(move || list_backups(&backup_dir)).await?
+}
+
+#[tauri::command]
+pub async fn delete_database_backup(app: AppHandle, file_name: String) -> Result<(), NoWorkError> {
+    let backup_dir = get_backup_dir(&app);
+    tokio::task::spawn_blocking(move || delete_backup_file(&backup_dir, &file_name)).await?
+}
+
+// Keeps the newest `keep` backups of each kind, or only of `kind` if given
+#[tauri::command]
+pub async fn prune_database_backups(
+    app: AppHandle,
+    kind: Option<BackupKind>,
+    keep: usize,
+) -> Result<Vec<BackupInfo>, NoWorkError> {
+    let backup_dir = get_backup_dir(&app);
+    tokio::task::spawn_blocking(move || prune(&backup_dir, kind, keep)).await?
+}
+
+#[tauri::command]
+pub async fn restore_database_backup(app: AppHandle, db: State<'_, Database>, file_name: String) -> Result<(), NoWorkError> {
+    let db = db.inner().clone();
+    let backup_dir = get_backup_dir(&app);
+    tokio::task::spawn_blocking(move || restore(&db, &backup_dir, &file_name)).await?
+}
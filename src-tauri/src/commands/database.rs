@@ -1,6 +1,10 @@
-use rusqlite::{Connection, Result};
 use std::path::PathBuf;
-use tauri::{AppHandle, Manager};
+use tauri::{AppHandle, Manager, State};
+
+use crate::commands::{backup, migrations, types::BackupKind};
+use crate::error::NoWorkError;
+
+pub use nowork_core::database::Database;
 
 pub fn get_db_path(app: &AppHandle) -> PathBuf {
     let base_dir = app
@@ -11,61 +15,39 @@ pub fn get_db_path(app: &AppHandle) -> PathBuf {
     base_dir.join("tasks.db")
 }
 
-pub fn init_db(app: &AppHandle) -> Result<()> {
-    let conn = Connection::open(get_db_path(app))?;
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS tasks (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            name TEXT NOT NULL,
-            due_date TEXT,
-            created_at TEXT DEFAULT (strftime('%Y-%m-%dT%H:%M:%fZ', 'now')),
-            completed BOOLEAN DEFAULT 0,
-            completed_at TEXT,
-            priority INTEGER
-        )",
-        [],
-    )?;
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS tags (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            name TEXT NOT NULL UNIQUE,
-            color TEXT DEFAULT 'default'
-        )",
-        [],
-    )?;
-
-    // 1.1 migration, adds the priority column
-    let _ = conn.execute(
-        "ALTER TABLE tasks ADD COLUMN priority INTEGER",
-        [],
-    );
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS task_tags (
-            task_id INTEGER NOT NULL,
-            tag_id INTEGER NOT NULL,
-            PRIMARY KEY (task_id, tag_id),
-            FOREIGN KEY (task_id) REFERENCES tasks(id) ON DELETE CASCADE,
-            FOREIGN KEY (tag_id) REFERENCES tags(id) ON DELETE CASCADE
-        )",
-        [],
-    )?;
-
-    Ok(())
+pub fn get_backup_dir(app: &AppHandle) -> PathBuf {
+    get_db_path(app)
+        .parent()
+        .expect("Database path has no parent")
+        .join("backups")
 }
 
-pub fn open_conn(app: &AppHandle) -> Result<Connection> {
-    Connection::open(get_db_path(app))
+// Opens the database and applies any pending schema migrations
+pub fn init_db(app: &AppHandle) -> Result<Database, NoWorkError> {
+    nowork_core::database::open(get_db_path(app), &get_backup_dir(app))
 }
 
 #[tauri::command]
-pub fn reset_database(app: AppHandle) -> Result<(), String> {
-    let db_path = get_db_path(&app);
-    if db_path.exists() {
-        std::fs::remove_file(&db_path).map_err(|e| e.to_string())?;
-    }
-    init_db(&app).map_err(|e| e.to_string())?;
-    Ok(())
+pub async fn reset_database(app: AppHandle, db: State<'_, Database>) -> Result<(), NoWorkError> {
+    let db = db.inner().clone();
+    let backup_dir = get_backup_dir(&app);
+
+    tokio::task::spawn_blocking(move || {
+        // refuse to reset if the current data could not be saved
+        backup::create_backup(&*db.get()?, &backup_dir, BackupKind::PreReset)?;
+        db.close_idle();
+
+        // WAL mode keeps recent writes in side files next to the database
+        for suffix in ["", "-wal", "-shm"] {
+            let mut path = db.path().as_os_str().to_owned();
+            path.push(suffix);
+            let path = PathBuf::from(path);
+            if path.exists() {
+                std::fs::remove_file(&path)?;
+            }
+        }
+
+        migrations::migrate(&mut *db.get()?, &backup_dir)
+    })
+    .await?
 }
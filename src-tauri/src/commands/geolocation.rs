@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::error::NoWorkError;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GeoPosition {
     pub lat: f64,
@@ -10,34 +12,34 @@ pub struct GeoPosition {
 }
 
 #[tauri::command]
-pub async fn get_ip_geoposition() -> Result<GeoPosition, String> {
+pub async fn get_ip_geoposition() -> Result<GeoPosition, NoWorkError> {
     let client = reqwest::Client::builder()
         .timeout(std::time::Duration::from_secs(5))
-        .build()
-        .map_err(|e| e.to_string())?;
+        .build()?;
 
     let resp = client
         .get("https://ipwho.is/?fields=success,message,latitude,longitude,city,country,region")
         .send()
-        .await
-        .map_err(|e| e.to_string())?;
+        .await?;
 
-    let json = resp.json::<serde_json::Value>().await.map_err(|e| e.to_string())?;
+    let json = resp.json::<serde_json::Value>().await?;
 
     println!("{:?}", json);
 
     if json["success"] == false {
-        return Err(json["message"]
-            .as_str()
-            .unwrap_or("geolocation failed")
-            .to_string());
+        return Err(NoWorkError::Network(
+            json["message"]
+                .as_str()
+                .unwrap_or("geolocation failed")
+                .to_string(),
+        ));
     }
 
     Ok(GeoPosition {
-        lat: json["latitude"].as_f64().ok_or("missing latitude")?,
-        lon: json["longitude"].as_f64().ok_or("missing longitude")?,
-        city: json["city"].as_str().ok_or("missing city")?.to_string(),
-        region: json["region"].as_str().ok_or("missing region")?.to_string(),
-        country: json["country"].as_str().ok_or("missing country")?.to_string()
+        lat: json["latitude"].as_f64().ok_or_else(|| NoWorkError::InvalidResponse("missing latitude".to_string()))?,
+        lon: json["longitude"].as_f64().ok_or_else(|| NoWorkError::InvalidResponse("missing longitude".to_string()))?,
+        city: json["city"].as_str().ok_or_else(|| NoWorkError::InvalidResponse("missing city".to_string()))?.to_string(),
+        region: json["region"].as_str().ok_or_else(|| NoWorkError::InvalidResponse("missing region".to_string()))?.to_string(),
+        country: json["country"].as_str().ok_or_else(|| NoWorkError::InvalidResponse("missing country".to_string()))?.to_string()
     })
 }
\ No newline at end of file
@@ -5,6 +5,8 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use tauri::{path::BaseDirectory, Manager};
 
+use crate::error::NoWorkError;
+
 #[derive(Debug, Deserialize, Serialize)]
 struct Greetings {
     #[serde(flatten)]
@@ -12,18 +14,18 @@ struct Greetings {
 }
 
 #[tauri::command]
-pub fn greet(app: tauri::AppHandle) -> Result<String, String> {
+pub fn greet(app: tauri::AppHandle) -> Result<String, NoWorkError> {
     // Try to get the app's data directory
     let resource_path = app
         .path()
         .resolve("assets/greeting.json", BaseDirectory::Resource)
-        .map_err(|e| format!("Failed to resolve resource: {}", e))?;
+        .map_err(|e| NoWorkError::Storage(format!("Failed to resolve resource: {}", e)))?;
 
     let json_file = std::fs::File::open(&resource_path)
-        .map_err(|e| format!("Failed to read greeting.json at {:?}", e))?;
+        .map_err(|e| NoWorkError::Storage(format!("Failed to read greeting.json at {:?}", e)))?;
 
     let greetings_data: Greetings = serde_json::from_reader(&json_file)
-        .map_err(|e| format!("Failed to parse to greetings data {:?}", e))?;
+        .map_err(|e| NoWorkError::Storage(format!("Failed to parse to greetings data {:?}", e)))?;
 
     let today: Weekday = Local::now().weekday();
 
@@ -40,11 +42,11 @@ pub fn greet(app: tauri::AppHandle) -> Result<String, String> {
     let greeting_for_day: &Vec<String> = greetings_data
         .greetings
         .get(day_name)
-        .ok_or_else(|| format!("No greetings found for day: {}", day_name))?;
+        .ok_or_else(|| NoWorkError::Storage(format!("No greetings found for day: {}", day_name)))?;
 
     let chosen_greeting: &String = greeting_for_day
         .choose(&mut rng())
-        .ok_or_else(|| format!("No greetings available for {}", day_name))?;
+        .ok_or_else(|| NoWorkError::Storage(format!("No greetings available for {}", day_name)))?;
 
     Ok(format!("{}", chosen_greeting))
 }
@@ -2,6 +2,12 @@ pub mod database;
 pub mod greet;
 pub mod tags;
 pub mod tasks;
-pub mod types;
 pub mod geolocation;
-pub mod priority;
\ No newline at end of file
+pub mod priority;
+pub mod recurrence;
+pub mod subtasks;
+pub mod search;
+pub mod query;
+pub mod backup;
+pub mod workspace;
+pub use nowork_core::{migrations, types};
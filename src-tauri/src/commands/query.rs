@@ -0,0 +1,12 @@
+use tauri::State;
+
+use crate::commands::types::{TaskPage, TaskQuery};
+use crate::error::NoWorkError;
+use crate::storage::Storage;
+
+pub use nowork_core::query::run_query;
+
+#[tauri::command]
+pub async fn query_tasks(storage: State<'_, Storage>, query: TaskQuery) -> Result<TaskPage, NoWorkError> {
+    storage.get().query_tasks(query).await
+}
@@ -0,0 +1,26 @@
+use tauri::State;
+
+use crate::commands::types::{Recurrence, Task, TaskChanges};
+use crate::error::NoWorkError;
+use crate::storage::Storage;
+
+pub use nowork_core::recurrence::{spawn_next_occurrence, validate};
+
+#[tauri::command]
+pub async fn update_task_recurrence_by_id(
+    storage: State<'_, Storage>,
+    task_id: i32,
+    new_recurrence: Option<Recurrence>,
+) -> Result<Task, NoWorkError> {
+    // checked here so an invalid rule is never queued for the server
+    let changes = TaskChanges {
+        recurrence: Some(new_recurrence.map(validate).transpose()?),
+        ..Default::default()
+    };
+    storage.get().update_task(task_id, changes).await
+}
+
+#[tauri::command]
+pub async fn clear_task_recurrence_by_id(storage: State<'_, Storage>, task_id: i32) -> Result<Task, NoWorkError> {
+    update_task_recurrence_by_id(storage, task_id, None).await
+}
@@ -0,0 +1,10 @@
+use crate::commands::types::{SearchQuery, SearchResult};
+use crate::error::NoWorkError;
+use crate::storage::Storage;
+use tauri::State;
+
+// Ranked full-text search over task names
+#[tauri::command]
+pub async fn search_tasks(storage: State<'_, Storage>, query: SearchQuery) -> Result<Vec<SearchResult>, NoWorkError> {
+    storage.get().search_tasks(query).await
+}
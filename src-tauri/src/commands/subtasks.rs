@@ -0,0 +1,27 @@
+use crate::commands::types::Task;
+use crate::error::NoWorkError;
+use crate::storage::Storage;
+use tauri::State;
+
+// Add a subtask to the end of a task's checklist
+#[tauri::command]
+pub async fn add_subtask(storage: State<'_, Storage>, parent_id: i32, name: String) -> Result<Task, NoWorkError> {
+    storage.get().add_subtask(parent_id, name).await
+}
+
+#[tauri::command]
+pub async fn get_subtasks(storage: State<'_, Storage>, parent_id: i32) -> Result<Vec<Task>, NoWorkError> {
+    storage.get().subtasks(parent_id).await
+}
+
+// Reorder a checklist, subtask_ids must list every subtask of the parent exactly once
+#[tauri::command]
+pub async fn reorder_subtasks(storage: State<'_, Storage>, parent_id: i32, subtask_ids: Vec<i32>) -> Result<Vec<Task>, NoWorkError> {
+    storage.get().reorder_subtasks(parent_id, subtask_ids).await
+}
+
+// Tick or untick a checklist item
+#[tauri::command]
+pub async fn set_subtask_completed(storage: State<'_, Storage>, subtask_id: i32, completed: bool) -> Result<Task, NoWorkError> {
+    storage.get().set_subtask_completed(subtask_id, completed).await
+}
@@ -0,0 +1,464 @@
+use chrono::Utc;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use tauri::{AppHandle, State};
+
+use crate::commands::{
+    backup,
+    database::{get_backup_dir, Database},
+    recurrence,
+    tasks::load_tasks,
+    types::{
+        BackupKind, ImportMode, ImportRecordError, ImportReport, Tag, Task, TaskTagLink, WorkspaceExport,
+    },
+};
+use crate::error::NoWorkError;
+
+const EXPORT_FORMAT: &str = "nowork-workspace";
+// bump when the document layout changes, import accepts anything up to this
+const EXPORT_VERSION: u32 = 1;
+
+// Reads the whole workspace into an export document
+pub fn export(conn: &Connection) -> Result<WorkspaceExport, NoWorkError> {
+    let mut stmt = conn.prepare("SELECT id, name, color FROM tags ORDER BY id")?;
+    let tags = stmt
+        .query_map([], |row| {
+            Ok(Tag {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                color: row.get(2)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<Tag>>>()?;
+
+    let tasks = load_tasks(conn, "ORDER BY tasks.id", [])?
+        .into_iter()
+        .map(|task| Task {
+            tags: None,
+            subtask_progress: None,
+            ..task
+        })
+        .collect();
+
+    let mut stmt = conn.prepare("SELECT task_id, tag_id FROM task_tags ORDER BY task_id, tag_id")?;
+    let task_tags = stmt
+        .query_map([], |row| {
+            Ok(TaskTagLink {
+                task_id: row.get(0)?,
+                tag_id: row.get(1)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<TaskTagLink>>>()?;
+
+    Ok(WorkspaceExport {
+        format: EXPORT_FORMAT.to_string(),
+        version: EXPORT_VERSION,
+        exported_at: Utc::now(),
+        tags,
+        tasks,
+        task_tags,
+    })
+}
+
+// The document is read loosely first so one malformed record is reported
+// on its own instead of failing the whole import
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RawImport {
+    format: Option<String>,
+    version: u32,
+    #[serde(default)]
+    tags: Vec<serde_json::Value>,
+    #[serde(default)]
+    tasks: Vec<serde_json::Value>,
+    #[serde(default)]
+    task_tags: Vec<serde_json::Value>,
+}
+
+fn record_error(report: &mut ImportReport, section: &str, index: usize, id: Option<i64>, message: impl Into<String>) {
+    report.errors.push(ImportRecordError {
+        section: section.to_string(),
+        index,
+        id,
+        message: message.into(),
+    });
+}
+
+fn parse_records<T: DeserializeOwned>(
+    values: Vec<serde_json::Value>,
+    section: &str,
+    report: &mut ImportReport,
+) -> Vec<(usize, T)> {
+    let mut records = Vec::new();
+    for (index, value) in values.into_iter().enumerate() {
+        let id = value.get("id").and_then(|id| id.as_i64());
+        match serde_json::from_value(value) {
+            Ok(record) => records.push((index, record)),
+            Err(e) => record_error(report, section, index, id, e.to_string()),
+        }
+    }
+    records
+}
+
+// Imports an export document inside one transaction. Invalid records are
+// skipped and listed in the report, storage errors roll everything back.
+pub fn import(conn: &mut Connection, json: &str, mode: ImportMode) -> Result<ImportReport, NoWorkError> {
+    let raw: RawImport = serde_json::from_str(json)
+        .map_err(|e| NoWorkError::validation(format!("Not a NoWork export: {}", e)))?;
+
+    if raw.format.as_deref().is_some_and(|f| f != EXPORT_FORMAT) {
+        return Err(NoWorkError::validation("Not a NoWork export"));
+    }
+    if raw.version > EXPORT_VERSION {
+        return Err(NoWorkError::validation(format!(
+            "This export was made by a newer version of NoWork (format {}), please update NoWork",
+            raw.version
+        )));
+    }
+
+    let mut report = ImportReport::default();
+    let tags: Vec<(usize, Tag)> = parse_records(raw.tags, "tags", &mut report);
+    let tasks: Vec<(usize, Task)> = parse_records(raw.tasks, "tasks", &mut report);
+    let links: Vec<(usize, TaskTagLink)> = parse_records(raw.task_tags, "taskTags", &mut report);
+
+    let tx = conn.transaction()?;
+
+    if mode == ImportMode::Replace {
+        // task_tags rows go with them through ON DELETE CASCADE
+        tx.execute("DELETE FROM tasks", [])?;
+        tx.execute("DELETE FROM tags", [])?;
+    }
+
+    // document tag id -> database tag id
+    let mut tag_ids: HashMap<i64, i64> = HashMap::new();
+    for (index, tag) in tags {
+        let name = tag.name.trim();
+        if name.is_empty() {
+            record_error(&mut report, "tags", index, Some(tag.id), "Tag name is empty");
+            continue;
+        }
+        if tag_ids.contains_key(&tag.id) {
+            record_error(&mut report, "tags", index, Some(tag.id), "Duplicate tag id");
+            continue;
+        }
+
+        let existing: Option<i64> = tx
+            .query_row("SELECT id FROM tags WHERE name = ?1", params![name], |row| row.get(0))
+            .optional()?;
+
+        let new_id = match existing {
+            Some(id) => {
+                report.tags_merged += 1;
+                id
+            }
+            None => {
+                tx.execute("INSERT INTO tags (name, color) VALUES (?1, ?2)", params![name, tag.color])?;
+                report.tags_created += 1;
+                tx.last_insert_rowid()
+            }
+        };
+        tag_ids.insert(tag.id, new_id);
+    }
+
+    // parents are inserted before their subtasks so parent ids can be remapped
+    let (top_level, subtasks): (Vec<_>, Vec<_>) = tasks.into_iter().partition(|(_, task)| task.parent_id.is_none());
+    let nested: HashSet<i32> = subtasks.iter().map(|(_, task)| task.id).collect();
+
+    // document task id -> database task id
+    let mut task_ids: HashMap<i32, i64> = HashMap::new();
+    let mut subtask_ids: HashMap<i32, i64> = HashMap::new();
+    for (index, task) in top_level.into_iter().chain(subtasks) {
+        let id = Some(task.id as i64);
+
+        if task.name.trim().is_empty() {
+            record_error(&mut report, "tasks", index, id, "Task name is empty");
+            continue;
+        }
+        if task_ids.contains_key(&task.id) || subtask_ids.contains_key(&task.id) {
+            record_error(&mut report, "tasks", index, id, "Duplicate task id");
+            continue;
+        }
+
+        let parent_id = match task.parent_id {
+            None => None,
+            Some(parent) => match task_ids.get(&parent) {
+                Some(new_parent) => Some(*new_parent),
+                None if nested.contains(&parent) => {
+                    record_error(&mut report, "tasks", index, id, "Subtasks cannot have subtasks of their own");
+                    continue;
+                }
+                None => {
+                    record_error(&mut report, "tasks", index, id, format!("Parent task {} is not in the import", parent));
+                    continue;
+                }
+            },
+        };
+
+        let recurrence = match task.recurrence.map(recurrence::validate).transpose() {
+            Ok(recurrence) => recurrence,
+            Err(e) => {
+                record_error(&mut report, "tasks", index, id, e.to_string());
+                continue;
+            }
+        };
+
+        tx.execute(
+            "INSERT INTO tasks (name, due_date, created_at, completed, completed_at, priority,
+                                recurrence_rule, recurrence_mode, parent_id, position)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                task.name,
+                task.due_date.map(|dt| dt.to_rfc3339()),
+                task.created_at.to_rfc3339(),
+                task.completed,
+                task.completed_at.map(|dt| dt.to_rfc3339()),
+                task.priority.map(|p| p.as_i32()),
+                recurrence.as_ref().map(|r| r.rule.clone()),
+                recurrence.as_ref().map(|r| r.mode.as_i32()),
+                parent_id,
+                task.position
+            ],
+        )?;
+
+        let new_id = tx.last_insert_rowid();
+        if parent_id.is_some() {
+            subtask_ids.insert(task.id, new_id);
+        } else {
+            task_ids.insert(task.id, new_id);
+        }
+        report.tasks_imported += 1;
+    }
+
+    for (index, link) in links {
+        let task_id = task_ids.get(&link.task_id).or_else(|| subtask_ids.get(&link.task_id));
+        let Some(task_id) = task_id else {
+            record_error(&mut report, "taskTags", index, None, format!("Task {} is not in the import", link.task_id));
+            continue;
+        };
+        let Some(tag_id) = tag_ids.get(&link.tag_id) else {
+            record_error(&mut report, "taskTags", index, None, format!("Tag {} is not in the import", link.tag_id));
+            continue;
+        };
+
+        report.links_imported += tx.execute(
+            "INSERT OR IGNORE INTO task_tags (task_id, tag_id) VALUES (?1, ?2)",
+            params![task_id, tag_id],
+        )?;
+    }
+
+    tx.commit()?;
+
+    // records are validated out of order (parents first), report them in document order
+    let section_order = |section: &str| ["tags", "tasks", "taskTags"].iter().position(|s| *s == section);
+    report.errors.sort_by_key(|e| (section_order(&e.section), e.index));
+    Ok(report)
+}
+
+// Writes the workspace as pretty printed JSON to path
+#[tauri::command]
+pub async fn export_workspace(db: State<'_, Database>, path: String) -> Result<(), NoWorkError> {
+    db.run(move |conn| {
+        let document = export(conn)?;
+        let file = std::fs::File::create(&path)?;
+        serde_json::to_writer_pretty(std::io::BufWriter::new(file), &document)?;
+        Ok(())
+    })
+    .await
+}
+
+// Imports a file written by export_workspace, the current data is backed up first
+#[tauri::command]
+pub async fn import_workspace(
+    app: AppHandle,
+    db: State<'_, Database>,
+    path: String,
+    mode: ImportMode,
+) -> Result<ImportReport, NoWorkError> {
+    let backup_dir = get_backup_dir(&app);
+
+    db.run(move |conn| {
+        let json = std::fs::read_to_string(&path)?;
+        backup::create_backup(conn, &backup_dir, BackupKind::PreImport)?;
+        import(conn, &json, mode)
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::{
+        migrations,
+        types::{NewTag, NewTask},
+    };
+    use serde_json::json;
+    use std::path::Path;
+
+    fn database() -> Connection {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.pragma_update(None, "foreign_keys", true).unwrap();
+        migrations::migrate(&mut conn, Path::new("unused")).unwrap();
+        conn
+    }
+
+    fn add_task(conn: &mut Connection, name: &str, tags: &[(&str, &str)]) -> i32 {
+        let tags = tags
+            .iter()
+            .map(|(name, color)| NewTag {
+                name: name.to_string(),
+                color: color.to_string(),
+            })
+            .collect();
+        nowork_core::tasks::add_task(
+            conn,
+            NewTask {
+                name: name.to_string(),
+                due_date: None,
+                priority: None,
+                tags: Some(tags),
+                recurrence: None,
+            },
+        )
+        .unwrap()
+    }
+
+    // name, parent name, recurrence rule and tag names of every task
+    fn snapshot(conn: &Connection) -> Vec<(String, Option<String>, Option<String>, String)> {
+        let mut stmt = conn
+            .prepare(
+                "SELECT tasks.name, parent.name, tasks.recurrence_rule,
+                        COALESCE((SELECT group_concat(name, ',') FROM (
+                            SELECT tags.name FROM task_tags JOIN tags ON tags.id = task_tags.tag_id
+                            WHERE task_tags.task_id = tasks.id ORDER BY tags.name)), '')
+                 FROM tasks LEFT JOIN tasks AS parent ON parent.id = tasks.parent_id
+                 ORDER BY tasks.name",
+            )
+            .unwrap();
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .unwrap()
+    }
+
+    fn tag_colors(conn: &Connection) -> Vec<(String, String)> {
+        let mut stmt = conn.prepare("SELECT name, color FROM tags ORDER BY name").unwrap();
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .unwrap()
+    }
+
+    fn task(id: i32, name: &str, parent_id: Option<i32>) -> serde_json::Value {
+        json!({ "id": id, "name": name, "createdAt": "2026-01-01T00:00:00Z", "parentId": parent_id })
+    }
+
+    #[test]
+    fn export_then_import_round_trips() {
+        let mut source = database();
+        let trip = add_task(&mut source, "Plan trip", &[("travel", "blue"), ("family", "green")]);
+        add_task(&mut source, "Water plants", &[("home", "red")]);
+        nowork_core::subtasks::add_subtask(&mut source, trip, "Book flights").unwrap();
+        source
+            .execute("UPDATE tasks SET recurrence_rule = 'FREQ=WEEKLY', recurrence_mode = 0 WHERE name = 'Water plants'", [])
+            .unwrap();
+        let json = serde_json::to_string(&export(&source).unwrap()).unwrap();
+
+        // ids in the target are taken already, so every one is remapped
+        let mut target = database();
+        add_task(&mut target, "Placeholder", &[("placeholder", "gray")]);
+        let report = import(&mut target, &json, ImportMode::Replace).unwrap();
+
+        assert!(report.errors.is_empty(), "{:?}", report.errors.iter().map(|e| &e.message).collect::<Vec<_>>());
+        assert_eq!((report.tags_created, report.tasks_imported, report.links_imported), (3, 3, 3));
+        assert_eq!(snapshot(&target), snapshot(&source));
+        assert_eq!(tag_colors(&target), tag_colors(&source));
+    }
+
+    #[test]
+    fn merge_keeps_existing_data_and_reuses_tags_by_name() {
+        let document = json!({
+            "format": "nowork-workspace",
+            "version": 1,
+            "tags": [{ "id": 7, "name": "work", "color": "blue" }, { "id": 8, "name": "home", "color": "green" }],
+            "tasks": [task(1, "Write report", None)],
+            "taskTags": [{ "taskId": 1, "tagId": 7 }, { "taskId": 1, "tagId": 8 }],
+        })
+        .to_string();
+
+        let mut merged = database();
+        add_task(&mut merged, "Existing", &[("work", "red")]);
+        let report = import(&mut merged, &document, ImportMode::Merge).unwrap();
+        assert_eq!((report.tags_created, report.tags_merged, report.links_imported), (1, 1, 2));
+        let names: Vec<String> = snapshot(&merged).into_iter().map(|t| t.0).collect();
+        assert_eq!(names, ["Existing", "Write report"]);
+        assert_eq!(snapshot(&merged)[1].3, "home,work");
+        // a merged tag keeps its own color
+        assert_eq!(tag_colors(&merged), [("home".to_string(), "green".to_string()), ("work".to_string(), "red".to_string())]);
+
+        let mut replaced = database();
+        add_task(&mut replaced, "Existing", &[("work", "red")]);
+        let report = import(&mut replaced, &document, ImportMode::Replace).unwrap();
+        assert_eq!((report.tags_created, report.tags_merged), (2, 0));
+        let names: Vec<String> = snapshot(&replaced).into_iter().map(|t| t.0).collect();
+        assert_eq!(names, ["Write report"]);
+        assert_eq!(tag_colors(&replaced)[1], ("work".to_string(), "blue".to_string()));
+    }
+
+    #[test]
+    fn invalid_records_are_skipped_and_reported_in_document_order() {
+        let mut bad_rule = task(4, "Bad rule", None);
+        bad_rule["recurrence"] = json!({ "rule": "FREQ=SOMETIMES", "mode": "fixedSchedule" });
+        let document = json!({
+            "format": "nowork-workspace",
+            "version": 1,
+            "tags": [{ "id": 1, "name": "work", "color": "blue" }, { "id": 1, "name": "again", "color": "red" }],
+            "tasks": [
+                task(5, "Subtask", Some(1)),
+                task(1, "Parent", None),
+                task(2, "Orphan", Some(99)),
+                task(1, "Same id", None),
+                bad_rule,
+                task(6, "Nested", Some(5)),
+                { "id": 7 },
+            ],
+            "taskTags": [{ "taskId": 2, "tagId": 1 }, { "taskId": 1, "tagId": 3 }, { "taskId": 5, "tagId": 1 }],
+        })
+        .to_string();
+
+        let mut conn = database();
+        let report = import(&mut conn, &document, ImportMode::Merge).unwrap();
+
+        let errors: Vec<(&str, usize, &str)> = report
+            .errors
+            .iter()
+            .map(|e| (e.section.as_str(), e.index, e.message.as_str()))
+            .collect();
+        assert_eq!(errors.len(), 8, "{:?}", errors);
+        assert_eq!(errors[0], ("tags", 1, "Duplicate tag id"));
+        assert_eq!(errors[1], ("tasks", 2, "Parent task 99 is not in the import"));
+        assert_eq!(errors[2], ("tasks", 3, "Duplicate task id"));
+        assert_eq!((errors[3].0, errors[3].1), ("tasks", 4));
+        assert_eq!(errors[4], ("tasks", 5, "Subtasks cannot have subtasks of their own"));
+        // a record that is not a task at all
+        assert_eq!((errors[5].0, errors[5].1), ("tasks", 6));
+        assert_eq!(errors[6], ("taskTags", 0, "Task 2 is not in the import"));
+        assert_eq!(errors[7], ("taskTags", 1, "Tag 3 is not in the import"));
+
+        assert_eq!((report.tasks_imported, report.links_imported), (2, 1));
+        let tasks = snapshot(&conn);
+        assert_eq!(tasks[0], ("Parent".to_string(), None, None, String::new()));
+        assert_eq!(tasks[1], ("Subtask".to_string(), Some("Parent".to_string()), None, "work".to_string()));
+    }
+
+    #[test]
+    fn rejects_other_documents() {
+        let mut conn = database();
+        assert!(import(&mut conn, "[]", ImportMode::Merge).is_err());
+        let other = json!({ "format": "something-else", "version": 1 }).to_string();
+        assert!(import(&mut conn, &other, ImportMode::Merge).is_err());
+        let newer = json!({ "format": "nowork-workspace", "version": EXPORT_VERSION + 1 }).to_string();
+        assert!(import(&mut conn, &newer, ImportMode::Replace).is_err());
+    }
+}
@@ -1,7 +1,17 @@
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
 mod commands;
 mod calendar;
-use crate::{calendar::cal_credentials::init_keyring, commands::database::init_db};
+mod storage;
+use nowork_core::error;
+use crate::{
+    calendar::cal_credentials::init_keyring,
+    commands::{
+        backup::schedule_daily_backups,
+        database::{get_backup_dir, init_db},
+    },
+    storage::{schedule_flush, Storage},
+};
+use tauri::Manager;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
@@ -16,14 +26,19 @@ pub fn run() {
         .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_store::Builder::new().build())
         .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_log::Builder::new().level(log::LevelFilter::Info).build())
         .setup(|app| {
-            let handle = app.handle();
-            init_db(&handle)?;
+            let db = init_db(app.handle())?;
+            schedule_daily_backups(db.clone(), get_backup_dir(app.handle()));
+            app.manage(Storage::load(db.clone())?);
+            app.manage(db);
+            schedule_flush(app.handle().clone());
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             commands::greet::greet,
             commands::tasks::add_database_task,
+            commands::tasks::parse_quick_add,
             commands::tasks::get_all_tasks,
             commands::tasks::complete_task,
             commands::tasks::delete_task,
@@ -43,10 +58,45 @@ pub fn run() {
             commands::tags::update_tag_color,
             commands::geolocation::get_ip_geoposition,
             commands::priority::update_task_priority_by_id,
-            calendar::authtest::test_auth,
-            calendar::cal_credentials::save_credentials,
-            calendar::cal_credentials::load_credentials,
-            calendar::cal_credentials::delete_credentials
+            commands::recurrence::update_task_recurrence_by_id,
+            commands::recurrence::clear_task_recurrence_by_id,
+            commands::subtasks::add_subtask,
+            commands::subtasks::get_subtasks,
+            commands::subtasks::reorder_subtasks,
+            commands::subtasks::set_subtask_completed,
+            commands::search::search_tasks,
+            commands::query::query_tasks,
+            commands::backup::create_database_backup,
+            commands::backup::list_database_backups,
+            commands::backup::delete_database_backup,
+            commands::backup::prune_database_backups,
+            commands::backup::restore_database_backup,
+            commands::workspace::export_workspace,
+            commands::workspace::import_workspace,
+            storage::get_storage_status,
+            storage::use_remote_storage,
+            storage::use_local_storage,
+            storage::retry_failed_change,
+            storage::discard_failed_change,
+            calendar::cache::sync_calendars,
+            calendar::cache::fetch_events,
+            calendar::cache::list_calendars,
+            calendar::accounts::add_calendar_account,
+            calendar::accounts::list_calendar_accounts,
+            calendar::accounts::remove_calendar_account,
+            calendar::accounts::set_calendar_enabled,
+            calendar::events::create_event,
+            calendar::events::get_event,
+            calendar::events::update_event,
+            calendar::events::delete_event,
+            calendar::subscriptions::subscribe_calendar,
+            calendar::subscriptions::import_calendar,
+            calendar::subscriptions::remove_local_calendar,
+            calendar::task_export::export_tasks,
+            calendar::todo_sync::sync_tasks,
+            calendar::todo_sync::set_task_calendar,
+            calendar::todo_sync::get_task_calendar,
+            calendar::accounts::discover_calendars
 
         ])
         .run(tauri::generate_context!())
@@ -0,0 +1,103 @@
+use async_trait::async_trait;
+use nowork_core::{query::run_query, search, subtasks, tags, tasks, tasks::TaskView};
+
+use crate::commands::{
+    database::Database,
+    types::{NewTag, NewTask, SearchQuery, SearchResult, Tag, Task, TaskChanges, TaskPage, TaskQuery},
+};
+use crate::error::NoWorkError;
+use crate::storage::TaskStore;
+
+// Tasks in this device's tasks.db
+pub struct LocalStore {
+    db: Database,
+}
+
+impl LocalStore {
+    pub fn new(db: Database) -> Self {
+        LocalStore { db }
+    }
+}
+
+#[async_trait]
+impl TaskStore for LocalStore {
+    async fn add_task(&self, task: NewTask) -> Result<(), NoWorkError> {
+        self.db.run(move |conn| tasks::add_task(conn, task).map(|_| ())).await
+    }
+
+    async fn tasks(&self, view: TaskView) -> Result<Vec<Task>, NoWorkError> {
+        self.db.run(move |conn| Ok(run_query(conn, &view.query())?.tasks)).await
+    }
+
+    async fn query_tasks(&self, query: TaskQuery) -> Result<TaskPage, NoWorkError> {
+        self.db.run(move |conn| run_query(conn, &query)).await
+    }
+
+    async fn task(&self, task_id: i32) -> Result<Task, NoWorkError> {
+        self.db.run(move |conn| tasks::load_task(conn, task_id)).await
+    }
+
+    async fn update_task(&self, task_id: i32, changes: TaskChanges) -> Result<Task, NoWorkError> {
+        self.db.run(move |conn| tasks::update_task(conn, task_id, changes)).await
+    }
+
+    async fn complete_task(&self, task_id: i32, complete_subtasks: bool) -> Result<(), NoWorkError> {
+        self.db
+            .run(move |conn| tasks::complete_task(conn, task_id, complete_subtasks))
+            .await
+    }
+
+    async fn delete_task(&self, task_id: i32) -> Result<(), NoWorkError> {
+        self.db.run(move |conn| tasks::delete_task(conn, task_id)).await
+    }
+
+    async fn tags(&self) -> Result<Vec<Tag>, NoWorkError> {
+        self.db.run(|conn| tags::list_tags(conn)).await
+    }
+
+    async fn add_tag(&self, new_tag: NewTag) -> Result<Tag, NoWorkError> {
+        self.db.run(move |conn| tags::add_tag(conn, new_tag)).await
+    }
+
+    async fn remove_tag(&self, tag_name: String) -> Result<(), NoWorkError> {
+        self.db.run(move |conn| tags::remove_tag(conn, &tag_name)).await
+    }
+
+    async fn update_tag_color(&self, tag_id: i32, color: String) -> Result<(), NoWorkError> {
+        self.db.run(move |conn| tags::update_tag_color(conn, tag_id, &color)).await
+    }
+
+    async fn add_tag_to_task(&self, task_id: i32, tag_id: i32) -> Result<(), NoWorkError> {
+        self.db.run(move |conn| tags::add_tag_to_task(conn, task_id, tag_id)).await
+    }
+
+    async fn remove_tag_from_task(&self, task_id: i32, tag_id: i32) -> Result<(), NoWorkError> {
+        self.db
+            .run(move |conn| tags::remove_tag_from_task(conn, task_id, tag_id))
+            .await
+    }
+
+    async fn add_subtask(&self, parent_id: i32, name: String) -> Result<Task, NoWorkError> {
+        self.db.run(move |conn| subtasks::add_subtask(conn, parent_id, &name)).await
+    }
+
+    async fn subtasks(&self, parent_id: i32) -> Result<Vec<Task>, NoWorkError> {
+        self.db.run(move |conn| tasks::load_subtasks(conn, parent_id)).await
+    }
+
+    async fn reorder_subtasks(&self, parent_id: i32, subtask_ids: Vec<i32>) -> Result<Vec<Task>, NoWorkError> {
+        self.db
+            .run(move |conn| subtasks::reorder_subtasks(conn, parent_id, &subtask_ids))
+            .await
+    }
+
+    async fn set_subtask_completed(&self, subtask_id: i32, completed: bool) -> Result<Task, NoWorkError> {
+        self.db
+            .run(move |conn| subtasks::set_subtask_completed(conn, subtask_id, completed))
+            .await
+    }
+
+    async fn search_tasks(&self, query: SearchQuery) -> Result<Vec<SearchResult>, NoWorkError> {
+        self.db.run(move |conn| search::search_tasks(conn, &query)).await
+    }
+}
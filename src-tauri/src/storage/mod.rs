@@ -0,0 +1,247 @@
+pub mod local;
+pub mod remote;
+
+use async_trait::async_trait;
+use nowork_core::tasks::TaskView;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+use tauri::{AppHandle, Manager, State};
+
+use crate::commands::{
+    database::Database,
+    types::{NewTag, NewTask, SearchQuery, SearchResult, Tag, Task, TaskChanges, TaskPage, TaskQuery},
+};
+use crate::error::NoWorkError;
+use crate::storage::{
+    local::LocalStore,
+    remote::{FailedChange, RemoteStore},
+};
+
+// how often changes made offline are retried while the app is open
+const FLUSH_INTERVAL: Duration = Duration::from_secs(60);
+
+// Where the task, subtask, tag and search commands read and write. Backups,
+// workspace files and calendars always use this device's database.
+#[async_trait]
+pub trait TaskStore: Send + Sync {
+    async fn add_task(&self, task: NewTask) -> Result<(), NoWorkError>;
+    async fn tasks(&self, view: TaskView) -> Result<Vec<Task>, NoWorkError>;
+    async fn query_tasks(&self, query: TaskQuery) -> Result<TaskPage, NoWorkError>;
+    async fn task(&self, task_id: i32) -> Result<Task, NoWorkError>;
+    async fn update_task(&self, task_id: i32, changes: TaskChanges) -> Result<Task, NoWorkError>;
+    async fn complete_task(&self, task_id: i32, complete_subtasks: bool) -> Result<(), NoWorkError>;
+    async fn delete_task(&self, task_id: i32) -> Result<(), NoWorkError>;
+    async fn tags(&self) -> Result<Vec<Tag>, NoWorkError>;
+    async fn add_tag(&self, new_tag: NewTag) -> Result<Tag, NoWorkError>;
+    async fn remove_tag(&self, tag_name: String) -> Result<(), NoWorkError>;
+    async fn update_tag_color(&self, tag_id: i32, color: String) -> Result<(), NoWorkError>;
+    async fn add_tag_to_task(&self, task_id: i32, tag_id: i32) -> Result<(), NoWorkError>;
+    async fn remove_tag_from_task(&self, task_id: i32, tag_id: i32) -> Result<(), NoWorkError>;
+    async fn add_subtask(&self, parent_id: i32, name: String) -> Result<Task, NoWorkError>;
+    async fn subtasks(&self, parent_id: i32) -> Result<Vec<Task>, NoWorkError>;
+    async fn reorder_subtasks(&self, parent_id: i32, subtask_ids: Vec<i32>) -> Result<Vec<Task>, NoWorkError>;
+    async fn set_subtask_completed(&self, subtask_id: i32, completed: bool) -> Result<Task, NoWorkError>;
+    async fn search_tasks(&self, query: SearchQuery) -> Result<Vec<SearchResult>, NoWorkError>;
+
+    // Sends changes that could not be delivered yet, returns how many are left
+    async fn flush(&self) -> Result<i64, NoWorkError> {
+        Ok(0)
+    }
+}
+
+// The active TaskStore, registered as Tauri state next to the Database
+pub struct Storage {
+    db: Database,
+    current: RwLock<Arc<dyn TaskStore>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StorageStatus {
+    // None while tasks are kept on this device
+    pub server_url: Option<String>,
+    // changes made offline that have not reached the server yet
+    pub pending_changes: i64,
+    // changes the server rejected, kept until they are retried or discarded
+    pub failed_changes: Vec<FailedChange>,
+}
+
+impl StorageStatus {
+    // everything on this device the server has not accepted
+    fn unsent(&self) -> usize {
+        self.pending_changes as usize + self.failed_changes.len()
+    }
+}
+
+fn saved_server(conn: &Connection) -> Result<Option<String>, NoWorkError> {
+    Ok(conn
+        .query_row("SELECT url FROM remote_server WHERE id = 1", [], |row| row.get(0))
+        .optional()?)
+}
+
+fn status(conn: &Connection) -> Result<StorageStatus, NoWorkError> {
+    Ok(StorageStatus {
+        server_url: saved_server(conn)?,
+        pending_changes: remote::pending_changes(conn)?,
+        failed_changes: remote::failed_changes(conn)?,
+    })
+}
+
+impl Storage {
+    // Uses the server chosen in settings, if any
+    pub fn load(db: Database) -> Result<Self, NoWorkError> {
+        let store: Arc<dyn TaskStore> = match saved_server(&*db.get()?)? {
+            Some(url) => {
+                // without its token every request fails with invalid_token,
+                // and settings can ask for it again
+                let token = remote::load_token(&url).unwrap_or_else(|e| {
+                    log::warn!("Could not load the token for {}: {}", url, e);
+                    String::new()
+                });
+                Arc::new(RemoteStore::new(db.clone(), &url, token)?)
+            }
+            None => Arc::new(LocalStore::new(db.clone())),
+        };
+
+        Ok(Storage {
+            db,
+            current: RwLock::new(store),
+        })
+    }
+
+    pub fn get(&self) -> Arc<dyn TaskStore> {
+        self.current
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn set(&self, store: Arc<dyn TaskStore>) {
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = store;
+    }
+}
+
+// Retries changes made offline for as long as the app is open
+pub fn schedule_flush(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(FLUSH_INTERVAL).await;
+            if let Err(e) = app.state::<Storage>().get().flush().await {
+                log::warn!("Sending offline changes failed: {}", e);
+            }
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn get_storage_status(storage: State<'_, Storage>) -> Result<StorageStatus, NoWorkError> {
+    storage.db.run(|conn| status(conn)).await
+}
+
+// Keeps tasks on a nowork-server from now on. The address and token are
+// checked first, the tasks on this device stay where they are.
+#[tauri::command]
+pub async fn use_remote_storage(
+    storage: State<'_, Storage>,
+    server_url: String,
+    token: String,
+) -> Result<StorageStatus, NoWorkError> {
+    let server_url = server_url.trim().trim_end_matches('/').to_string();
+    let token = token.trim().to_string();
+    if server_url.is_empty() || token.is_empty() {
+        return Err(NoWorkError::validation("Enter the server address and its token"));
+    }
+
+    let current = storage.db.run(|conn| status(conn)).await?;
+    if current.unsent() > 0 && current.server_url.as_deref() != Some(server_url.as_str()) {
+        return Err(NoWorkError::validation(format!(
+            "{} changes have not reached {} yet",
+            current.unsent(),
+            current.server_url.unwrap_or_default()
+        )));
+    }
+
+    let store = RemoteStore::new(storage.db.clone(), &server_url, token.clone())?;
+    store.check().await?;
+    remote::save_token(&server_url, &token)?;
+
+    let url = server_url.clone();
+    let status = storage
+        .db
+        .run(move |conn| {
+            conn.execute(
+                "INSERT OR REPLACE INTO remote_server (id, url) VALUES (1, ?1)",
+                params![url],
+            )?;
+            // cached answers of another server must not stand in for this one
+            conn.execute("DELETE FROM remote_cache", [])?;
+            status(conn)
+        })
+        .await?;
+
+    if let Some(previous) = current.server_url.filter(|url| *url != server_url) {
+        remote::delete_token(&previous)?;
+    }
+    storage.set(Arc::new(store));
+    Ok(status)
+}
+
+// Goes back to the tasks on this device. Changes still waiting for the
+// server, failed ones included, are only thrown away with discard_pending set.
+#[tauri::command]
+pub async fn use_local_storage(
+    storage: State<'_, Storage>,
+    discard_pending: Option<bool>,
+) -> Result<StorageStatus, NoWorkError> {
+    let discard = discard_pending.unwrap_or(false);
+    let (previous, status) = storage
+        .db
+        .run(move |conn| {
+            let current = status(conn)?;
+            if current.unsent() > 0 && !discard {
+                return Err(NoWorkError::validation(format!(
+                    "{} changes have not reached the server yet",
+                    current.unsent()
+                )));
+            }
+
+            let tx = conn.transaction()?;
+            tx.execute("DELETE FROM remote_server", [])?;
+            tx.execute("DELETE FROM remote_queue", [])?;
+            tx.execute("DELETE FROM remote_cache", [])?;
+            tx.commit()?;
+            Ok((current.server_url, status(conn)?))
+        })
+        .await?;
+
+    if let Some(url) = previous {
+        remote::delete_token(&url)?;
+    }
+    storage.set(Arc::new(LocalStore::new(storage.db.clone())));
+    Ok(status)
+}
+
+// Sends a change the server rejected once more, e.g. after fixing the
+// problem on the server. It keeps its place among the queued changes.
+#[tauri::command]
+pub async fn retry_failed_change(storage: State<'_, Storage>, change_id: i64) -> Result<StorageStatus, NoWorkError> {
+    storage.db.run(move |conn| remote::retry_failed(conn, change_id)).await?;
+    if let Err(e) = storage.get().flush().await {
+        log::warn!("Sending offline changes failed: {}", e);
+    }
+    storage.db.run(|conn| status(conn)).await
+}
+
+// Gives up on a change the server rejected
+#[tauri::command]
+pub async fn discard_failed_change(storage: State<'_, Storage>, change_id: i64) -> Result<StorageStatus, NoWorkError> {
+    storage
+        .db
+        .run(move |conn| {
+            remote::discard_failed(conn, change_id)?;
+            status(conn)
+        })
+        .await
+}
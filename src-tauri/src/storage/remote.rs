@@ -0,0 +1,740 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use keyring_core::{Entry, Error};
+use nowork_core::tasks::TaskView;
+use reqwest::{Client, Method, StatusCode, Url};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::commands::{
+    database::Database,
+    types::{NewTag, NewTask, SearchQuery, SearchResult, Tag, Task, TaskChanges, TaskPage, TaskQuery},
+};
+use crate::error::NoWorkError;
+use crate::storage::TaskStore;
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+const TOKEN_SERVICE: &str = "NoWork server";
+const IDEMPOTENCY_KEY: &str = "Idempotency-Key";
+
+// API tokens are kept in the system keyring, keyed by server address
+fn token_entry(server_url: &str) -> Result<Entry, NoWorkError> {
+    Ok(Entry::new(TOKEN_SERVICE, server_url)?)
+}
+
+pub fn save_token(server_url: &str, token: &str) -> Result<(), NoWorkError> {
+    token_entry(server_url)?.set_password(token)?;
+    Ok(())
+}
+
+pub fn load_token(server_url: &str) -> Result<String, NoWorkError> {
+    Ok(token_entry(server_url)?.get_password()?)
+}
+
+pub fn delete_token(server_url: &str) -> Result<(), NoWorkError> {
+    match token_entry(server_url)?.delete_credential() {
+        Ok(()) | Err(Error::NoEntry) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+enum Failure {
+    // no answer, the request can be tried again later
+    Offline(NoWorkError),
+    // the server answered with an error, retrying would not help
+    Rejected(NoWorkError),
+}
+
+// What became of a request that was not rejected
+enum Sent {
+    Done(Option<Value>),
+    Offline(NoWorkError),
+}
+
+// How a queued change shows in the cached copies of a task
+enum CachedEdit {
+    Update(Map<String, Value>),
+    Complete,
+    Delete,
+}
+
+// Tasks kept on a nowork-server. Changes made while it cannot be reached are
+// queued in tasks.db and sent in order once it answers again, reads fall
+// back to the last response the server gave.
+pub struct RemoteStore {
+    db: Database,
+    http: Client,
+    api: Url,
+    token: String,
+    // queued changes are replayed by one caller at a time
+    flushing: tokio::sync::Mutex<()>,
+}
+
+impl RemoteStore {
+    pub fn new(db: Database, server_url: &str, token: String) -> Result<Self, NoWorkError> {
+        let api = Url::parse(&format!("{}/api/", server_url.trim_end_matches('/')))
+            .ok()
+            .filter(|url| matches!(url.scheme(), "http" | "https"))
+            .ok_or_else(|| NoWorkError::validation("Enter the server address starting with http:// or https://"))?;
+        let http = Client::builder().timeout(REQUEST_TIMEOUT).build()?;
+
+        Ok(RemoteStore {
+            db,
+            http,
+            api,
+            token,
+            flushing: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    // Checks the address and token with a request that needs both
+    pub async fn check(&self) -> Result<(), NoWorkError> {
+        match self.send(Method::GET, "tags", None, None).await {
+            Ok(_) => Ok(()),
+            Err(Failure::Offline(e) | Failure::Rejected(e)) => Err(e),
+        }
+    }
+
+    // key is sent as the Idempotency-Key header, see add_task
+    async fn send(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&Value>,
+        key: Option<&str>,
+    ) -> Result<Option<Value>, Failure> {
+        let url = self
+            .api
+            .join(path)
+            .map_err(|e| Failure::Rejected(NoWorkError::validation(e.to_string())))?;
+
+        let mut request = self.http.request(method, url).bearer_auth(&self.token);
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+        if let Some(key) = key {
+            request = request.header(IDEMPOTENCY_KEY, key);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(|e| Failure::Offline(e.into()))?;
+
+        let status = response.status();
+        if matches!(
+            status,
+            StatusCode::BAD_GATEWAY | StatusCode::SERVICE_UNAVAILABLE | StatusCode::GATEWAY_TIMEOUT
+        ) {
+            return Err(Failure::Offline(NoWorkError::Network(status.to_string())));
+        }
+        if !status.is_success() {
+            let body = response.json::<Value>().await.ok();
+            return Err(Failure::Rejected(remote_error(status, body)));
+        }
+        if status == StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+
+        response
+            .json()
+            .await
+            .map(Some)
+            .map_err(|e| Failure::Rejected(NoWorkError::InvalidResponse(e.to_string())))
+    }
+
+    // Sends changes queued while offline, oldest first. Stops at the first
+    // one the server cannot be reached for and returns how many are left.
+    // Changes the server rejects stay in the queue marked as failed.
+    async fn flush_queue(&self) -> Result<i64, NoWorkError> {
+        let _flushing = self.flushing.lock().await;
+
+        loop {
+            let Some((id, method, path, body, key)) = self.db.run(|conn| next_queued(conn)).await? else {
+                return Ok(0);
+            };
+
+            let method = Method::from_bytes(method.as_bytes())
+                .map_err(|e| NoWorkError::Storage(e.to_string()))?;
+            let body = body.map(|body| serde_json::from_str::<Value>(&body)).transpose()?;
+
+            match self.send(method, &path, body.as_ref(), key.as_deref()).await {
+                Ok(_) => {
+                    self.db
+                        .run(move |conn| {
+                            conn.execute("DELETE FROM remote_queue WHERE id = ?1", params![id])?;
+                            Ok(())
+                        })
+                        .await?;
+                }
+                // e.g. the task was deleted on another device in the meantime
+                Err(Failure::Rejected(e)) => {
+                    log::warn!("The server rejected a queued change to {}: {}", path, e);
+                    let error = e.to_string();
+                    self.db.run(move |conn| mark_failed(conn, id, &error)).await?;
+                }
+                Err(Failure::Offline(_)) => return self.db.run(|conn| pending_changes(conn)).await,
+            }
+        }
+    }
+
+    // Sends a request unless older changes are still waiting for the server
+    async fn try_send(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&Value>,
+        key: Option<&str>,
+    ) -> Result<Sent, NoWorkError> {
+        if self.flush_queue().await? > 0 {
+            return Ok(Sent::Offline(NoWorkError::Network("The server cannot be reached".to_string())));
+        }
+        match self.send(method, path, body, key).await {
+            Ok(value) => Ok(Sent::Done(value)),
+            Err(Failure::Offline(e)) => Ok(Sent::Offline(e)),
+            Err(Failure::Rejected(e)) => Err(e),
+        }
+    }
+
+    // A read, answered from the cache while the server cannot be reached
+    async fn read(&self, method: Method, path: String, body: Option<Value>) -> Result<Value, NoWorkError> {
+        let key = cache_key(&path, body.as_ref());
+
+        let error = match self.try_send(method, &path, body.as_ref(), None).await? {
+            Sent::Done(value) => {
+                let value = value.unwrap_or_default();
+                let cached = value.clone();
+                self.db.run(move |conn| store_cached(conn, &key, &cached)).await?;
+                return Ok(value);
+            }
+            Sent::Offline(e) => e,
+        };
+
+        self.db.run(move |conn| cached(conn, &key)).await?.ok_or(error)
+    }
+
+    // A change without a result, queued if the server cannot be reached
+    async fn write(
+        &self,
+        method: Method,
+        path: String,
+        body: Option<Value>,
+        edit: Option<(i32, CachedEdit)>,
+    ) -> Result<(), NoWorkError> {
+        if let Sent::Done(_) = self.try_send(method.clone(), &path, body.as_ref(), None).await? {
+            return Ok(());
+        }
+
+        self.db
+            .run(move |conn| {
+                enqueue(conn, &method, &path, body.as_ref(), None)?;
+                if let Some((task_id, edit)) = edit {
+                    patch_cache(conn, task_id, &edit)?;
+                }
+                Ok(())
+            })
+            .await
+    }
+
+    // A change the server answers with the edited task. Offline the edited
+    // cached copy stands in for that answer, so a task that was never
+    // fetched cannot be edited offline.
+    async fn edit(
+        &self,
+        method: Method,
+        path: String,
+        body: Value,
+        task_id: i32,
+        changes: Map<String, Value>,
+    ) -> Result<Task, NoWorkError> {
+        let error = match self.try_send(method.clone(), &path, Some(&body), None).await? {
+            Sent::Done(task) => return Ok(serde_json::from_value(task.unwrap_or_default())?),
+            Sent::Offline(e) => e,
+        };
+
+        let task = self
+            .db
+            .run(move |conn| {
+                let Some(task) = patch_cache(conn, task_id, &CachedEdit::Update(changes))? else {
+                    return Ok(None);
+                };
+                enqueue(conn, &method, &path, Some(&body), None)?;
+                Ok(Some(task))
+            })
+            .await?
+            .ok_or(error)?;
+
+        Ok(serde_json::from_value(task)?)
+    }
+
+    // A change whose answer only the server can give, e.g. a new id
+    async fn send_now(&self, method: Method, path: &str, body: Option<&Value>) -> Result<Value, NoWorkError> {
+        match self.try_send(method, path, body, None).await? {
+            Sent::Done(value) => Ok(value.unwrap_or_default()),
+            Sent::Offline(e) => Err(e),
+        }
+    }
+}
+
+#[async_trait]
+impl TaskStore for RemoteStore {
+    async fn add_task(&self, task: NewTask) -> Result<(), NoWorkError> {
+        // The key becomes the task's uid. A request that timed out may still
+        // have added the task, sending it again with the same key does not
+        // add it a second time.
+        let key = Uuid::new_v4().simple().to_string();
+        let body = serde_json::to_value(&task)?;
+        if let Sent::Done(_) = self.try_send(Method::POST, "tasks", Some(&body), Some(&key)).await? {
+            return Ok(());
+        }
+        // shows up in lists once the server has it
+        self.db
+            .run(move |conn| enqueue(conn, &Method::POST, "tasks", Some(&body), Some(&key)))
+            .await
+    }
+
+    async fn tasks(&self, view: TaskView) -> Result<Vec<Task>, NoWorkError> {
+        let path = format!("tasks?view={}", view.as_str());
+        Ok(serde_json::from_value(self.read(Method::GET, path, None).await?)?)
+    }
+
+    async fn query_tasks(&self, query: TaskQuery) -> Result<TaskPage, NoWorkError> {
+        let body = serde_json::to_value(&query)?;
+        let page = self.read(Method::POST, "tasks/query".to_string(), Some(body)).await?;
+        Ok(serde_json::from_value(page)?)
+    }
+
+    async fn task(&self, task_id: i32) -> Result<Task, NoWorkError> {
+        let task = self.read(Method::GET, format!("tasks/{}", task_id), None).await?;
+        Ok(serde_json::from_value(task)?)
+    }
+
+    async fn update_task(&self, task_id: i32, changes: TaskChanges) -> Result<Task, NoWorkError> {
+        let body = serde_json::to_value(&changes)?;
+        let changes = match &body {
+            Value::Object(changes) => changes.clone(),
+            _ => Map::new(),
+        };
+        self.edit(Method::PATCH, format!("tasks/{}", task_id), body, task_id, changes)
+            .await
+    }
+
+    async fn complete_task(&self, task_id: i32, complete_subtasks: bool) -> Result<(), NoWorkError> {
+        let path = format!("tasks/{}/complete?completeSubtasks={}", task_id, complete_subtasks);
+        self.write(Method::POST, path, None, Some((task_id, CachedEdit::Complete)))
+            .await
+    }
+
+    async fn delete_task(&self, task_id: i32) -> Result<(), NoWorkError> {
+        self.write(Method::DELETE, format!("tasks/{}", task_id), None, Some((task_id, CachedEdit::Delete)))
+            .await
+    }
+
+    async fn tags(&self) -> Result<Vec<Tag>, NoWorkError> {
+        Ok(serde_json::from_value(self.read(Method::GET, "tags".to_string(), None).await?)?)
+    }
+
+    async fn add_tag(&self, new_tag: NewTag) -> Result<Tag, NoWorkError> {
+        let body = serde_json::to_value(&new_tag)?;
+        Ok(serde_json::from_value(self.send_now(Method::POST, "tags", Some(&body)).await?)?)
+    }
+
+    async fn remove_tag(&self, tag_name: String) -> Result<(), NoWorkError> {
+        self.write(Method::DELETE, format!("tags/{}", path_segment(&tag_name)), None, None)
+            .await
+    }
+
+    // the server knows tags by name
+    async fn update_tag_color(&self, tag_id: i32, color: String) -> Result<(), NoWorkError> {
+        let tag = self
+            .tags()
+            .await?
+            .into_iter()
+            .find(|tag| tag.id == i64::from(tag_id))
+            .ok_or(NoWorkError::TagNotFound)?;
+        let path = format!("tags/{}/color", path_segment(&tag.name));
+        self.write(Method::PUT, path, Some(serde_json::json!({ "color": color })), None)
+            .await
+    }
+
+    async fn add_tag_to_task(&self, task_id: i32, tag_id: i32) -> Result<(), NoWorkError> {
+        self.write(Method::PUT, format!("tasks/{}/tags/{}", task_id, tag_id), None, None)
+            .await
+    }
+
+    async fn remove_tag_from_task(&self, task_id: i32, tag_id: i32) -> Result<(), NoWorkError> {
+        self.write(Method::DELETE, format!("tasks/{}/tags/{}", task_id, tag_id), None, None)
+            .await
+    }
+
+    async fn add_subtask(&self, parent_id: i32, name: String) -> Result<Task, NoWorkError> {
+        let path = format!("tasks/{}/subtasks", parent_id);
+        let body = serde_json::json!({ "name": name });
+        Ok(serde_json::from_value(self.send_now(Method::POST, &path, Some(&body)).await?)?)
+    }
+
+    async fn subtasks(&self, parent_id: i32) -> Result<Vec<Task>, NoWorkError> {
+        let path = format!("tasks/{}/subtasks", parent_id);
+        Ok(serde_json::from_value(self.read(Method::GET, path, None).await?)?)
+    }
+
+    async fn reorder_subtasks(&self, parent_id: i32, subtask_ids: Vec<i32>) -> Result<Vec<Task>, NoWorkError> {
+        let path = format!("tasks/{}/subtasks", parent_id);
+        let body = serde_json::json!({ "subtaskIds": subtask_ids });
+        Ok(serde_json::from_value(self.send_now(Method::PUT, &path, Some(&body)).await?)?)
+    }
+
+    async fn set_subtask_completed(&self, subtask_id: i32, completed: bool) -> Result<Task, NoWorkError> {
+        let body = serde_json::json!({ "completed": completed });
+        let completed_at = match completed {
+            true => Value::String(Utc::now().to_rfc3339()),
+            false => Value::Null,
+        };
+        let changes = Map::from_iter([
+            ("completed".to_string(), Value::Bool(completed)),
+            ("completedAt".to_string(), completed_at),
+        ]);
+        let path = format!("subtasks/{}/completed", subtask_id);
+        self.edit(Method::PUT, path, body, subtask_id, changes).await
+    }
+
+    async fn search_tasks(&self, query: SearchQuery) -> Result<Vec<SearchResult>, NoWorkError> {
+        let body = serde_json::to_value(&query)?;
+        let results = self.read(Method::POST, "tasks/search".to_string(), Some(body)).await?;
+        Ok(serde_json::from_value(results)?)
+    }
+
+    async fn flush(&self) -> Result<i64, NoWorkError> {
+        self.flush_queue().await
+    }
+}
+
+// Turns the server's { code, category, message } back into a NoWorkError
+fn remote_error(status: StatusCode, body: Option<Value>) -> NoWorkError {
+    let field = |name: &str| {
+        body.as_ref()
+            .and_then(|body| body.get(name))
+            .and_then(Value::as_str)
+            .map(str::to_string)
+    };
+    let message = field("message").unwrap_or_else(|| status.to_string());
+
+    match field("code").as_deref() {
+        Some("task_not_found") => NoWorkError::TaskNotFound,
+        Some("subtask_not_found") => NoWorkError::SubtaskNotFound,
+        Some("tag_not_found") => NoWorkError::TagNotFound,
+        _ if status == StatusCode::UNAUTHORIZED => NoWorkError::InvalidToken,
+        _ if status.is_client_error() => NoWorkError::Validation(message),
+        _ => NoWorkError::InvalidResponse(message),
+    }
+}
+
+// Percent-encodes a tag name for use as one path segment
+fn path_segment(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn cache_key(path: &str, body: Option<&Value>) -> String {
+    match body {
+        Some(body) => format!("{} {}", path, body),
+        None => path.to_string(),
+    }
+}
+
+fn cached(conn: &Connection, key: &str) -> Result<Option<Value>, NoWorkError> {
+    let body: Option<String> = conn
+        .query_row("SELECT body FROM remote_cache WHERE key = ?1", params![key], |row| row.get(0))
+        .optional()?;
+    Ok(body.map(|body| serde_json::from_str(&body)).transpose()?)
+}
+
+fn store_cached(conn: &Connection, key: &str, value: &Value) -> Result<(), NoWorkError> {
+    conn.execute(
+        "INSERT OR REPLACE INTO remote_cache (key, body) VALUES (?1, ?2)",
+        params![key, value.to_string()],
+    )?;
+    Ok(())
+}
+
+fn is_task(value: &Value, task_id: i32) -> bool {
+    value.get("id").and_then(Value::as_i64) == Some(i64::from(task_id))
+}
+
+fn apply(task: &mut Value, edit: &CachedEdit) {
+    let Some(task) = task.as_object_mut() else { return };
+    match edit {
+        CachedEdit::Update(changes) => {
+            for (field, value) in changes {
+                task.insert(field.clone(), value.clone());
+            }
+        }
+        CachedEdit::Complete => {
+            task.insert("completed".to_string(), Value::Bool(true));
+            task.insert("completedAt".to_string(), Value::String(Utc::now().to_rfc3339()));
+        }
+        CachedEdit::Delete => {}
+    }
+}
+
+// Applies a queued change to every cached copy of the task, so reads answered
+// from the cache include it. Returns the edited task if a copy was cached.
+fn patch_cache(conn: &Connection, task_id: i32, edit: &CachedEdit) -> Result<Option<Value>, NoWorkError> {
+    let mut stmt = conn.prepare("SELECT key, body FROM remote_cache")?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let incomplete_list = format!("tasks?view={}", TaskView::Incomplete.as_str());
+    let mut edited = None;
+
+    for (key, body) in rows {
+        let mut value: Value = serde_json::from_str(&body)?;
+        // the list of incomplete tasks loses a task once it is completed
+        let drops_completed = key == incomplete_list;
+
+        // lists are arrays, query results are pages with a tasks array
+        let target = if value.get("tasks").is_some() {
+            value.get_mut("tasks")
+        } else {
+            Some(&mut value)
+        };
+
+        let changed = match target {
+            Some(Value::Array(tasks)) => {
+                let mut found = false;
+                tasks.retain_mut(|task| {
+                    if !is_task(task, task_id) {
+                        return true;
+                    }
+                    found = true;
+                    match edit {
+                        CachedEdit::Delete => false,
+                        CachedEdit::Complete if drops_completed => false,
+                        _ => {
+                            apply(task, edit);
+                            edited = Some(task.clone());
+                            true
+                        }
+                    }
+                });
+                found
+            }
+            Some(task) if is_task(task, task_id) => {
+                if let CachedEdit::Delete = edit {
+                    conn.execute("DELETE FROM remote_cache WHERE key = ?1", params![key])?;
+                    continue;
+                }
+                apply(task, edit);
+                edited = Some(task.clone());
+                true
+            }
+            _ => false,
+        };
+
+        if changed {
+            store_cached(conn, &key, &value)?;
+        }
+    }
+
+    Ok(edited)
+}
+
+fn enqueue(
+    conn: &Connection,
+    method: &Method,
+    path: &str,
+    body: Option<&Value>,
+    key: Option<&str>,
+) -> Result<(), NoWorkError> {
+    conn.execute(
+        "INSERT INTO remote_queue (method, path, body, idempotency_key) VALUES (?1, ?2, ?3, ?4)",
+        params![method.as_str(), path, body.map(Value::to_string), key],
+    )?;
+    Ok(())
+}
+
+type QueuedChange = (i64, String, String, Option<String>, Option<String>);
+
+fn next_queued(conn: &Connection) -> Result<Option<QueuedChange>, NoWorkError> {
+    Ok(conn
+        .query_row(
+            "SELECT id, method, path, body, idempotency_key FROM remote_queue
+             WHERE failed_at IS NULL ORDER BY id LIMIT 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+        )
+        .optional()?)
+}
+
+fn mark_failed(conn: &Connection, id: i64, error: &str) -> Result<(), NoWorkError> {
+    conn.execute(
+        "UPDATE remote_queue SET failed_at = strftime('%Y-%m-%dT%H:%M:%fZ', 'now'), error = ?1 WHERE id = ?2",
+        params![error, id],
+    )?;
+    Ok(())
+}
+
+// Changes still waiting to be sent, failed ones not included
+pub fn pending_changes(conn: &Connection) -> Result<i64, NoWorkError> {
+    Ok(conn.query_row("SELECT COUNT(*) FROM remote_queue WHERE failed_at IS NULL", [], |row| row.get(0))?)
+}
+
+// A queued change the server rejected, kept until it is retried or discarded
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FailedChange {
+    pub id: i64,
+    pub method: String,
+    pub path: String,
+    pub body: Option<String>,
+    pub error: String,
+    pub queued_at: String,
+    pub failed_at: String,
+}
+
+pub fn failed_changes(conn: &Connection) -> Result<Vec<FailedChange>, NoWorkError> {
+    let mut stmt = conn.prepare(
+        "SELECT id, method, path, body, error, queued_at, failed_at FROM remote_queue
+         WHERE failed_at IS NOT NULL ORDER BY id",
+    )?;
+    let changes = stmt
+        .query_map([], |row| {
+            Ok(FailedChange {
+                id: row.get(0)?,
+                method: row.get(1)?,
+                path: row.get(2)?,
+                body: row.get(3)?,
+                error: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+                queued_at: row.get(5)?,
+                failed_at: row.get(6)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(changes)
+}
+
+// Puts a failed change back in line, it is sent again in its original order
+pub fn retry_failed(conn: &Connection, id: i64) -> Result<(), NoWorkError> {
+    let updated = conn.execute(
+        "UPDATE remote_queue SET failed_at = NULL, error = NULL WHERE id = ?1 AND failed_at IS NOT NULL",
+        params![id],
+    )?;
+    if updated == 0 {
+        return Err(NoWorkError::validation("This change is no longer waiting to be retried"));
+    }
+    Ok(())
+}
+
+// Drops a failed change for good, only ever at the user's request
+pub fn discard_failed(conn: &Connection, id: i64) -> Result<(), NoWorkError> {
+    conn.execute(
+        "DELETE FROM remote_queue WHERE id = ?1 AND failed_at IS NOT NULL",
+        params![id],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{header, header_exists, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    #[tokio::test]
+    async fn rejected_changes_stay_queued_as_failed() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = nowork_core::database::open(dir.path().join("tasks.db"), &dir.path().join("backups")).unwrap();
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .and(path("/api/tasks/1"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(serde_json::json!({
+                "code": "not_found",
+                "category": "not_found",
+                "message": "Task not found",
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("DELETE"))
+            .and(path("/api/tasks/2"))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        db.run(|conn| {
+            enqueue(conn, &Method::DELETE, "tasks/1", None, None)?;
+            enqueue(conn, &Method::DELETE, "tasks/2", None, None)
+        })
+        .await
+        .unwrap();
+
+        let store = RemoteStore::new(db.clone(), &server.uri(), "token".to_string()).unwrap();
+        // the rejected change does not hold up the ones after it
+        assert_eq!(store.flush().await.unwrap(), 0);
+
+        let failed = db.run(|conn| failed_changes(conn)).await.unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].path, "tasks/1");
+        assert!(failed[0].error.contains("Task not found"), "{}", failed[0].error);
+
+        let id = failed[0].id;
+        db.run(move |conn| retry_failed(conn, id)).await.unwrap();
+        assert_eq!(db.run(|conn| pending_changes(conn)).await.unwrap(), 1);
+        assert!(db.run(|conn| failed_changes(conn)).await.unwrap().is_empty());
+
+        db.run(move |conn| mark_failed(conn, id, "again")).await.unwrap();
+        db.run(move |conn| discard_failed(conn, id)).await.unwrap();
+        assert_eq!(db.run(|conn| pending_changes(conn)).await.unwrap(), 0);
+        assert!(db.run(|conn| failed_changes(conn)).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn new_tasks_keep_their_idempotency_key_while_queued() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = nowork_core::database::open(dir.path().join("tasks.db"), &dir.path().join("backups")).unwrap();
+        let server = MockServer::start().await;
+        let store = RemoteStore::new(db.clone(), &server.uri(), "token".to_string()).unwrap();
+        let task = NewTask {
+            name: "Renew passport".to_string(),
+            due_date: None,
+            priority: None,
+            tags: None,
+            recurrence: None,
+        };
+
+        {
+            let _unavailable = Mock::given(method("POST"))
+                .and(path("/api/tasks"))
+                .and(header_exists(IDEMPOTENCY_KEY))
+                .respond_with(ResponseTemplate::new(503))
+                .expect(1)
+                .mount_as_scoped(&server)
+                .await;
+            store.add_task(task).await.unwrap();
+        }
+        let key: String = db
+            .run(|conn| Ok(conn.query_row("SELECT idempotency_key FROM remote_queue", [], |row| row.get(0))?))
+            .await
+            .unwrap();
+        assert_eq!(key.len(), 32);
+
+        Mock::given(method("POST"))
+            .and(path("/api/tasks"))
+            .and(header(IDEMPOTENCY_KEY, key.as_str()))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({})))
+            .expect(1)
+            .mount(&server)
+            .await;
+        assert_eq!(store.flush().await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,71 @@
+import { invoke } from "@tauri-apps/api/core";
+import type { CalendarInfo } from "./calendar";
+
+export type CalendarProvider = "icloud" | "fastmail" | "caldav";
+
+export interface CalendarAccount {
+    id: number;
+    username: string;
+    provider: CalendarProvider;
+    serverUrl: string;
+    // the calendar tasks are synced with, if any
+    taskCalendarUrl: string | null;
+}
+
+export const providerOptions: { value: CalendarProvider, label: string }[] = [
+    { value: "icloud", label: "iCloud" },
+    { value: "fastmail", label: "Fastmail" },
+    { value: "caldav", label: "Other CalDAV server" },
+];
+
+export async function listAccounts(): Promise<CalendarAccount[]> {
+    return await invoke<CalendarAccount[]>("list_calendar_accounts");
+}
+
+// Checks the password, saves the account and syncs it, serverUrl is only
+// used for "caldav"
+export async function addAccount(provider: CalendarProvider, serverUrl: string | null, username: string, password: string): Promise<CalendarInfo[]> {
+    return await invoke<CalendarInfo[]>("add_calendar_account", { provider, serverUrl, username, password });
+}
+
+// Signs out, the password and everything synced from the account are removed
+export async function removeAccount(account: number): Promise<void> {
+    await invoke("remove_calendar_account", { account });
+}
+
+// The account's calendars as of the last sync
+export async function listCalendars(account: number): Promise<CalendarInfo[]> {
+    return await invoke<CalendarInfo[]>("list_calendars", { account });
+}
+
+// Disabled calendars are hidden and not synced
+export async function setCalendarEnabled(account: number, calendarUrl: string, enabled: boolean): Promise<void> {
+    await invoke("set_calendar_enabled", { account, calendarUrl, enabled });
+}
+
+// id of the built-in account subscribed feeds and imported files belong to
+export const LOCAL_ACCOUNT = 0;
+
+export const refreshOptions: { value: string, label: string }[] = [
+    { value: "60", label: "Every hour" },
+    { value: "360", label: "Every 6 hours" },
+    { value: "1440", label: "Every day" },
+    { value: "10080", label: "Every week" },
+];
+
+// Subscribes to a read-only ICS feed (https:// or webcal://), refreshed with
+// the other calendars once refreshMinutes have passed
+export async function subscribeCalendar(url: string, refreshMinutes: number | null): Promise<CalendarInfo> {
+    return await invoke<CalendarInfo>("subscribe_calendar", { url, refreshMinutes });
+}
+
+// Imports a .ics file as a calendar of its own, named after the file unless it names itself
+export async function importCalendar(file: File): Promise<CalendarInfo> {
+    const name = file.name.replace(/\.ics$/i, "");
+    return await invoke<CalendarInfo>("import_calendar", { name, data: await file.text() });
+}
+
+// Unsubscribes from a feed or drops an imported file
+export async function removeLocalCalendar(url: string): Promise<void> {
+    await invoke("remove_local_calendar", { url });
+}
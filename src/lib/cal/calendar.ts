@@ -1,3 +1,6 @@
+import { invoke } from "@tauri-apps/api/core";
+import type { TaskQuery } from "$lib/types/filter";
+
 export interface CalendarEvent {
     summary: string;
     start: string;
@@ -5,6 +8,167 @@ export interface CalendarEvent {
     uid: string;
     description: string | null;
     location: string | null;
+    // start and end are plain dates ("2026-01-19"), end is the day after the last one
+    allDay: boolean;
+    // the resource the event is stored in, what it is edited and deleted by
+    href: string;
+    // color of the calendar the event is in, "#RRGGBB"
+    color: string | null;
+}
+
+// An event as the editor sees it, start and end are written like those of CalendarEvent
+export interface EventDraft {
+    summary: string;
+    start: string;
+    end: string;
+    allDay: boolean;
+    // IANA zone, e.g. "Europe/Berlin", null writes the times in UTC
+    timeZone: string | null;
+    description: string | null;
+    location: string | null;
+    // RRULE value, e.g. "FREQ=WEEKLY;BYDAY=MO,WE"
+    recurrence: string | null;
+    // reminders, in minutes before the start
+    alarms: number[];
+}
+
+export interface CalendarInfo {
+    url: string;
+    displayName: string | null;
+    color: string | null;
+    ctag: string | null;
+    syncToken: string | null;
+    components: string[];
+    // disabled calendars are hidden and not synced
+    enabled: boolean;
+}
+
+export interface TaskSyncReport {
+    pushed: number;
+    pulled: number;
+    deleted: number;
+    // names of tasks edited on both sides, the server copy was kept
+    conflicts: string[];
+}
+
+export interface DateRange {
+    start: Date;
+    end: Date;
+}
+
+function startOfDay(date: Date): Date {
+    const d = new Date(date);
+    d.setHours(0, 0, 0, 0);
+    return d;
+}
+
+function addDays(date: Date, days: number): Date {
+    const d = new Date(date);
+    d.setDate(d.getDate() + days);
+    return d;
+}
+
+// The week containing date, from local midnight on its first day
+export function weekRange(date: Date, weekStartsOn: 0 | 1 = 0): DateRange {
+    const day = startOfDay(date);
+    const start = addDays(day, -((day.getDay() - weekStartsOn + 7) % 7));
+    return { start, end: addDays(start, 7) };
+}
+
+// Every day shown in a month grid, padded to whole weeks on both sides
+export function monthRange(date: Date, weekStartsOn: 0 | 1 = 0): DateRange {
+    const first = new Date(date.getFullYear(), date.getMonth(), 1);
+    const last = new Date(date.getFullYear(), date.getMonth() + 1, 0);
+    return {
+        start: weekRange(first, weekStartsOn).start,
+        end: weekRange(last, weekStartsOn).end,
+    };
+}
+
+// The next `days` days starting today, for the agenda list
+export function agendaRange(date: Date, days = 30): DateRange {
+    const start = startOfDay(date);
+    return { start, end: addDays(start, days) };
+}
+
+// Events of the last synced enabled calendars overlapping the range, with
+// recurring events expanded. null takes every account. Works offline.
+export async function fetchEvents(account: number | null, range: DateRange): Promise<CalendarEvent[]> {
+    return await invoke<CalendarEvent[]>("fetch_events", {
+        account,
+        start: range.start.toISOString(),
+        end: range.end.toISOString(),
+    });
+}
+
+// Pulls what changed on the server into the local cache, null syncs every account
+export async function syncCalendars(account: number | null): Promise<CalendarInfo[]> {
+    return await invoke<CalendarInfo[]>("sync_calendars", { account });
+}
+
+// Calendars that can hold tasks, an empty component list accepts anything
+export function taskCalendars(calendars: CalendarInfo[]): CalendarInfo[] {
+    return calendars.filter(c => c.components.length === 0 || c.components.includes("VTODO"));
+}
+
+export async function getTaskCalendar(account: number): Promise<string | null> {
+    return await invoke<string | null>("get_task_calendar", { account });
+}
+
+// null stops syncing tasks
+export async function setTaskCalendar(account: number, calendarUrl: string | null): Promise<void> {
+    await invoke("set_task_calendar", { account, calendarUrl });
+}
+
+// Two-way sync of tasks with the chosen task list
+export async function syncTasks(account: number): Promise<TaskSyncReport> {
+    return await invoke<TaskSyncReport>("sync_tasks", { account });
+}
+
+// Writes the tasks matching query (all of them if null) to an .ics file.
+// asEvents writes tasks with a due date as events at that time, for calendar
+// apps that ignore tasks. Returns how many tasks were written.
+export async function exportTasks(path: string, query: TaskQuery | null, asEvents = false): Promise<number> {
+    return await invoke<number>("export_tasks", { path, query, asEvents });
+}
+
+// Calendars that can hold events, an empty component list accepts anything
+export function eventCalendars(calendars: CalendarInfo[]): CalendarInfo[] {
+    return calendars.filter(c => c.components.length === 0 || c.components.includes("VEVENT"));
+}
+
+// An empty draft starting at start, in the user's time zone
+export function newEventDraft(start: Date, minutes = 60): EventDraft {
+    return {
+        summary: "",
+        start: start.toISOString(),
+        end: new Date(start.getTime() + minutes * 60_000).toISOString(),
+        allDay: false,
+        timeZone: Intl.DateTimeFormat().resolvedOptions().timeZone ?? null,
+        description: null,
+        location: null,
+        recurrence: null,
+        alarms: [],
+    };
+}
+
+// Adds an event to a calendar and returns its href
+export async function createEvent(account: number, calendarUrl: string, event: EventDraft): Promise<string> {
+    return await invoke<string>("create_event", { account, calendarUrl, event });
+}
+
+export async function getEvent(account: number, href: string): Promise<EventDraft> {
+    return await invoke<EventDraft>("get_event", { account, href });
+}
+
+// Fails with code "edit_conflict" if the event changed elsewhere since the last sync
+export async function updateEvent(account: number, href: string, event: EventDraft): Promise<void> {
+    await invoke("update_event", { account, href, event });
+}
+
+// Deletes the event with all of its occurrences
+export async function deleteEvent(account: number, href: string): Promise<void> {
+    await invoke("delete_event", { account, href });
 }
 
 export function getTime(date: string): number {
@@ -0,0 +1,27 @@
+import { invoke } from "@tauri-apps/api/core";
+import type { NewTag, Recurrence, TaskPriority } from "$lib/types/task";
+
+// Mirrors QuickAdd in src-tauri/core/src/quick_add.rs
+export type QuickAddKind = "due" | "priority" | "tag" | "recurrence";
+
+export interface QuickAddMatch {
+    kind: QuickAddKind;
+    // the words as typed, e.g. "fri 5pm"
+    text: string;
+}
+
+export interface QuickAdd {
+    // what is left of the input once dates, tags and the rest are taken out
+    name: string;
+    dueDate: string | null;
+    priority: TaskPriority;
+    tags: NewTag[];
+    recurrence: Recurrence | null;
+    matches: QuickAddMatch[];
+}
+
+// Reads "Submit report fri 5pm #work !high" into the parts of a new task,
+// dates are relative to the local time
+export async function parseQuickAdd(text: string): Promise<QuickAdd> {
+    return await invoke<QuickAdd>("parse_quick_add", { text });
+}
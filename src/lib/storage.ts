@@ -0,0 +1,47 @@
+import { invoke } from "@tauri-apps/api/core";
+
+// Mirrors StorageStatus in src-tauri/src/storage/mod.rs
+export interface StorageStatus {
+    // null while tasks are kept on this device
+    serverUrl: string | null;
+    // changes made offline that have not reached the server yet
+    pendingChanges: number;
+    // changes the server rejected, kept until they are retried or discarded
+    failedChanges: FailedChange[];
+}
+
+// Mirrors FailedChange in src-tauri/src/storage/remote.rs
+export interface FailedChange {
+    id: number;
+    method: string;
+    path: string;
+    body: string | null;
+    error: string;
+    queuedAt: string;
+    failedAt: string;
+}
+
+export async function getStorageStatus(): Promise<StorageStatus> {
+    return await invoke<StorageStatus>("get_storage_status");
+}
+
+// Keeps tasks on a nowork-server from now on, the address and token are checked first
+export async function connectToServer(serverUrl: string, token: string): Promise<StorageStatus> {
+    return await invoke<StorageStatus>("use_remote_storage", { serverUrl, token });
+}
+
+// Goes back to the tasks on this device, refused while changes are still
+// waiting for the server unless discardPending is set
+export async function disconnectFromServer(discardPending = false): Promise<StorageStatus> {
+    return await invoke<StorageStatus>("use_local_storage", { discardPending });
+}
+
+// Sends a change the server rejected once more
+export async function retryFailedChange(changeId: number): Promise<StorageStatus> {
+    return await invoke<StorageStatus>("retry_failed_change", { changeId });
+}
+
+// Gives up on a change the server rejected
+export async function discardFailedChange(changeId: number): Promise<StorageStatus> {
+    return await invoke<StorageStatus>("discard_failed_change", { changeId });
+}
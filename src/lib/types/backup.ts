@@ -0,0 +1,28 @@
+export type BackupKind = "manual" | "daily" | "preReset" | "preRestore" | "preMigration" | "preImport";
+
+// Mirrors BackupInfo in src-tauri/core/src/types.rs
+export interface BackupInfo {
+  fileName: string;
+  kind: BackupKind;
+  createdAt: string;
+  sizeBytes: number;
+}
+
+export type ImportMode = "merge" | "replace";
+
+export interface ImportRecordError {
+  section: "tags" | "tasks" | "taskTags";
+  // position of the record within its section
+  index: number;
+  id?: number | null;
+  message: string;
+}
+
+// Returned by import_workspace
+export interface ImportReport {
+  tagsCreated: number;
+  tagsMerged: number;
+  tasksImported: number;
+  linksImported: number;
+  errors: ImportRecordError[];
+}
@@ -0,0 +1,39 @@
+// Mirrors NoWorkError in src-tauri/core/src/error.rs, every command rejects with this shape
+export type ErrorCategory = "notFound" | "validation" | "storage" | "network" | "auth";
+
+export type ErrorCode =
+  | "task_not_found"
+  | "subtask_not_found"
+  | "tag_not_found"
+  | "backup_not_found"
+  | "credentials_not_found"
+  | "account_not_found"
+  | "event_not_found"
+  | "invalid_input"
+  | "invalid_recurrence"
+  | "database_locked"
+  | "schema_too_new"
+  | "storage_failed"
+  | "network_failed"
+  | "invalid_response"
+  | "auth_failed"
+  | "invalid_token"
+  | "keyring_failed"
+  | "edit_conflict";
+
+export interface NoWorkError {
+  code: ErrorCode;
+  category: ErrorCategory;
+  message: string;
+}
+
+export function isNoWorkError(err: unknown): err is NoWorkError {
+  return typeof err === "object" && err !== null && "code" in err && "category" in err && "message" in err;
+}
+
+// User facing text for anything caught from invoke()
+export function errorMessage(err: unknown): string {
+  if (isNoWorkError(err)) return err.message;
+  if (err instanceof Error) return err.message;
+  return String(err);
+}
@@ -5,17 +5,46 @@ export type TaskFilter = {
     priorities: TaskPriority[]
 }
 
-export function matchesFilter(task: Task, filter: TaskFilter): boolean {
-    // first, check if there even if a filtered tag
-    const matchesTags =
-        filter.tags.length === 0 ||
-        filter.tags.some(filterTag =>
-            task.tags?.some(taskTag => taskTag.id === filterTag.id) ?? false
-        );
+// start is inclusive, end is exclusive, both ISO strings
+export interface DateRange {
+    start?: string | null;
+    end?: string | null;
+}
+
+export type TaskSortKey = "id" | "name" | "dueDate" | "createdAt" | "completedAt" | "priority";
+
+export interface TaskSort {
+    key: TaskSortKey;
+    descending?: boolean;
+}
 
-    const matchesPriority = 
-        filter.priorities.length === 0 ||
-        filter.priorities.includes(task.priority);
+// mirrors TaskQuery in src-tauri/core/src/types.rs, every field is optional
+export interface TaskQuery {
+    tags?: { any?: number[], all?: number[], none?: number[] };
+    priorities?: TaskPriority[];
+    due?: DateRange | null;
+    hasDueDate?: boolean | null;
+    overdue?: boolean | null;
+    completed?: boolean | null;
+    created?: DateRange | null;
+    completedBetween?: DateRange | null;
+    includeSubtasks?: boolean;
+    sort?: TaskSort[];
+    limit?: number | null;
+    offset?: number | null;
+}
+
+export interface TaskPage {
+    tasks: Task[];
+    total: number;
+}
 
-    return matchesTags && matchesPriority;
-}
\ No newline at end of file
+// incomplete tasks matching any selected tag and any selected priority, soonest due first
+export function toTaskQuery(filter: TaskFilter): TaskQuery {
+    return {
+        completed: false,
+        tags: { any: filter.tags.map(tag => tag.id) },
+        priorities: filter.priorities,
+        sort: [{ key: "dueDate" }],
+    };
+}
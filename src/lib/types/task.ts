@@ -10,6 +10,14 @@ export const taskPriorityOptions = [
 
 export type TaskPriority = "low" | "medium" | "high" | null;
 
+export type RecurrenceMode = "fixedSchedule" | "afterCompletion";
+
+export interface Recurrence {
+  // RFC 5545 RRULE value, e.g. "FREQ=DAILY" or "FREQ=WEEKLY;BYDAY=MO"
+  rule: string;
+  mode: RecurrenceMode;
+}
+
 export interface Task {
   id: number;
   name: string;
@@ -19,6 +27,15 @@ export interface Task {
   completedAt?: string | null;
   priority: TaskPriority;
   tags?: Tag[] | null;
+  recurrence?: Recurrence | null;
+  parentId?: number | null;
+  position: number;
+  subtaskProgress?: SubtaskProgress | null;
+}
+
+export interface SubtaskProgress {
+  completed: number;
+  total: number;
 }
 export interface Tag {
   id: number;
@@ -31,6 +48,21 @@ export interface NewTag {
   color: TagColor
 }
 
+export interface SearchQuery {
+  text: string;
+  tagIds?: number[];
+  priorities?: TaskPriority[];
+  completed?: boolean | null;
+  limit?: number | null;
+}
+
+export interface SearchResult {
+  task: Task;
+  // escaped task name with matches wrapped in <mark></mark>, safe to render as html
+  snippet: string;
+  rank: number;
+}
+
 export type TagColor = keyof typeof flavorMap;
 
 export async function getTasksDueToday(): Promise<Task[]> {